@@ -0,0 +1,127 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Unit used by the trigonometric functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    #[default]
+    Degrees,
+    Radians,
+    Gradians,
+}
+
+impl AngleMode {
+    /// "deg", "rad", "grad" and their long names
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "deg" | "degree" | "degrees" => Some(AngleMode::Degrees),
+            "rad" | "radian" | "radians" => Some(AngleMode::Radians),
+            "grad" | "gradian" | "gradians" => Some(AngleMode::Gradians),
+            _ => None,
+        }
+    }
+
+    /// Converts an angle in this unit to radians: the argument of sin, cos, ...
+    pub fn angle_to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Degrees => angle.to_radians(),
+            AngleMode::Radians => angle,
+            AngleMode::Gradians => angle * PI / 200.0,
+        }
+    }
+
+    /// Converts radians to this unit: the result of arcsin, arctan, ...
+    pub fn radians_to_angle(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Degrees => angle.to_degrees(),
+            AngleMode::Radians => angle,
+            AngleMode::Gradians => angle * 200.0 / PI,
+        }
+    }
+}
+
+impl fmt::Display for AngleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AngleMode::Degrees => write!(f, "degrees"),
+            AngleMode::Radians => write!(f, "radians"),
+            AngleMode::Gradians => write!(f, "gradians"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::resolver::MapOnlyResolver;
+    use super::super::{EvalSettings, Evaluator, VariableManager};
+    use super::*;
+    use crate::utils::print::lex_parse_input;
+
+    const MODES: [AngleMode; 3] = [AngleMode::Degrees, AngleMode::Radians, AngleMode::Gradians];
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-12 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn names_of_the_units() {
+        assert_eq!(AngleMode::parse("deg"), Some(AngleMode::Degrees));
+        assert_eq!(AngleMode::parse("Radians"), Some(AngleMode::Radians));
+        assert_eq!(AngleMode::parse("GRAD"), Some(AngleMode::Gradians));
+        assert_eq!(AngleMode::parse("turns"), None);
+        assert_eq!(AngleMode::default(), AngleMode::Degrees);
+        for mode in MODES {
+            assert_eq!(AngleMode::parse(&mode.to_string()), Some(mode));
+        }
+    }
+
+    #[test]
+    fn a_right_angle_in_every_unit() {
+        let right = [
+            (AngleMode::Degrees, 90.0),
+            (AngleMode::Radians, PI / 2.0),
+            (AngleMode::Gradians, 100.0),
+        ];
+        for (mode, angle) in right {
+            assert!(close(mode.angle_to_radians(angle), PI / 2.0), "{}", mode);
+            assert!(close(mode.radians_to_angle(PI / 2.0), angle), "{}", mode);
+        }
+    }
+
+    #[test]
+    fn conversions_are_inverse() {
+        for mode in MODES {
+            for angle in [-720.0, -1.5, 0.0, 0.25, 33.0, 400.0] {
+                let back = mode.radians_to_angle(mode.angle_to_radians(angle));
+                assert!(close(back, angle), "{} {}", mode, angle);
+            }
+        }
+    }
+
+    #[test]
+    fn trigonometric_functions_use_the_unit() {
+        let evaluate = |input: &str, angle_mode: AngleMode| {
+            let mut vars = VariableManager::new();
+            let mut resolver = MapOnlyResolver;
+            let settings = EvalSettings {
+                angle_mode,
+                ..EvalSettings::default()
+            };
+            Evaluator::new(&mut vars, &mut resolver)
+                .with_settings(settings)
+                .evaluate(&lex_parse_input(input).unwrap())
+                .unwrap()
+        };
+
+        assert!(close(evaluate("sin(30)", AngleMode::Degrees), 0.5));
+        assert!(close(evaluate("cos(pi)", AngleMode::Radians), -1.0));
+        assert!(close(evaluate("tan(50)", AngleMode::Gradians), 1.0));
+        assert!(close(evaluate("arcsin(1)", AngleMode::Degrees), 90.0));
+        assert!(close(evaluate("arccos(-1)", AngleMode::Gradians), 200.0));
+        assert!(close(evaluate("atan2(1, 1)", AngleMode::Radians), PI / 4.0));
+        // Hyperbolic functions do not take an angle
+        for mode in MODES {
+            assert!(close(evaluate("sinh(1)", mode), 1f64.sinh()));
+        }
+    }
+}
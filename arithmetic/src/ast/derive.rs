@@ -0,0 +1,682 @@
+use super::{
+    ASTNode, AngleMode, CompilerError, EvaluationError, Num, TextSpan, TokenKind, VariableManager,
+};
+use crate::builtins::Builtin;
+use std::collections::BTreeSet;
+
+/// Symbolic differentiation. Built-in functions use the chain rule, trigonometric
+/// functions include the factor of the angle unit: d/dx sin(x) = (π/180)·cos(x) in degrees.
+impl ASTNode {
+    /// Derivative with respect to var. Calls of user functions must be inlined first,
+    /// see `inline_functions`; the ones that are left are undefined functions.
+    pub fn derivative_in(
+        &self,
+        var: &str,
+        angle_mode: AngleMode,
+    ) -> Result<ASTNode, CompilerError> {
+        let var = var.to_lowercase();
+        self.derive(&var, angle_mode)
+    }
+
+    /// true if the value of the node changes with var
+    pub fn depends_on(&self, var: &str) -> bool {
+        match self {
+            ASTNode::Identifier(name, _) => name.to_lowercase() == var.to_lowercase(),
+            ASTNode::BinaryOp(left, _, right, _) => left.depends_on(var) || right.depends_on(var),
+            ASTNode::UnaryOp(_, expr, _) => expr.depends_on(var),
+            ASTNode::FunctionCall(_, args, _) => args.iter().any(|arg| arg.depends_on(var)),
+            ASTNode::Number(_, _)
+            | ASTNode::Mantissa(_, _)
+            | ASTNode::Constant(_, _)
+            | ASTNode::Error(_) => false,
+        }
+    }
+
+    /// Lower case names of the variables, sorted
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables(&self, names: &mut BTreeSet<String>) {
+        match self {
+            ASTNode::Identifier(id, _) => {
+                names.insert(id.to_lowercase());
+            }
+            ASTNode::BinaryOp(left, _, right, _) => {
+                left.collect_variables(names);
+                right.collect_variables(names);
+            }
+            ASTNode::UnaryOp(_, expr, _) => expr.collect_variables(names),
+            ASTNode::FunctionCall(_, args, _) => {
+                for arg in args.iter() {
+                    arg.collect_variables(names);
+                }
+            }
+            ASTNode::Number(_, _)
+            | ASTNode::Mantissa(_, _)
+            | ASTNode::Constant(_, _)
+            | ASTNode::Error(_) => {}
+        }
+    }
+
+    /// Replaces the calls of user functions by their bodies. A function that calls
+    /// itself, directly or through other functions, can not be inlined.
+    pub fn inline_functions(&self, vars: &VariableManager) -> Result<ASTNode, CompilerError> {
+        self.inline(vars, &mut Vec::new())
+    }
+
+    /// `inlining` holds the lower case names of the functions whose body is being inlined
+    fn inline(
+        &self,
+        vars: &VariableManager,
+        inlining: &mut Vec<String>,
+    ) -> Result<ASTNode, CompilerError> {
+        Ok(match self {
+            ASTNode::BinaryOp(left, op, right, span) => ASTNode::BinaryOp(
+                Box::new(left.inline(vars, inlining)?),
+                op.clone(),
+                Box::new(right.inline(vars, inlining)?),
+                span.clone(),
+            ),
+            ASTNode::UnaryOp(op, expr, span) => ASTNode::UnaryOp(
+                op.clone(),
+                Box::new(expr.inline(vars, inlining)?),
+                span.clone(),
+            ),
+            ASTNode::FunctionCall(name, args, span) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.inline(vars, inlining))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match vars.get_function(name) {
+                    // A wrong number of arguments is reported when the call is evaluated
+                    Some(function) if function.params.len() == args.len() => {
+                        let key = name.to_lowercase();
+                        if inlining.contains(&key) {
+                            return Err(CompilerError::Eval(EvaluationError::RecursionLimit(
+                                name.clone(),
+                                span.clone(),
+                            )));
+                        }
+
+                        // Parameters are replaced at once so arguments are not substituted again
+                        let body = function.body.map_identifiers(&|id, span| match function
+                            .params
+                            .iter()
+                            .position(|param| *param == id.to_lowercase())
+                        {
+                            Some(i) => args[i].clone(),
+                            None => ASTNode::Identifier(id.to_string(), span.clone()),
+                        });
+
+                        inlining.push(key);
+                        let inlined = body.inline(vars, inlining);
+                        inlining.pop();
+                        inlined?
+                    }
+                    _ => ASTNode::FunctionCall(name.clone(), args, span.clone()),
+                }
+            }
+            _ => self.clone(),
+        })
+    }
+
+    fn map_identifiers(&self, f: &dyn Fn(&str, &TextSpan) -> ASTNode) -> ASTNode {
+        match self {
+            ASTNode::Identifier(id, span) => f(id, span),
+            ASTNode::BinaryOp(left, op, right, span) => ASTNode::BinaryOp(
+                Box::new(left.map_identifiers(f)),
+                op.clone(),
+                Box::new(right.map_identifiers(f)),
+                span.clone(),
+            ),
+            ASTNode::UnaryOp(op, expr, span) => {
+                ASTNode::UnaryOp(op.clone(), Box::new(expr.map_identifiers(f)), span.clone())
+            }
+            ASTNode::FunctionCall(name, args, span) => ASTNode::FunctionCall(
+                name.clone(),
+                args.iter().map(|arg| arg.map_identifiers(f)).collect(),
+                span.clone(),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    fn derive(&self, var: &str, mode: AngleMode) -> Result<ASTNode, CompilerError> {
+        let span = self.span().clone();
+
+        // Constants, and everything that does not contain var
+        if !self.depends_on(var) {
+            return Ok(match self {
+                ASTNode::Error(_) => self.clone(),
+                _ => number(0, &span),
+            });
+        }
+
+        match self {
+            ASTNode::Identifier(_, _) => Ok(number(1, &span)),
+            ASTNode::UnaryOp(TokenKind::Minus, expr, _) => {
+                Ok(negate(expr.derive(var, mode)?, &span))
+            }
+            ASTNode::UnaryOp(_, expr, _) => expr.derive(var, mode),
+            ASTNode::BinaryOp(left, op, right, _) => {
+                derive_binary(left, op, right, var, mode, &span)
+            }
+            ASTNode::FunctionCall(name, args, _) => derive_call(name, args, var, mode, &span),
+            _ => Ok(number(0, &span)),
+        }
+    }
+}
+
+fn derive_binary(
+    u: &ASTNode,
+    op: &TokenKind,
+    v: &ASTNode,
+    var: &str,
+    mode: AngleMode,
+    span: &TextSpan,
+) -> Result<ASTNode, CompilerError> {
+    let du = u.derive(var, mode)?;
+    let dv = v.derive(var, mode)?;
+
+    Ok(match op {
+        TokenKind::Plus => add(du, dv, span),
+        TokenKind::Minus => sub(du, dv, span),
+        // (uv)' = u'v + uv'
+        TokenKind::Multiply => add(mul(du, v.clone(), span), mul(u.clone(), dv, span), span),
+        // (u/v)' = (u'v - uv') / v^2
+        TokenKind::Divide => div(
+            sub(mul(du, v.clone(), span), mul(u.clone(), dv, span), span),
+            pow(v.clone(), number(2, span), span),
+            span,
+        ),
+        TokenKind::Power => derive_power(u, v, du, dv, var, span),
+        // u mod v = u - v·floor(u/v), floor is constant between its jumps
+        TokenKind::Mod => sub(
+            du,
+            mul(
+                dv,
+                call("floor", vec![div(u.clone(), v.clone(), span)], span),
+                span,
+            ),
+            span,
+        ),
+        // Integer division is constant between its jumps
+        _ => number(0, span),
+    })
+}
+
+/// (u^v)' for a constant exponent, a constant base or both depending on var
+fn derive_power(
+    u: &ASTNode,
+    v: &ASTNode,
+    du: ASTNode,
+    dv: ASTNode,
+    var: &str,
+    span: &TextSpan,
+) -> ASTNode {
+    let power = pow(u.clone(), v.clone(), span);
+
+    if !v.depends_on(var) {
+        // v·u^(v-1)·u'
+        let exponent = sub(v.clone(), number(1, span), span);
+        return mul(
+            mul(v.clone(), pow(u.clone(), exponent, span), span),
+            du,
+            span,
+        );
+    }
+    if !u.depends_on(var) {
+        // u^v·ln(u)·v'
+        return mul(
+            mul(power, call("ln", vec![u.clone()], span), span),
+            dv,
+            span,
+        );
+    }
+
+    // u^v·(v'·ln(u) + v·u'/u)
+    let inner = add(
+        mul(dv, call("ln", vec![u.clone()], span), span),
+        div(mul(v.clone(), du, span), u.clone(), span),
+        span,
+    );
+    mul(power, inner, span)
+}
+
+fn derive_call(
+    name: &str,
+    args: &[ASTNode],
+    var: &str,
+    mode: AngleMode,
+    span: &TextSpan,
+) -> Result<ASTNode, CompilerError> {
+    // Calls of user functions that were not inlined, and wrong numbers of arguments
+    let builtin = Builtin::lookup(name).ok_or_else(|| {
+        CompilerError::Eval(EvaluationError::UndefinedFunction(
+            name.to_string(),
+            span.clone(),
+        ))
+    })?;
+    if !builtin.accepts(args.len()) {
+        return Err(CompilerError::Eval(EvaluationError::ArityMismatch(
+            name.to_string(),
+            builtin.expected_args(),
+            args.len(),
+            span.clone(),
+        )));
+    }
+
+    let u = args[0].clone();
+    let du = u.derive(var, mode)?;
+    let f = |name: &str, arg: ASTNode| call(name, vec![arg], span);
+    let one_minus_sqr = sub(number(1, span), pow(u.clone(), number(2, span), span), span);
+    let one_plus_sqr = add(number(1, span), pow(u.clone(), number(2, span), span), span);
+
+    // f'(u) without the factor u'
+    let outer = match (name, args.len()) {
+        // Trigonometric functions: the argument is converted to radians
+        ("sin", _) => mul(f("cos", u), to_radians(mode, span), span),
+        ("cos", _) => negate(mul(f("sin", u), to_radians(mode, span), span), span),
+        ("tan", _) => div(
+            to_radians(mode, span),
+            pow(f("cos", u), number(2, span), span),
+            span,
+        ),
+        ("cotan", _) => negate(
+            div(
+                to_radians(mode, span),
+                pow(f("sin", u), number(2, span), span),
+                span,
+            ),
+            span,
+        ),
+        // Inverse functions: the result is converted from radians
+        ("arcsin", _) => div(
+            number(1, span),
+            mul(f("sqrt", one_minus_sqr), to_radians(mode, span), span),
+            span,
+        ),
+        ("arccos", _) => negate(
+            div(
+                number(1, span),
+                mul(f("sqrt", one_minus_sqr), to_radians(mode, span), span),
+                span,
+            ),
+            span,
+        ),
+        ("arctan", _) => div(
+            number(1, span),
+            mul(one_plus_sqr, to_radians(mode, span), span),
+            span,
+        ),
+        ("arccotan", _) => negate(
+            div(
+                number(1, span),
+                mul(one_plus_sqr, to_radians(mode, span), span),
+                span,
+            ),
+            span,
+        ),
+        ("atan2", _) => {
+            // (x·y' - y·x') / (x^2 + y^2)
+            let (y, x) = (&args[0], &args[1]);
+            let numerator = sub(
+                mul(x.clone(), y.derive(var, mode)?, span),
+                mul(y.clone(), x.derive(var, mode)?, span),
+                span,
+            );
+            let denominator = add(
+                pow(x.clone(), number(2, span), span),
+                pow(y.clone(), number(2, span), span),
+                span,
+            );
+            return Ok(div(
+                numerator,
+                mul(denominator, to_radians(mode, span), span),
+                span,
+            ));
+        }
+        // Hyperbolic functions
+        ("sinh", _) => f("cosh", u),
+        ("cosh", _) => f("sinh", u),
+        ("tanh", _) => div(
+            number(1, span),
+            pow(f("cosh", u), number(2, span), span),
+            span,
+        ),
+        ("arcsinh", _) => div(
+            number(1, span),
+            f(
+                "sqrt",
+                add(pow(u, number(2, span), span), number(1, span), span),
+            ),
+            span,
+        ),
+        ("arccosh", _) => div(
+            number(1, span),
+            f(
+                "sqrt",
+                sub(pow(u, number(2, span), span), number(1, span), span),
+            ),
+            span,
+        ),
+        ("arctanh", _) => div(number(1, span), one_minus_sqr, span),
+        // Logarithms and exponentials
+        ("ln", _) => div(number(1, span), u, span),
+        ("log", 2) => {
+            // log(u, b) = ln(u) / ln(b)
+            let quotient = div(f("ln", u), f("ln", args[1].clone()), span);
+            return quotient.derive(var, mode);
+        }
+        ("log", _) => div(
+            number(1, span),
+            mul(u, f("ln", number(10, span)), span),
+            span,
+        ),
+        ("log2", _) => div(
+            number(1, span),
+            mul(u, f("ln", number(2, span)), span),
+            span,
+        ),
+        ("exp", _) => f("exp", u),
+        // Powers and roots
+        ("sqrt", _) => div(
+            number(1, span),
+            mul(number(2, span), f("sqrt", u), span),
+            span,
+        ),
+        ("sqr", _) => mul(number(2, span), u, span),
+        ("cbrt", _) => div(
+            number(1, span),
+            mul(
+                number(3, span),
+                pow(f("cbrt", u), number(2, span), span),
+                span,
+            ),
+            span,
+        ),
+        ("pow", _) => return pow(u, args[1].clone(), span).derive(var, mode),
+        ("root", _) => {
+            let (n, x) = (&args[0], &args[1]);
+            if n.depends_on(var) {
+                // root(n, x) = x^(1/n)
+                let power = pow(x.clone(), div(number(1, span), n.clone(), span), span);
+                return power.derive(var, mode);
+            }
+            // x'·root(n, x) / (n·x), also right for odd roots of negative numbers
+            let root = call("root", args.to_vec(), span);
+            return Ok(div(
+                mul(x.derive(var, mode)?, root, span),
+                mul(n.clone(), x.clone(), span),
+                span,
+            ));
+        }
+        ("hypot", _) => {
+            // (u·u' + v·v') / hypot(u, v)
+            let v = &args[1];
+            let numerator = add(
+                mul(u, du, span),
+                mul(v.clone(), v.derive(var, mode)?, span),
+                span,
+            );
+            return Ok(div(numerator, call("hypot", args.to_vec(), span), span));
+        }
+        // Step functions are constant between their jumps
+        ("floor" | "ceil" | "round" | "trunc" | "sign", _) => return Ok(number(0, span)),
+        ("abs", _) => f("sign", u),
+        // min(a, b) = (a + b - |a - b|) / 2, max(a, b) = (a + b + |a - b|) / 2
+        ("min" | "max", _) => {
+            let b = match args.len() {
+                2 => args[1].clone(),
+                _ => call(name, args[1..].to_vec(), span),
+            };
+            let (da, db) = (du, b.derive(var, mode)?);
+            let sign = f("sign", sub(u, b, span));
+            let jump = mul(sign, sub(da.clone(), db.clone(), span), span);
+            let sum = match name {
+                "min" => sub(add(da, db, span), jump, span),
+                _ => add(add(da, db, span), jump, span),
+            };
+            return Ok(div(sum, number(2, span), span));
+        }
+        // Special functions: Γ'(x) = Γ(x)·ψ(x)
+        ("gamma", _) => mul(f("gamma", u.clone()), f("digamma", u), span),
+        ("factorial", _) => mul(
+            f("factorial", u.clone()),
+            f("digamma", add(u, number(1, span), span)),
+            span,
+        ),
+        _ => {
+            return Err(CompilerError::Eval(EvaluationError::NoDerivative(
+                name.to_string(),
+                span.clone(),
+            )))
+        }
+    };
+
+    Ok(mul(outer, du, span))
+}
+
+/// Radians per unit of the angle mode: π/180 for degrees, π/200 for gradians
+fn to_radians(mode: AngleMode, span: &TextSpan) -> ASTNode {
+    let pi = ASTNode::Constant(TokenKind::Pi, span.clone());
+    match mode {
+        AngleMode::Degrees => div(pi, number(180, span), span),
+        AngleMode::Gradians => div(pi, number(200, span), span),
+        AngleMode::Radians => number(1, span),
+    }
+}
+
+// Node builders. Products and sums with 0 and 1 are left out to keep the result readable.
+
+fn number(value: i64, span: &TextSpan) -> ASTNode {
+    ASTNode::Number(Num::Integer(value), span.clone())
+}
+
+fn is_number(node: &ASTNode, value: i64) -> bool {
+    match node {
+        ASTNode::Number(Num::Integer(i), _) => *i == value,
+        ASTNode::Number(Num::Float(f), _) => *f == value as f64,
+        _ => false,
+    }
+}
+
+fn binary(left: ASTNode, op: TokenKind, right: ASTNode, span: &TextSpan) -> ASTNode {
+    ASTNode::BinaryOp(Box::new(left), op, Box::new(right), span.clone())
+}
+
+fn call(name: &str, args: Vec<ASTNode>, span: &TextSpan) -> ASTNode {
+    ASTNode::FunctionCall(name.to_string(), args, span.clone())
+}
+
+fn negate(node: ASTNode, span: &TextSpan) -> ASTNode {
+    match node {
+        _ if is_number(&node, 0) => node,
+        ASTNode::UnaryOp(TokenKind::Minus, inner, _) => *inner,
+        _ => ASTNode::UnaryOp(TokenKind::Minus, Box::new(node), span.clone()),
+    }
+}
+
+fn add(left: ASTNode, right: ASTNode, span: &TextSpan) -> ASTNode {
+    if is_number(&left, 0) {
+        right
+    } else if is_number(&right, 0) {
+        left
+    } else {
+        binary(left, TokenKind::Plus, right, span)
+    }
+}
+
+fn sub(left: ASTNode, right: ASTNode, span: &TextSpan) -> ASTNode {
+    if is_number(&right, 0) {
+        left
+    } else if is_number(&left, 0) {
+        negate(right, span)
+    } else {
+        binary(left, TokenKind::Minus, right, span)
+    }
+}
+
+fn mul(left: ASTNode, right: ASTNode, span: &TextSpan) -> ASTNode {
+    if is_number(&left, 0) || is_number(&right, 1) {
+        left
+    } else if is_number(&right, 0) || is_number(&left, 1) {
+        right
+    } else {
+        binary(left, TokenKind::Multiply, right, span)
+    }
+}
+
+fn div(left: ASTNode, right: ASTNode, span: &TextSpan) -> ASTNode {
+    if is_number(&left, 0) || is_number(&right, 1) {
+        left
+    } else {
+        binary(left, TokenKind::Divide, right, span)
+    }
+}
+
+fn pow(base: ASTNode, exponent: ASTNode, span: &TextSpan) -> ASTNode {
+    if is_number(&exponent, 1) {
+        base
+    } else if is_number(&exponent, 0) {
+        number(1, span)
+    } else {
+        binary(base, TokenKind::Power, exponent, span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        resolver::MapOnlyResolver, root::RootConfig, wrapper::ASTWrapper, EvalSettings, Evaluator,
+    };
+    use super::*;
+    use crate::codegen::{CodeGenerator, Language};
+    use crate::ir::lower::Lowering;
+    use crate::utils::print::{lex_parse_input, lex_parse_program};
+    use crate::vm::Bytecode;
+
+    fn derive(input: &str) -> Result<ASTNode, CompilerError> {
+        lex_parse_input(input)
+            .unwrap()
+            .derivative_in("x", AngleMode::Radians)
+    }
+
+    #[test]
+    fn unknown_function_is_an_error_at_the_call() {
+        match derive("1 + foo(x)") {
+            Err(CompilerError::Eval(EvaluationError::UndefinedFunction(name, span))) => {
+                assert_eq!((name.as_str(), span.column), ("foo", 4))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wrong_number_of_arguments_is_an_error() {
+        let span = TextSpan::new(0, 3, "sin".to_string(), 1, 0);
+        let x = ASTNode::Identifier("x".to_string(), span.clone());
+        let call = ASTNode::FunctionCall("sin".to_string(), vec![x.clone(), x], span);
+        assert!(matches!(
+            call.derivative_in("x", AngleMode::Radians),
+            Err(CompilerError::Eval(EvaluationError::ArityMismatch(
+                _,
+                _,
+                2,
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn derivative_of_gamma_uses_digamma() {
+        assert_eq!(derive("gamma(x)").unwrap().infix(), "gamma(x) * digamma(x)");
+        assert!(matches!(
+            derive("digamma(x)"),
+            Err(CompilerError::Eval(EvaluationError::NoDerivative(_, _)))
+        ));
+    }
+
+    /// The definitions of the program are run, its last statement is returned
+    fn define(input: &str) -> (ASTNode, VariableManager) {
+        let (statements, result) = lex_parse_program(input).unwrap().split_result();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        for statement in statements.iter() {
+            evaluator.execute_statement(statement).unwrap();
+        }
+        (result, vars)
+    }
+
+    fn is_recursion_in(result: Result<impl std::fmt::Debug, CompilerError>, name: &str) -> bool {
+        match result {
+            Err(CompilerError::Eval(EvaluationError::RecursionLimit(found, _))) => found == name,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn functions_are_inlined_once_per_call() {
+        let (ast, vars) = define("g(x) := x + 1\nh(x) := g(x) * g(2 * x)\nh(g(t))");
+        assert_eq!(
+            ast.inline_functions(&vars).unwrap().infix(),
+            "(t + 1 + 1) * (2 * (t + 1) + 1)"
+        );
+    }
+
+    #[test]
+    fn recursive_functions_are_not_inlined() {
+        let (ast, vars) = define("f(x) := f(x) + f(x)\nf(x)");
+        assert!(is_recursion_in(ast.inline_functions(&vars), "f"));
+
+        // Through another function, reported at the call that closes the cycle
+        let (ast, vars) = define("f(x) := g(x) + 1\ng(x) := 2 * f(x)\n1 + g(3)");
+        assert!(is_recursion_in(ast.inline_functions(&vars), "g"));
+    }
+
+    #[test]
+    fn recursion_is_an_error_for_every_consumer() {
+        let input = "f(x) := f(x) + f(x)\nf(x) - 1";
+        let (ast, vars) = define(input);
+        assert!(is_recursion_in(Bytecode::compile(&ast, &vars), "f"));
+        assert!(is_recursion_in(
+            Lowering::new(&vars, AngleMode::Radians).lower(&ast),
+            "f"
+        ));
+        assert!(is_recursion_in(
+            CodeGenerator::new(&vars, Language::C).generate(&ast),
+            "f"
+        ));
+
+        let mut wrapper = ASTWrapper::from_program(
+            lex_parse_program(input).unwrap(),
+            VariableManager::new(),
+            Box::new(MapOnlyResolver),
+            EvalSettings::default(),
+        )
+        .unwrap();
+        assert!(is_recursion_in(wrapper.derivative("x"), "f"));
+        assert!(is_recursion_in(wrapper.polynomial_roots(), "f"));
+        assert!(is_recursion_in(
+            wrapper.roots_report(Some(-1.0), Some(1.0), RootConfig::default()),
+            "f"
+        ));
+        assert!(is_recursion_in(
+            wrapper.solve(None, Some(-1.0), Some(1.0), RootConfig::default()),
+            "f"
+        ));
+
+        // The evaluator stops at its call depth
+        let (ast, mut vars) = define("f(x) := f(x) + f(x)\nf(1)");
+        let mut resolver = MapOnlyResolver;
+        assert!(is_recursion_in(
+            Evaluator::new(&mut vars, &mut resolver).evaluate(&ast),
+            "f"
+        ));
+    }
+}
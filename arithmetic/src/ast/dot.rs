@@ -0,0 +1,187 @@
+use super::{ASTNode, Num, TextSpan, TokenKind};
+
+/// Converts the AST into a Graphviz graph. Every node is labelled with its
+/// operator or value and the line:column it starts at.
+impl ASTNode {
+    /// Writes the node, its children and the edges. Returns the id of the node.
+    pub(super) fn dot_nodes(&self, next_id: &mut usize, out: &mut String) -> usize {
+        let id = dot_node(next_id, out, &self.dot_label(), self.span());
+
+        let children: Vec<&ASTNode> = match self {
+            ASTNode::BinaryOp(left, _, right, _) => vec![left, right],
+            ASTNode::UnaryOp(_, expr, _) => vec![expr],
+            ASTNode::FunctionCall(_, args, _) => args.iter().collect(),
+            _ => Vec::new(),
+        };
+        for child in children {
+            let child_id = child.dot_nodes(next_id, out);
+            dot_edge(out, id, child_id);
+        }
+
+        id
+    }
+
+    fn dot_label(&self) -> String {
+        match self {
+            ASTNode::Number(Num::Integer(i), _) => i.to_string(),
+            ASTNode::Number(Num::Float(f), _) => f.to_string(),
+            ASTNode::Mantissa(mantiss_str, _) => mantiss_str.clone(),
+            ASTNode::Identifier(id, _) => id.clone(),
+            ASTNode::Constant(TokenKind::Pi, _) => "pi".to_string(),
+            ASTNode::Constant(c, _) => c.to_string(),
+            ASTNode::BinaryOp(_, op, _, _) | ASTNode::UnaryOp(op, _, _) => op.to_string(),
+            ASTNode::FunctionCall(func, _, _) => format!("{}()", func),
+            ASTNode::Error(_) => "<error>".to_string(),
+        }
+    }
+}
+
+/// digraph around the nodes and edges
+pub(super) fn dot_graph(body: &str) -> String {
+    format!("digraph AST {{\n    node [shape=box];\n{}}}\n", body)
+}
+
+/// Writes a node with a fresh id and returns the id
+pub(super) fn dot_node(
+    next_id: &mut usize,
+    out: &mut String,
+    label: &str,
+    span: &TextSpan,
+) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+    out.push_str(&format!(
+        "    n{} [label=\"{}\\n{}:{}\"];\n",
+        id,
+        label,
+        span.line,
+        span.column + 1
+    ));
+    id
+}
+
+pub(super) fn dot_edge(out: &mut String, from: usize, to: usize) {
+    out.push_str(&format!("    n{} -> n{};\n", from, to));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::print::lex_parse_program;
+
+    fn dot(input: &str) -> String {
+        lex_parse_program(input).unwrap().to_dot()
+    }
+
+    /// The lines between the graph header and the closing brace
+    fn body(input: &str) -> Vec<String> {
+        let graph = dot(input);
+        let lines: Vec<&str> = graph.lines().collect();
+        lines[2..lines.len() - 1]
+            .iter()
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn the_whole_graph() {
+        assert_eq!(
+            dot("1 + 2 * 3"),
+            "digraph AST {
+    node [shape=box];
+    n0 [label=\"+\\n1:3\"];
+    n1 [label=\"1\\n1:1\"];
+    n0 -> n1;
+    n2 [label=\"*\\n1:7\"];
+    n3 [label=\"2\\n1:5\"];
+    n2 -> n3;
+    n4 [label=\"3\\n1:9\"];
+    n2 -> n4;
+    n0 -> n2;
+}
+"
+        );
+    }
+
+    #[test]
+    fn parentheses_are_in_the_structure() {
+        assert_eq!(
+            body("(1 + 2) * 3"),
+            [
+                "n0 [label=\"*\\n1:9\"];",
+                "n1 [label=\"+\\n1:4\"];",
+                "n2 [label=\"1\\n1:2\"];",
+                "n1 -> n2;",
+                "n3 [label=\"2\\n1:6\"];",
+                "n1 -> n3;",
+                "n0 -> n1;",
+                "n4 [label=\"3\\n1:11\"];",
+                "n0 -> n4;",
+            ]
+        );
+    }
+
+    #[test]
+    fn unary_minus_and_powers() {
+        // -x^2 is (-x)^2, 2^3^2 is 2^(3^2)
+        assert_eq!(
+            body("-x^2"),
+            [
+                "n0 [label=\"^\\n1:3\"];",
+                "n1 [label=\"-\\n1:1\"];",
+                "n2 [label=\"x\\n1:2\"];",
+                "n1 -> n2;",
+                "n0 -> n1;",
+                "n3 [label=\"2\\n1:4\"];",
+                "n0 -> n3;",
+            ]
+        );
+        assert_eq!(
+            body("2^3^2"),
+            [
+                "n0 [label=\"^\\n1:2\"];",
+                "n1 [label=\"2\\n1:1\"];",
+                "n0 -> n1;",
+                "n2 [label=\"^\\n1:4\"];",
+                "n3 [label=\"3\\n1:3\"];",
+                "n2 -> n3;",
+                "n4 [label=\"2\\n1:5\"];",
+                "n2 -> n4;",
+                "n0 -> n2;",
+            ]
+        );
+    }
+
+    #[test]
+    fn functions_and_statements() {
+        assert_eq!(
+            body("max(x, -1.5)"),
+            [
+                "n0 [label=\"max()\\n1:1\"];",
+                "n1 [label=\"x\\n1:5\"];",
+                "n0 -> n1;",
+                "n2 [label=\"-\\n1:8\"];",
+                "n3 [label=\"1.5\\n1:9\"];",
+                "n2 -> n3;",
+                "n0 -> n2;",
+            ]
+        );
+        // Every statement is a tree of the same graph, the ids go on
+        assert_eq!(
+            body("x := 2\ny = pi"),
+            [
+                "n0 [label=\":=\\n1:3\"];",
+                "n1 [label=\"x\\n1:3\"];",
+                "n0 -> n1;",
+                "n2 [label=\"2\\n1:6\"];",
+                "n0 -> n2;",
+                "n3 [label=\"=\\n2:3\"];",
+                "n4 [label=\"y\\n2:1\"];",
+                "n3 -> n4;",
+                "n5 [label=\"pi\\n2:5\"];",
+                "n3 -> n5;",
+            ]
+        );
+    }
+}
@@ -1,24 +1,58 @@
-use super::{ASTNode, CompilerError, EvaluationError, Num, TextSpan, TokenKind, VariableManager};
+use super::{
+    power_check, resolver::num_from_f64, ASTNode, Builtin, CompilerError, DomainMode, EvalSettings,
+    EvaluationError, Num, Program, Statement, TextSpan, TokenKind, UserFunction, VariableManager,
+    VariableResolver,
+};
 use std::collections::VecDeque;
 use std::f64::consts::{E, PI};
 
+/// Maximum number of nested user function calls
+const MAX_CALL_DEPTH: usize = 128;
+
 /// Evaluate the tree using postfix notation and a stack
 pub struct Evaluator<'a> {
     pub stack: VecDeque<f64>,
     pub vars: &'a mut VariableManager,
+    pub resolver: &'a mut dyn VariableResolver,
+    pub settings: EvalSettings,
+    /// Domain problems found in lenient mode
+    pub warnings: Vec<EvaluationError>,
+    /// Arguments of the user function calls being evaluated, the innermost last. A body
+    /// only sees its own parameters, other names are global variables.
+    scopes: Vec<Vec<(String, f64)>>,
 }
 
 impl<'a> Evaluator<'a> {
-    pub fn new(vars: &'a mut VariableManager) -> Self {
+    pub fn new(vars: &'a mut VariableManager, resolver: &'a mut dyn VariableResolver) -> Self {
         Self {
             vars,
+            resolver,
             stack: VecDeque::new(),
+            settings: EvalSettings::default(),
+            warnings: Vec::new(),
+            scopes: Vec::new(),
         }
     }
 
+    /// Sets the angle unit and the domain mode. Degrees and lenient by default.
+    pub fn with_settings(mut self, settings: EvalSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
     /// Evaluate f(x) with any given value
     pub fn evaluate_with_x(&mut self, node: &ASTNode, x: f64) -> Result<f64, CompilerError> {
-        self.vars.set("x".to_string(), Num::Float(x));
+        self.evaluate_with(node, "x", x)
+    }
+
+    /// Evaluate with the given value of var, it is stored in the VariableManager
+    pub fn evaluate_with(
+        &mut self,
+        node: &ASTNode,
+        var: &str,
+        value: f64,
+    ) -> Result<f64, CompilerError> {
+        self.vars.set(var.to_string(), Num::Float(value));
         self.evaluate(node)
     }
 
@@ -30,7 +64,45 @@ impl<'a> Evaluator<'a> {
         self.postfix_traverse(node)?;
 
         // Return the final result from the stack
-        self.get_result()
+        self.get_result(node.span())
+    }
+
+    /// Runs every statement and returns the value of the last one that has a value
+    pub fn evaluate_program(&mut self, program: &Program) -> Result<Option<f64>, CompilerError> {
+        let mut result = None;
+
+        for statement in program.statements.iter() {
+            if let Some(value) = self.execute_statement(statement)? {
+                result = Some(value);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Evaluates a statement. Assignments store the value in the VariableManager.
+    /// Function definitions are stored and have no value, an equation is an error:
+    /// it is solved, not evaluated.
+    pub fn execute_statement(
+        &mut self,
+        statement: &Statement,
+    ) -> Result<Option<f64>, CompilerError> {
+        match statement {
+            Statement::Assign(name, expr, _) => {
+                let value = self.evaluate(expr)?;
+                self.vars.set(name.clone(), num_from_f64(value));
+                Ok(Some(value))
+            }
+            Statement::FunctionDef(name, params, body, _) => {
+                let function = UserFunction::new(params.clone(), body.clone());
+                self.vars.define_function(name.clone(), function);
+                Ok(None)
+            }
+            Statement::Expression(expr) => self.evaluate(expr).map(Some),
+            Statement::Equation(_, _, span) => Err(CompilerError::Eval(
+                EvaluationError::EquationHasNoValue(span.clone()),
+            )),
+        }
     }
 
     fn postfix_traverse(&mut self, node: &ASTNode) -> Result<(), CompilerError> {
@@ -56,13 +128,23 @@ impl<'a> Evaluator<'a> {
                 // Apply the unary operation
                 self.apply_unary_op(op, span)
             }
-            ASTNode::FunctionCall(func, arg, span) => {
-                // Traverse the argument
-                self.postfix_traverse(arg)?;
+            ASTNode::FunctionCall(func, args, span) => {
+                // Traverse the arguments
+                for arg in args.iter() {
+                    self.postfix_traverse(arg)?;
+                }
 
                 // Then apply the function
-                self.apply_function_call(func, span)
+                match self.vars.get_function(func) {
+                    Some(function) => {
+                        let function = function.clone();
+                        self.apply_user_function(func, &function, args.len(), span)
+                    }
+                    None => self.apply_function_call(func, args.len(), span),
+                }
             }
+            // Partial trees from error recovery can not be evaluated
+            ASTNode::Error(span) => Err(CompilerError::GenericError(span.clone())),
         }
     }
 
@@ -89,13 +171,23 @@ impl<'a> Evaluator<'a> {
                 Ok(())
             }
             _ => Err(CompilerError::Eval(EvaluationError::InvalidConstant(
-                span.line, span.start,
+                span.clone(),
             ))),
         }
     }
 
-    fn process_identifier(&mut self, id: &str, _: &TextSpan) -> Result<(), CompilerError> {
-        let value = self.vars.get(id);
+    fn process_identifier(&mut self, id: &str, span: &TextSpan) -> Result<(), CompilerError> {
+        let name = id.to_lowercase();
+        let parameter = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.iter().find(|(param, _)| *param == name));
+        if let Some(&(_, value)) = parameter {
+            self.stack.push_back(value);
+            return Ok(());
+        }
+
+        let value = self.vars.get(id, span, self.resolver)?;
 
         match value {
             Num::Integer(i) => self.stack.push_back(i as f64),
@@ -105,9 +197,9 @@ impl<'a> Evaluator<'a> {
     }
 
     fn process_mantissa(&mut self, value: &str, span: &TextSpan) -> Result<(), CompilerError> {
-        let parsed_value = value.parse::<f64>().map_err(|_| {
-            CompilerError::Eval(EvaluationError::InvalidMantissa(span.line, span.start))
-        })?;
+        let parsed_value = value
+            .parse::<f64>()
+            .map_err(|_| CompilerError::Eval(EvaluationError::InvalidMantissa(span.clone())))?;
         self.stack.push_back(parsed_value);
         Ok(())
     }
@@ -117,27 +209,28 @@ impl<'a> Evaluator<'a> {
         let right_val = self
             .stack
             .pop_back()
-            .ok_or_else(|| CompilerError::GenericError(span.line, span.start))?;
+            .ok_or_else(|| CompilerError::GenericError(span.clone()))?;
         let left_val = self
             .stack
             .pop_back()
-            .ok_or_else(|| CompilerError::GenericError(span.line, span.start))?;
+            .ok_or_else(|| CompilerError::GenericError(span.clone()))?;
 
         let result = match op {
             TokenKind::Plus => left_val + right_val,
             TokenKind::Minus => left_val - right_val,
             TokenKind::Multiply => left_val * right_val,
-            TokenKind::Divide => self.handle_division(left_val, right_val, span)?,
-            TokenKind::Mod => self.handle_modulo(left_val, right_val, span)?,
-            TokenKind::Div => self.handle_integer_division(left_val, right_val, span)?,
-            TokenKind::Power => left_val.powf(right_val),
+            TokenKind::Divide => divide(left_val, right_val, span)?,
+            TokenKind::Mod => modulo(left_val, right_val, span)?,
+            TokenKind::Div => integer_divide(left_val, right_val, span)?,
+            TokenKind::Power => {
+                let result = left_val.powf(right_val);
+                let offending = power_check(left_val, right_val);
+                self.check_result("^", &[left_val, right_val], result, offending, span)?;
+                result
+            }
             _ => {
                 return Err(CompilerError::Eval(
-                    EvaluationError::UnsupportedBinaryOperator(
-                        op.to_string(),
-                        span.line,
-                        span.start,
-                    ),
+                    EvaluationError::UnsupportedBinaryOperator(op.to_string(), span.clone()),
                 ))
             }
         };
@@ -151,18 +244,14 @@ impl<'a> Evaluator<'a> {
         let val = self
             .stack
             .pop_back()
-            .ok_or_else(|| CompilerError::GenericError(span.line, span.start))?;
+            .ok_or_else(|| CompilerError::GenericError(span.clone()))?;
 
         let result = match op {
             TokenKind::Minus => -val,
             TokenKind::Plus => val,
             _ => {
                 return Err(CompilerError::Eval(
-                    EvaluationError::UnsupportedUnaryOperator(
-                        op.to_string(),
-                        span.line,
-                        span.start,
-                    ),
+                    EvaluationError::UnsupportedUnaryOperator(op.to_string(), span.clone()),
                 ))
             }
         };
@@ -171,90 +260,385 @@ impl<'a> Evaluator<'a> {
         Ok(())
     }
 
-    fn apply_function_call(&mut self, func: &str, span: &TextSpan) -> Result<(), CompilerError> {
-        // Ensure we have at least one value on the stack
-        let arg_val = self
-            .stack
-            .pop_back()
-            .ok_or_else(|| CompilerError::GenericError(span.line, span.start))?;
-
-        let result = match func {
-            "sin" => arg_val.to_radians().sin(),
-            "cos" => arg_val.to_radians().cos(),
-            "tan" => arg_val.to_radians().tan(),
-            "arctan" => arg_val.atan().to_degrees(),
-            "arccotan" => (PI / 2.0 - arg_val.atan()).to_degrees(),
-            "ln" => arg_val.ln(),
-            "log" => arg_val.log10(),
-            "exp" => arg_val.exp(),
-            "sqrt" => arg_val.sqrt(),
-            "sqr" => arg_val * arg_val,
-            _ => {
-                return Err(CompilerError::Eval(EvaluationError::UnsupportedFunction(
-                    func.to_string(),
-                    span.line,
-                )))
-            }
-        };
+    fn apply_function_call(
+        &mut self,
+        func: &str,
+        arg_count: usize,
+        span: &TextSpan,
+    ) -> Result<(), CompilerError> {
+        // Ensure we have all the arguments on the stack
+        let args = self.pop_arguments(arg_count, span)?;
+
+        let builtin = Builtin::lookup(func).ok_or_else(|| {
+            CompilerError::Eval(EvaluationError::UndefinedFunction(
+                func.to_string(),
+                span.clone(),
+            ))
+        })?;
+
+        if !builtin.accepts(args.len()) {
+            return Err(CompilerError::Eval(EvaluationError::ArityMismatch(
+                func.to_string(),
+                builtin.expected_args(),
+                args.len(),
+                span.clone(),
+            )));
+        }
+
+        let result = (builtin.apply)(&args, self.settings.angle_mode);
+        let offending = (builtin.check)(&args, self.settings.angle_mode);
+        self.check_result(func, &args, result, offending, span)?;
 
         self.stack.push_back(result);
         Ok(())
     }
 
-    fn handle_division(
-        &self,
-        left_val: f64,
-        right_val: f64,
+    /// Strict mode fails on a domain problem of the result, lenient mode records a warning
+    fn check_result(
+        &mut self,
+        func: &str,
+        args: &[f64],
+        result: f64,
+        offending: Option<f64>,
         span: &TextSpan,
-    ) -> Result<f64, CompilerError> {
-        if right_val == 0.0 {
-            Err(CompilerError::Eval(EvaluationError::DivisionByZero(
-                span.line, span.start,
-            )))
-        } else {
-            Ok(left_val / right_val)
+    ) -> Result<(), CompilerError> {
+        match domain_problem(func, args, result, offending, span) {
+            Some(error) => report_domain_problem(self.settings, &mut self.warnings, error),
+            None => Ok(()),
         }
     }
 
-    // mod
-    fn handle_modulo(
-        &self,
-        left_val: f64,
-        right_val: f64,
-        span: &TextSpan,
-    ) -> Result<f64, CompilerError> {
-        if left_val.fract() != 0.0 || right_val.fract() != 0.0 {
-            Err(CompilerError::Eval(
-                EvaluationError::IntegerOperatorWithFloatOperands(span.line, span.start),
-            ))
-        } else {
-            Ok(left_val % right_val)
+    /// Pops the arguments of a call. They were pushed in order, so the last one is on top.
+    fn pop_arguments(&mut self, count: usize, span: &TextSpan) -> Result<Vec<f64>, CompilerError> {
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            let value = self
+                .stack
+                .pop_back()
+                .ok_or_else(|| CompilerError::GenericError(span.clone()))?;
+            args.push(value);
         }
+        args.reverse();
+        Ok(args)
     }
 
-    // div
-    fn handle_integer_division(
-        &self,
-        left_val: f64,
-        right_val: f64,
+    /// Binds the arguments to the parameters, evaluates the body and restores the old values
+    fn apply_user_function(
+        &mut self,
+        name: &str,
+        function: &UserFunction,
+        arg_count: usize,
         span: &TextSpan,
-    ) -> Result<f64, CompilerError> {
-        if left_val.fract() != 0.0 || right_val.fract() != 0.0 {
-            Err(CompilerError::Eval(
-                EvaluationError::IntegerOperatorWithFloatOperands(span.line, span.start),
-            ))
-        } else if right_val == 0.0 {
-            Err(CompilerError::Eval(EvaluationError::DivisionByZero(
-                span.line, span.start,
-            )))
-        } else {
-            Ok((left_val / right_val).floor())
+    ) -> Result<(), CompilerError> {
+        if arg_count != function.params.len() {
+            return Err(CompilerError::Eval(EvaluationError::ArityMismatch(
+                name.to_string(),
+                function.params.len().to_string(),
+                arg_count,
+                span.clone(),
+            )));
         }
+
+        if self.scopes.len() >= MAX_CALL_DEPTH {
+            return Err(CompilerError::Eval(EvaluationError::RecursionLimit(
+                name.to_string(),
+                span.clone(),
+            )));
+        }
+
+        let args = self.pop_arguments(arg_count, span)?;
+
+        // The body is evaluated in a new scope, like inline_functions substitutes it
+        let scope = function.params.iter().cloned().zip(args).collect();
+        self.scopes.push(scope);
+        let result = self.postfix_traverse(&function.body);
+        self.scopes.pop();
+
+        result
     }
 
-    pub fn get_result(&mut self) -> Result<f64, CompilerError> {
+    pub fn get_result(&mut self, span: &TextSpan) -> Result<f64, CompilerError> {
         self.stack
             .pop_back()
-            .ok_or(CompilerError::GenericError(0, 0))
+            .ok_or_else(|| CompilerError::GenericError(span.clone()))
+    }
+}
+
+/// A value outside of the domain or an infinite result from finite arguments
+pub fn domain_problem(
+    func: &str,
+    args: &[f64],
+    result: f64,
+    offending: Option<f64>,
+    span: &TextSpan,
+) -> Option<EvaluationError> {
+    let finite_args = args.iter().all(|arg| arg.is_finite());
+    let first = args.first().copied().unwrap_or(f64::NAN);
+
+    match offending {
+        Some(arg) => Some(EvaluationError::DomainError(
+            func.to_string(),
+            arg,
+            span.clone(),
+        )),
+        None if finite_args && result.is_nan() => Some(EvaluationError::DomainError(
+            func.to_string(),
+            first,
+            span.clone(),
+        )),
+        None if finite_args && result.is_infinite() => Some(EvaluationError::Overflow(
+            func.to_string(),
+            first,
+            span.clone(),
+        )),
+        None => None,
+    }
+}
+
+/// Strict mode fails, lenient mode keeps the value and records the warning once
+pub fn report_domain_problem(
+    settings: EvalSettings,
+    warnings: &mut Vec<EvaluationError>,
+    error: EvaluationError,
+) -> Result<(), CompilerError> {
+    match settings.domain_mode {
+        DomainMode::Strict => Err(CompilerError::Eval(error)),
+        DomainMode::Lenient => {
+            if !warnings.contains(&error) {
+                warnings.push(error);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `/`
+pub fn divide(left_val: f64, right_val: f64, span: &TextSpan) -> Result<f64, CompilerError> {
+    if right_val == 0.0 {
+        Err(CompilerError::Eval(EvaluationError::DivisionByZero(
+            span.clone(),
+        )))
+    } else {
+        Ok(left_val / right_val)
+    }
+}
+
+/// `mod`, only for integer operands
+pub fn modulo(left_val: f64, right_val: f64, span: &TextSpan) -> Result<f64, CompilerError> {
+    if left_val.fract() != 0.0 || right_val.fract() != 0.0 {
+        Err(CompilerError::Eval(
+            EvaluationError::IntegerOperatorWithFloatOperands(span.clone()),
+        ))
+    } else if right_val == 0.0 {
+        Err(CompilerError::Eval(EvaluationError::DivisionByZero(
+            span.clone(),
+        )))
+    } else {
+        Ok(left_val % right_val)
+    }
+}
+
+/// `div`, floor of the quotient of integer operands
+pub fn integer_divide(
+    left_val: f64,
+    right_val: f64,
+    span: &TextSpan,
+) -> Result<f64, CompilerError> {
+    if left_val.fract() != 0.0 || right_val.fract() != 0.0 {
+        Err(CompilerError::Eval(
+            EvaluationError::IntegerOperatorWithFloatOperands(span.clone()),
+        ))
+    } else if right_val == 0.0 {
+        Err(CompilerError::Eval(EvaluationError::DivisionByZero(
+            span.clone(),
+        )))
+    } else {
+        Ok((left_val / right_val).floor())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::resolver::MapOnlyResolver;
+    use super::*;
+    use crate::errors::parser::ParserError;
+    use crate::utils::print::lex_parse_program;
+    use crate::vm::{machine::VirtualMachine, Bytecode};
+
+    fn evaluate(input: &str, domain_mode: DomainMode) -> Result<Option<f64>, CompilerError> {
+        let program = lex_parse_program(input).unwrap();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let settings = EvalSettings {
+            domain_mode,
+            ..EvalSettings::default()
+        };
+        Evaluator::new(&mut vars, &mut resolver)
+            .with_settings(settings)
+            .evaluate_program(&program)
+    }
+
+    #[test]
+    fn assignments_are_kept_for_the_following_statements() {
+        let strict = DomainMode::Strict;
+        assert_eq!(evaluate("a := 2; b := a * 3\na + b", strict), Ok(Some(8.0)));
+        assert_eq!(evaluate("a := 2; a := a + 1; a", strict), Ok(Some(3.0)));
+        // An assignment has the assigned value, a definition has none
+        assert_eq!(evaluate("a := 5", strict), Ok(Some(5.0)));
+        assert_eq!(evaluate("a := 5; f(x) := x", strict), Ok(Some(5.0)));
+        assert_eq!(evaluate("f(x) := x", strict), Ok(None));
+        // Names are not case sensitive
+        assert_eq!(evaluate("Rate := 0.5; RATE * 4", strict), Ok(Some(2.0)));
+    }
+
+    #[test]
+    fn equations_have_no_value() {
+        for input in ["2 = 3", "x := 1; x = 1", "x = 1; 2"] {
+            let error = evaluate(input, DomainMode::Strict).unwrap_err();
+            match error {
+                CompilerError::Eval(EvaluationError::EquationHasNoValue(span)) => {
+                    assert_eq!(span.literal, "=", "{}", input)
+                }
+                error => panic!("{}: {:?}", input, error),
+            }
+        }
+    }
+
+    #[test]
+    fn free_variables_of_a_function_are_global() {
+        let (definitions, result) = lex_parse_program("f(x) := x + y; g(y) := f(1); g(5)")
+            .unwrap()
+            .split_result();
+        let mut vars = VariableManager::new();
+        vars.set("y".to_string(), Num::Integer(100));
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        for statement in definitions.iter() {
+            evaluator.execute_statement(statement).unwrap();
+        }
+
+        // The y of g is not the y of f
+        assert_eq!(evaluator.evaluate(&result), Ok(101.0));
+        let inlined = result.inline_functions(evaluator.vars).unwrap();
+        assert_eq!(inlined.infix(), "1 + y");
+        assert_eq!(evaluator.evaluate(&inlined), Ok(101.0));
+        assert_eq!(vars.get_stored("y"), Some(Num::Integer(100)));
+        assert_eq!(vars.get_stored("x"), None);
+
+        let bytecode = Bytecode::compile(&result, &vars).unwrap();
+        let values = bytecode.bind(&mut vars, &mut resolver, None).unwrap();
+        assert_eq!(VirtualMachine::new().run(&bytecode, &values), Ok(101.0));
+    }
+
+    #[test]
+    fn parameters_shadow_globals_only_in_the_body() {
+        let strict = DomainMode::Strict;
+        assert_eq!(
+            evaluate("x := 10; f(x) := 2*x; f(3) + x", strict),
+            Ok(Some(16.0))
+        );
+        // Each call sees its own argument
+        assert_eq!(
+            evaluate("f(x) := 2*x; g(x) := f(x + 1) * x; g(3)", strict),
+            Ok(Some(24.0))
+        );
+    }
+
+    #[test]
+    fn failing_statement_stops_the_program() {
+        assert!(matches!(
+            evaluate("a := 1; b := c + 1; a", DomainMode::Strict),
+            Err(CompilerError::Eval(EvaluationError::UndefinedVariable(name, _))) if name == "c"
+        ));
+    }
+
+    #[test]
+    fn builtins_with_several_arguments() {
+        let cases = [
+            ("log(8, 2)", 3.0),
+            ("log(100)", 2.0),
+            ("atan2(1, 1)", 45.0),
+            ("min(3, 1, 2)", 1.0),
+            ("max(3, 1, 2)", 3.0),
+            ("max(1, min(5, 2), 0)", 2.0),
+            ("pow(2, 10)", 1024.0),
+            ("hypot(3, 4)", 5.0),
+            ("root(3, -8)", -2.0),
+            ("round(1.23456, 3)", 1.235),
+        ];
+        for (input, expected) in cases {
+            let value = evaluate(input, DomainMode::Strict).unwrap().unwrap();
+            assert!((value - expected).abs() < 1e-12, "{} = {}", input, value);
+        }
+    }
+
+    #[test]
+    fn number_of_arguments_is_checked() {
+        // Built-in functions when parsing, user functions when they are called
+        for (input, expected, found) in [
+            ("min(1)", "at least 2", 1),
+            ("log(1, 2, 3)", "1 to 2", 3),
+            ("atan2(1)", "2", 1),
+        ] {
+            match lex_parse_program(input) {
+                Err(CompilerError::Parse(ParserError::InvalidArgumentCount(
+                    _,
+                    range,
+                    count,
+                    _,
+                ))) => {
+                    assert_eq!((range.as_str(), count), (expected, found), "{}", input)
+                }
+                other => panic!("{}: unexpected {:?}", input, other),
+            }
+        }
+        match evaluate("f(x, y) := x; f(1)", DomainMode::Strict) {
+            Err(CompilerError::Eval(EvaluationError::ArityMismatch(name, range, count, _))) => {
+                assert_eq!((name.as_str(), range.as_str(), count), ("f", "2", 1))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn domain_of_several_arguments() {
+        for input in ["log(8, 1)", "log(-8, 2)", "root(2, -4)", "pow(-8, 0.5)"] {
+            assert!(
+                matches!(
+                    evaluate(input, DomainMode::Strict),
+                    Err(CompilerError::Eval(EvaluationError::DomainError(..)))
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn modulo_by_zero_fails_in_both_modes() {
+        for mode in [DomainMode::Strict, DomainMode::Lenient] {
+            assert!(matches!(
+                evaluate("5 mod 0", mode),
+                Err(CompilerError::Eval(EvaluationError::DivisionByZero(_)))
+            ));
+        }
+        assert_eq!(
+            evaluate("-7 mod 3", DomainMode::Strict).unwrap(),
+            Some(-1.0)
+        );
+    }
+
+    #[test]
+    fn zero_to_a_negative_power_names_the_base() {
+        match evaluate("0^-1", DomainMode::Strict) {
+            Err(CompilerError::Eval(EvaluationError::DomainError(name, arg, _))) => {
+                assert_eq!((name.as_str(), arg), ("^", 0.0))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            evaluate("0^-1", DomainMode::Lenient).unwrap(),
+            Some(f64::INFINITY)
+        );
     }
 }
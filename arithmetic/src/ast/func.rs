@@ -0,0 +1,19 @@
+use super::ASTNode;
+
+/// Function defined in the program: f(x, y) := body
+#[derive(Debug, Clone)]
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: ASTNode,
+}
+
+impl UserFunction {
+    pub fn new(params: Vec<String>, body: ASTNode) -> Self {
+        Self { params, body }
+    }
+
+    /// "f(x, y)"
+    pub fn signature(&self, name: &str) -> String {
+        format!("{}({})", name, self.params.join(", "))
+    }
+}
@@ -0,0 +1,179 @@
+use super::{ASTNode, Num, TokenKind};
+
+/// Binding strength of a node, following the parser:
+/// expression (+ -) < term (* / mod div) < exponentiation (^) < unary minus < atoms
+pub(crate) const SUM: u8 = 1;
+pub(crate) const PRODUCT: u8 = 2;
+pub(crate) const POWER: u8 = 3;
+pub(crate) const UNARY: u8 = 4;
+pub(crate) const ATOM: u8 = 5;
+
+/// Converts the AST back into source text. Only the parentheses the parser needs are
+/// printed, so parsing the output gives the same tree.
+impl ASTNode {
+    pub fn infix(&self) -> String {
+        match self {
+            ASTNode::Number(n, _) => format_number(n),
+            ASTNode::Mantissa(mantiss_str, _) => mantiss_str.clone(),
+            ASTNode::Identifier(id, _) => id.clone(),
+
+            // π is printed as the keyword `pi`
+            ASTNode::Constant(TokenKind::Pi, _) => "pi".to_string(),
+            ASTNode::Constant(c, _) => c.to_string(),
+
+            ASTNode::BinaryOp(left, op, right, _) => {
+                let precedence = self.precedence();
+
+                // ^ is right-associative, the other operators are left-associative.
+                // -x^2 is (-x)^2 for the parser, the base keeps its parentheses.
+                let (left_min, right_min) = match op {
+                    TokenKind::Power => (ATOM, POWER),
+                    _ => (precedence, precedence + 1),
+                };
+                let left = left.infix_at(left_min);
+                let right = right.infix_at(right_min);
+
+                match op {
+                    TokenKind::Power => format!("{}^{}", left, right),
+                    _ => format!("{} {} {}", left, op, right),
+                }
+            }
+
+            // The operand of a unary minus is a factor
+            ASTNode::UnaryOp(op, expr, _) => format!("{}{}", op, expr.infix_at(UNARY)),
+
+            ASTNode::FunctionCall(func, args, _) => format!(
+                "{}({})",
+                func,
+                args.iter()
+                    .map(|arg| arg.infix())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+
+            ASTNode::Error(_) => "<error>".to_string(),
+        }
+    }
+
+    /// Infix text, in parentheses when the node binds weaker than min_precedence
+    fn infix_at(&self, min_precedence: u8) -> String {
+        if self.precedence() < min_precedence {
+            format!("({})", self.infix())
+        } else {
+            self.infix()
+        }
+    }
+
+    /// Binding strength in typeset output: fractions group themselves and
+    /// mantissas are written as a product, a × 10^b
+    pub(super) fn typeset_precedence(&self) -> u8 {
+        match self {
+            ASTNode::BinaryOp(_, TokenKind::Divide, _, _) => ATOM,
+            ASTNode::Mantissa(_, _) => PRODUCT,
+            ASTNode::FunctionCall(func, _, _) => match func.as_str() {
+                "pow" | "sqr" => POWER,
+                "factorial" => UNARY,
+                _ => ATOM,
+            },
+            _ => self.precedence(),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            ASTNode::BinaryOp(_, TokenKind::Plus | TokenKind::Minus, _, _) => SUM,
+            ASTNode::BinaryOp(_, TokenKind::Power, _, _) => POWER,
+            ASTNode::BinaryOp(_, _, _, _) => PRODUCT,
+            ASTNode::UnaryOp(_, _, _) => UNARY,
+            ASTNode::Number(Num::Integer(i), _) if *i < 0 => UNARY,
+            ASTNode::Number(Num::Float(f), _) if f.is_sign_negative() => UNARY,
+            _ => ATOM,
+        }
+    }
+}
+
+/// Floats keep their dot, 2.0 is read back as a float and not as the integer 2
+fn format_number(n: &Num) -> String {
+    match n {
+        Num::Integer(i) => i.to_string(),
+        Num::Float(f) if f.is_finite() && f.fract() == 0.0 => format!("{:.1}", f),
+        Num::Float(f) => f.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::print::lex_parse_input;
+
+    /// The tree without spans, every operation in parentheses
+    fn shape(node: &ASTNode) -> String {
+        match node {
+            ASTNode::Number(n, _) => format!("{:?}", n),
+            ASTNode::Mantissa(m, _) => m.clone(),
+            ASTNode::Identifier(id, _) => id.clone(),
+            ASTNode::Constant(c, _) => format!("{:?}", c),
+            ASTNode::BinaryOp(left, op, right, _) => {
+                format!("({} {:?} {})", shape(left), op, shape(right))
+            }
+            ASTNode::UnaryOp(op, expr, _) => format!("({:?} {})", op, shape(expr)),
+            ASTNode::FunctionCall(name, args, _) => format!(
+                "{}({})",
+                name,
+                args.iter().map(shape).collect::<Vec<_>>().join(", ")
+            ),
+            ASTNode::Error(_) => "<error>".to_string(),
+        }
+    }
+
+    /// Prints the input, checks the text and that parsing it gives the same tree
+    fn round_trip(input: &str, expected: &str) {
+        let ast = lex_parse_input(input).unwrap();
+        let text = ast.infix();
+        assert_eq!(text, expected, "infix of {}", input);
+        let reparsed = lex_parse_input(&text).unwrap();
+        assert_eq!(shape(&reparsed), shape(&ast), "tree of {}", input);
+    }
+
+    #[test]
+    fn precedence() {
+        round_trip("((x + 1)) * (2^(3^y))", "(x + 1) * 2^3^y");
+        round_trip("a / (b * c)", "a / (b * c)");
+        round_trip("(a * b) + (c / d)", "a * b + c / d");
+        round_trip("-(a + b) * c", "-(a + b) * c");
+    }
+
+    #[test]
+    fn associativity() {
+        round_trip("a - (b - c)", "a - (b - c)");
+        round_trip("(a - b) - c", "a - b - c");
+        round_trip("(2^3)^4", "(2^3)^4");
+        round_trip("2^(3^4)", "2^3^4");
+    }
+
+    #[test]
+    fn unary_minus_and_power() {
+        round_trip("-x^2", "(-x)^2");
+        round_trip("-(x^2)", "-(x^2)");
+        round_trip("2^-x", "2^-x");
+        round_trip("--x", "--x");
+    }
+
+    #[test]
+    fn div_and_mod() {
+        round_trip("7 div (2 mod 3)", "7 div (2 mod 3)");
+        round_trip("(7 div 2) mod 3", "7 div 2 mod 3");
+        round_trip("(x mod 4) * 2 + 1", "x mod 4 * 2 + 1");
+        round_trip("x div (y * 2)", "x div (y * 2)");
+    }
+
+    #[test]
+    fn nested_calls() {
+        round_trip(
+            "max(sin(x + 1), log(8, 2), -cos(y)^2)",
+            "max(sin(x + 1), log(8, 2), (-cos(y))^2)",
+        );
+        round_trip("SIN(root(3, (x)))^PI", "sin(root(3, x))^pi");
+        round_trip("f(g(x), 1.5E+3 * e)", "f(g(x), 1.5E+3 * e)");
+    }
+}
@@ -0,0 +1,185 @@
+use super::infix::{ATOM, UNARY};
+use super::{ASTNode, Num, TokenKind};
+
+/// Converts the AST into LaTeX math: \frac for division, superscripts for powers,
+/// \sqrt, \sin, \bmod and mantissas as a \times 10^{b}
+impl ASTNode {
+    pub fn to_latex(&self) -> String {
+        match self {
+            ASTNode::Number(Num::Integer(i), _) => i.to_string(),
+            ASTNode::Number(Num::Float(f), _) => f.to_string(),
+
+            ASTNode::Mantissa(mantiss_str, _) => {
+                let (mantissa, exponent) = split_mantissa(mantiss_str);
+                format!("{} \\times 10^{{{}}}", mantissa, exponent)
+            }
+
+            ASTNode::Identifier(id, _) => latex_identifier(id),
+
+            ASTNode::Constant(TokenKind::Pi, _) => "\\pi".to_string(),
+            ASTNode::Constant(c, _) => c.to_string(),
+
+            // The fraction bar groups both sides
+            ASTNode::BinaryOp(left, TokenKind::Divide, right, _) => {
+                format!("\\frac{{{}}}{{{}}}", left.to_latex(), right.to_latex())
+            }
+
+            // The braces group the exponent
+            ASTNode::BinaryOp(base, TokenKind::Power, exponent, _) => {
+                format!("{}^{{{}}}", base.latex_base(), exponent.to_latex())
+            }
+
+            ASTNode::BinaryOp(left, op, right, _) => {
+                let precedence = self.typeset_precedence();
+                let op = match op {
+                    TokenKind::Multiply => "\\cdot".to_string(),
+                    TokenKind::Mod => "\\bmod".to_string(),
+                    TokenKind::Div => "\\operatorname{div}".to_string(),
+                    op => op.to_string(),
+                };
+                format!(
+                    "{} {} {}",
+                    left.latex_at(precedence),
+                    op,
+                    right.latex_at(precedence + 1)
+                )
+            }
+
+            ASTNode::UnaryOp(op, expr, _) => format!("{}{}", op, expr.latex_at(UNARY)),
+
+            ASTNode::FunctionCall(func, args, _) => latex_call(func, args),
+
+            ASTNode::Error(_) => "\\square".to_string(),
+        }
+    }
+
+    /// Base of a power or factorial, fractions are put in parentheses as well
+    fn latex_base(&self) -> String {
+        match self {
+            ASTNode::BinaryOp(_, TokenKind::Divide, _, _) => {
+                format!("\\left({}\\right)", self.to_latex())
+            }
+            _ => self.latex_at(ATOM),
+        }
+    }
+
+    /// LaTeX in \left( \right) when the node binds weaker than min_precedence
+    fn latex_at(&self, min_precedence: u8) -> String {
+        if self.typeset_precedence() < min_precedence {
+            format!("\\left({}\\right)", self.to_latex())
+        } else {
+            self.to_latex()
+        }
+    }
+}
+
+/// 1.3E+2 = ("1.3", "2")
+pub(super) fn split_mantissa(mantiss_str: &str) -> (&str, &str) {
+    let (mantissa, exponent) = mantiss_str
+        .split_once(['E', 'e'])
+        .unwrap_or((mantiss_str, "0"));
+    (mantissa, exponent.trim_start_matches('+'))
+}
+
+/// Single letters are italic variables, longer names are set upright
+fn latex_identifier(id: &str) -> String {
+    if id.chars().count() == 1 {
+        id.to_string()
+    } else {
+        format!("\\mathrm{{{}}}", id.replace('_', "\\_"))
+    }
+}
+
+fn latex_call(func: &str, args: &[ASTNode]) -> String {
+    let arg = |i: usize| args.get(i).map(|arg| arg.to_latex()).unwrap_or_default();
+
+    match (func, args.len()) {
+        ("sqrt", 1) => format!("\\sqrt{{{}}}", arg(0)),
+        ("cbrt", 1) => format!("\\sqrt[3]{{{}}}", arg(0)),
+        ("root", 2) => format!("\\sqrt[{}]{{{}}}", arg(0), arg(1)),
+        ("abs", 1) => format!("\\left|{}\\right|", arg(0)),
+        ("floor", 1) => format!("\\left\\lfloor {} \\right\\rfloor", arg(0)),
+        ("ceil", 1) => format!("\\left\\lceil {} \\right\\rceil", arg(0)),
+        ("factorial", 1) => format!("{}!", args[0].latex_base()),
+        ("sqr", 1) => format!("{}^{{2}}", args[0].latex_base()),
+        ("pow", 2) => format!("{}^{{{}}}", args[0].latex_base(), arg(1)),
+        ("log", 2) => format!("\\log_{{{}}}\\left({}\\right)", arg(1), arg(0)),
+        ("log2", 1) => format!("\\log_{{2}}\\left({}\\right)", arg(0)),
+        _ => {
+            let args = args
+                .iter()
+                .map(|arg| arg.to_latex())
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!("{}\\left({}\\right)", latex_function_name(func), args)
+        }
+    }
+}
+
+fn latex_function_name(func: &str) -> String {
+    match func {
+        "sin" | "cos" | "tan" | "arcsin" | "arccos" | "arctan" | "sinh" | "cosh" | "tanh"
+        | "ln" | "log" | "exp" | "min" | "max" => format!("\\{}", func),
+        "cotan" => "\\cot".to_string(),
+        "gamma" => "\\Gamma".to_string(),
+        "digamma" => "\\psi".to_string(),
+        "sign" => "\\operatorname{sgn}".to_string(),
+        _ if func.chars().count() == 1 => func.to_string(),
+        _ => format!("\\operatorname{{{}}}", func.replace('_', "\\_")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::print::lex_parse_input;
+
+    fn latex(input: &str) -> String {
+        lex_parse_input(input).unwrap().to_latex()
+    }
+
+    #[test]
+    fn parentheses_follow_the_precedence() {
+        assert_eq!(latex("(1 + 2) * 3"), "\\left(1 + 2\\right) \\cdot 3");
+        assert_eq!(latex("1 + 2 * 3"), "1 + 2 \\cdot 3");
+        assert_eq!(latex("1 - (2 - 3)"), "1 - \\left(2 - 3\\right)");
+        assert_eq!(latex("2 - 3 + 4"), "2 - 3 + 4");
+        assert_eq!(latex("(x + 1) / (x - 1)"), "\\frac{x + 1}{x - 1}");
+        assert_eq!(
+            latex("x mod 3 + 7 div 2"),
+            "x \\bmod 3 + 7 \\operatorname{div} 2"
+        );
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(latex("-3"), "-3");
+        assert_eq!(latex("-(x + 1)"), "-\\left(x + 1\\right)");
+        assert_eq!(latex("2 * -x"), "2 \\cdot -x");
+        // The parser reads -x^2 as (-x)^2
+        assert_eq!(latex("-x^2"), "\\left(-x\\right)^{2}");
+    }
+
+    #[test]
+    fn powers() {
+        assert_eq!(latex("2^3^2"), "2^{3^{2}}");
+        assert_eq!(latex("(2^3)^2"), "\\left(2^{3}\\right)^{2}");
+        assert_eq!(latex("x^(y + 1)"), "x^{y + 1}");
+        assert_eq!(latex("(1/2)^x"), "\\left(\\frac{1}{2}\\right)^{x}");
+        assert_eq!(latex("1.3E+2"), "1.3 \\times 10^{2}");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(latex("sin(x)^2"), "\\sin\\left(x\\right)^{2}");
+        assert_eq!(latex("sqrt(x + 1)"), "\\sqrt{x + 1}");
+        assert_eq!(latex("root(3, x)"), "\\sqrt[3]{x}");
+        assert_eq!(latex("log(8, 2)"), "\\log_{2}\\left(8\\right)");
+        assert_eq!(latex("abs(-x)"), "\\left|-x\\right|");
+        assert_eq!(latex("factorial(n + 1)"), "\\left(n + 1\\right)!");
+        assert_eq!(latex("max(x, 2*y)"), "\\max\\left(x, 2 \\cdot y\\right)");
+        assert_eq!(
+            latex("gamma(x) * my_var * pi"),
+            "\\Gamma\\left(x\\right) \\cdot \\mathrm{my\\_var} \\cdot \\pi"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use super::infix::{ATOM, UNARY};
+use super::latex::split_mantissa;
+use super::{ASTNode, Num, TokenKind};
+
+/// Converts the AST into presentation MathML: <mfrac> for division, <msup> for powers,
+/// <msqrt>, function application and mantissas as a × 10^b
+impl ASTNode {
+    /// The expression as a complete <math> element
+    pub fn to_mathml(&self) -> String {
+        math(&self.mathml())
+    }
+
+    /// A single MathML element for the node
+    pub(super) fn mathml(&self) -> String {
+        match self {
+            ASTNode::Number(n, _) => {
+                let number_str = match n {
+                    Num::Integer(i) => i.to_string(),
+                    Num::Float(f) => f.to_string(),
+                };
+                match number_str.strip_prefix('-') {
+                    Some(magnitude) => format!("<mrow><mo>-</mo><mn>{}</mn></mrow>", magnitude),
+                    None => format!("<mn>{}</mn>", number_str),
+                }
+            }
+
+            ASTNode::Mantissa(mantiss_str, _) => {
+                let (mantissa, exponent) = split_mantissa(mantiss_str);
+                format!(
+                    "<mrow><mn>{}</mn><mo>&#x00D7;</mo><msup><mn>10</mn><mn>{}</mn></msup></mrow>",
+                    mantissa, exponent
+                )
+            }
+
+            ASTNode::Identifier(id, _) => format!("<mi>{}</mi>", id),
+
+            ASTNode::Constant(TokenKind::Pi, _) => "<mi>&#x03C0;</mi>".to_string(),
+            ASTNode::Constant(c, _) => format!("<mi>{}</mi>", c),
+
+            // The fraction bar groups both sides
+            ASTNode::BinaryOp(left, TokenKind::Divide, right, _) => {
+                format!("<mfrac>{}{}</mfrac>", left.mathml(), right.mathml())
+            }
+
+            ASTNode::BinaryOp(base, TokenKind::Power, exponent, _) => {
+                format!("<msup>{}{}</msup>", base.mathml_base(), exponent.mathml())
+            }
+
+            ASTNode::BinaryOp(left, op, right, _) => {
+                let precedence = self.typeset_precedence();
+                let op = match op {
+                    TokenKind::Multiply => "&#x22C5;".to_string(),
+                    op => op.to_string(),
+                };
+                format!(
+                    "<mrow>{}<mo>{}</mo>{}</mrow>",
+                    left.mathml_at(precedence),
+                    op,
+                    right.mathml_at(precedence + 1)
+                )
+            }
+
+            ASTNode::UnaryOp(op, expr, _) => {
+                format!("<mrow><mo>{}</mo>{}</mrow>", op, expr.mathml_at(UNARY))
+            }
+
+            ASTNode::FunctionCall(func, args, _) => mathml_call(func, args),
+
+            ASTNode::Error(_) => "<merror><mtext>error</mtext></merror>".to_string(),
+        }
+    }
+
+    /// Base of a power or factorial, fractions are put in parentheses as well
+    fn mathml_base(&self) -> String {
+        match self {
+            ASTNode::BinaryOp(_, TokenKind::Divide, _, _) => fenced(&self.mathml()),
+            _ => self.mathml_at(ATOM),
+        }
+    }
+
+    /// MathML in parentheses when the node binds weaker than min_precedence
+    fn mathml_at(&self, min_precedence: u8) -> String {
+        if self.typeset_precedence() < min_precedence {
+            fenced(&self.mathml())
+        } else {
+            self.mathml()
+        }
+    }
+}
+
+/// Top level <math> element
+pub(super) fn math(content: &str) -> String {
+    format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>",
+        content
+    )
+}
+
+fn fenced(content: &str) -> String {
+    format!("<mrow><mo>(</mo>{}<mo>)</mo></mrow>", content)
+}
+
+fn mathml_call(func: &str, args: &[ASTNode]) -> String {
+    let arg = |i: usize| args.get(i).map(|arg| arg.mathml()).unwrap_or_default();
+
+    match (func, args.len()) {
+        ("sqrt", 1) => format!("<msqrt>{}</msqrt>", arg(0)),
+        ("cbrt", 1) => format!("<mroot>{}<mn>3</mn></mroot>", arg(0)),
+        ("root", 2) => format!("<mroot>{}{}</mroot>", arg(1), arg(0)),
+        ("abs", 1) => format!("<mrow><mo>|</mo>{}<mo>|</mo></mrow>", arg(0)),
+        ("floor", 1) => format!("<mrow><mo>&#x230A;</mo>{}<mo>&#x230B;</mo></mrow>", arg(0)),
+        ("ceil", 1) => format!("<mrow><mo>&#x2308;</mo>{}<mo>&#x2309;</mo></mrow>", arg(0)),
+        ("factorial", 1) => format!("<mrow>{}<mo>!</mo></mrow>", args[0].mathml_base()),
+        ("sqr", 1) => format!("<msup>{}<mn>2</mn></msup>", args[0].mathml_base()),
+        ("pow", 2) => format!("<msup>{}{}</msup>", args[0].mathml_base(), arg(1)),
+        ("log", 2) => apply(&format!("<msub><mi>log</mi>{}</msub>", arg(1)), &args[..1]),
+        ("log2", 1) => apply("<msub><mi>log</mi><mn>2</mn></msub>", args),
+        _ => apply(&format!("<mi>{}</mi>", mathml_function_name(func)), args),
+    }
+}
+
+/// name&ApplyFunction;(arguments)
+fn apply(name: &str, args: &[ASTNode]) -> String {
+    let args = args
+        .iter()
+        .map(|arg| arg.mathml())
+        .collect::<Vec<String>>()
+        .join("<mo>,</mo>");
+    format!("<mrow>{}<mo>&#x2061;</mo>{}</mrow>", name, fenced(&args))
+}
+
+fn mathml_function_name(func: &str) -> &str {
+    match func {
+        "cotan" => "cot",
+        "gamma" => "&#x0393;",
+        "digamma" => "&#x03C8;",
+        "sign" => "sgn",
+        _ => func,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::print::lex_parse_input;
+
+    /// The content of the <math> element
+    fn mathml(input: &str) -> String {
+        let math = lex_parse_input(input).unwrap().to_mathml();
+        math.strip_prefix("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">")
+            .and_then(|content| content.strip_suffix("</math>"))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn parentheses_follow_the_precedence() {
+        assert_eq!(
+            mathml("(1 + 2) * 3"),
+            "<mrow><mrow><mo>(</mo><mrow><mn>1</mn><mo>+</mo><mn>2</mn></mrow><mo>)</mo></mrow>\
+             <mo>&#x22C5;</mo><mn>3</mn></mrow>"
+        );
+        assert_eq!(
+            mathml("2 - 3 + 4"),
+            "<mrow><mrow><mn>2</mn><mo>-</mo><mn>3</mn></mrow><mo>+</mo><mn>4</mn></mrow>"
+        );
+        assert_eq!(
+            mathml("1 - (2 - 3)"),
+            "<mrow><mn>1</mn><mo>-</mo>\
+             <mrow><mo>(</mo><mrow><mn>2</mn><mo>-</mo><mn>3</mn></mrow><mo>)</mo></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("x / (y + 1)"),
+            "<mfrac><mi>x</mi><mrow><mi>y</mi><mo>+</mo><mn>1</mn></mrow></mfrac>"
+        );
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(mathml("-3"), "<mrow><mo>-</mo><mn>3</mn></mrow>");
+        assert_eq!(
+            mathml("-(x + 1)"),
+            "<mrow><mo>-</mo>\
+             <mrow><mo>(</mo><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mo>)</mo></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("-x^2"),
+            "<msup><mrow><mo>(</mo><mrow><mo>-</mo><mi>x</mi></mrow><mo>)</mo></mrow><mn>2</mn></msup>"
+        );
+    }
+
+    #[test]
+    fn powers() {
+        assert_eq!(
+            mathml("2^3^2"),
+            "<msup><mn>2</mn><msup><mn>3</mn><mn>2</mn></msup></msup>"
+        );
+        assert_eq!(
+            mathml("(2^3)^2"),
+            "<msup><mrow><mo>(</mo><msup><mn>2</mn><mn>3</mn></msup><mo>)</mo></mrow><mn>2</mn></msup>"
+        );
+        assert_eq!(
+            mathml("(1/2)^x"),
+            "<msup><mrow><mo>(</mo><mfrac><mn>1</mn><mn>2</mn></mfrac><mo>)</mo></mrow><mi>x</mi></msup>"
+        );
+        assert_eq!(
+            mathml("1.3E+2"),
+            "<mrow><mn>1.3</mn><mo>&#x00D7;</mo><msup><mn>10</mn><mn>2</mn></msup></mrow>"
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            mathml("sin(x)^2"),
+            "<msup><mrow><mi>sin</mi><mo>&#x2061;</mo><mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow></mrow>\
+             <mn>2</mn></msup>"
+        );
+        assert_eq!(mathml("root(3, x)"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+        assert_eq!(
+            mathml("log(8, 2)"),
+            "<mrow><msub><mi>log</mi><mn>2</mn></msub><mo>&#x2061;</mo>\
+             <mrow><mo>(</mo><mn>8</mn><mo>)</mo></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("max(x, pi)"),
+            "<mrow><mi>max</mi><mo>&#x2061;</mo>\
+             <mrow><mo>(</mo><mi>x</mi><mo>,</mo><mi>&#x03C0;</mi><mo>)</mo></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("factorial(n + 1)"),
+            "<mrow><mrow><mo>(</mo><mrow><mi>n</mi><mo>+</mo><mn>1</mn></mrow><mo>)</mo></mrow>\
+             <mo>!</mo></mrow>"
+        );
+    }
+}
@@ -1,10 +1,25 @@
+pub mod angle;
+pub mod derive;
+pub mod dot;
 pub mod eval;
+pub mod func;
+pub mod infix;
+pub mod latex;
+pub mod mathml;
 pub mod plot;
+pub mod polynomial;
 pub mod postfix;
+pub mod program;
+pub mod resolver;
 pub mod root;
+pub mod root_report;
+pub mod settings;
+pub mod simplify;
+pub mod solve;
 pub mod var;
 pub mod wrapper;
 
+use crate::builtins::{power_check, Builtin};
 use crate::errors::{
     eval::EvaluationError, plot::PlottingError, root::RootFinderError, CompilerError,
 };
@@ -12,17 +27,21 @@ use crate::lexer::{
     span::TextSpan,
     token::{Num, TokenKind},
 };
-use crate::utils::{
-    prompt::get_and_parse_user_input, rand::generate_random_4_digits,
-};
+use crate::utils::{prompt::get_and_parse_user_input, rand::generate_random_4_digits};
+use serde::{Deserialize, Serialize};
 
+use angle::AngleMode;
 use eval::Evaluator;
+use func::UserFunction;
 use plot::FunctionPlotter;
+use program::{Program, Statement};
+use resolver::VariableResolver;
 use root::RootFinder;
+use settings::{DomainMode, EvalSettings};
 use var::VariableManager;
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ASTNode {
     Number(Num, TextSpan),
     BinaryOp(Box<ASTNode>, TokenKind, Box<ASTNode>, TextSpan),
@@ -30,11 +49,27 @@ pub enum ASTNode {
     Mantissa(String, TextSpan),
     Constant(TokenKind, TextSpan),
     Identifier(String, TextSpan),
-    FunctionCall(String, Box<ASTNode>, TextSpan),
+    FunctionCall(String, Vec<ASTNode>, TextSpan),
+    /// Placeholder for input that could not be parsed
+    Error(TextSpan),
 }
 
 /// Method that returns string representation of the AST in tree format
 impl ASTNode {
+    /// Location of the node in the source. Operators and calls point at their token.
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            ASTNode::Number(_, span)
+            | ASTNode::BinaryOp(_, _, _, span)
+            | ASTNode::UnaryOp(_, _, span)
+            | ASTNode::Mantissa(_, span)
+            | ASTNode::Constant(_, span)
+            | ASTNode::Identifier(_, span)
+            | ASTNode::FunctionCall(_, _, span)
+            | ASTNode::Error(span) => span,
+        }
+    }
+
     pub fn stringify(&self, prefix: String, is_left: bool) -> String {
         match self {
             // Formatting a number node
@@ -104,7 +139,7 @@ impl ASTNode {
             }
 
             // Formatting a function call node: sin(x), sqrt(x), ...
-            ASTNode::FunctionCall(func, arg, _) => {
+            ASTNode::FunctionCall(func, args, _) => {
                 let mut result = format!(
                     "{}{}{}\n",
                     prefix,
@@ -112,9 +147,21 @@ impl ASTNode {
                     func
                 );
                 let new_prefix = format!("{}{}", prefix, if is_left { "│   " } else { "    " });
-                result.push_str(&arg.stringify(new_prefix, false));
+                for (i, arg) in args.iter().enumerate() {
+                    let is_last = i + 1 == args.len();
+                    result.push_str(&arg.stringify(new_prefix.clone(), !is_last));
+                }
                 result
             }
+
+            // Formatting a placeholder for invalid input
+            ASTNode::Error(_) => {
+                format!(
+                    "{}{}<error>\n",
+                    prefix,
+                    if is_left { "├── " } else { "└── " }
+                )
+            }
         }
     }
 }
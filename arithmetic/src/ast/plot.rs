@@ -1,22 +1,35 @@
 use super::{
-    generate_random_4_digits, get_and_parse_user_input, ASTNode, CompilerError, Evaluator,
-    PlottingError, RootFinder, VariableManager,
+    generate_random_4_digits, ASTNode, CompilerError, EvalSettings, Evaluator, PlottingError,
+    RootFinder, VariableManager, VariableResolver,
 };
+use crate::vm::CompiledFunction;
 use plotters::prelude::*;
 use std::fs;
 
 pub struct FunctionPlotter<'a> {
     vars: &'a mut VariableManager,
+    resolver: &'a mut dyn VariableResolver,
+    settings: EvalSettings,
 }
 
 impl<'a> FunctionPlotter<'a> {
-    pub fn new(vars: &'a mut VariableManager) -> Self {
-        Self { vars }
+    pub fn new(vars: &'a mut VariableManager, resolver: &'a mut dyn VariableResolver) -> Self {
+        Self {
+            vars,
+            resolver,
+            settings: EvalSettings::default(),
+        }
+    }
+
+    /// Sets the angle unit and the domain mode used while plotting
+    pub fn with_settings(mut self, settings: EvalSettings) -> Self {
+        self.settings = settings;
+        self
     }
 
-    fn evaluate_with_x(&mut self, ast: &ASTNode, x: f64) -> Result<f64, CompilerError> {
-        let mut evaluator: Evaluator<'_> = Evaluator::new(self.vars);
-        evaluator.evaluate_with_x(ast, x)
+    /// Compiles the expression to bytecode once for all the sample points
+    fn compile(&mut self, ast: &ASTNode) -> Result<CompiledFunction, CompilerError> {
+        CompiledFunction::new(ast, self.vars, self.resolver, self.settings)
     }
 
     pub fn plot_function(
@@ -24,10 +37,26 @@ impl<'a> FunctionPlotter<'a> {
         ast: &ASTNode,
         a: Option<f64>,
         b: Option<f64>,
+        output: Option<&str>,
     ) -> Result<(), CompilerError> {
-        // Get user input
-        let a = a.unwrap_or_else(|| get_and_parse_user_input("a"));
-        let b = b.unwrap_or_else(|| get_and_parse_user_input("b"));
+        self.plot_functions(std::slice::from_ref(ast), a, b, output)
+    }
+
+    /// Plots several functions in one image, for example f and f'.
+    /// The roots are marked for the first one.
+    pub fn plot_functions(
+        &mut self,
+        asts: &[ASTNode],
+        a: Option<f64>,
+        b: Option<f64>,
+        output: Option<&str>,
+    ) -> Result<(), CompilerError> {
+        let ast = &asts[0];
+
+        // Ask for a missing end of the interval, if the resolver may
+        let missing = || CompilerError::Plot(PlottingError::MissingInterval);
+        let a = a.or_else(|| self.resolver.ask("a")).ok_or_else(missing)?;
+        let b = b.or_else(|| self.resolver.ask("b")).ok_or_else(missing)?;
 
         // Generate x values
         let sample_points = 1000;
@@ -35,14 +64,25 @@ impl<'a> FunctionPlotter<'a> {
             .map(|i| a + (b - a) * (i as f64 / sample_points as f64))
             .collect();
 
-        // Pre-calculate all y values
-        let mut y_values = Vec::with_capacity(x_values.len());
-        for &x in x_values.iter() {
-            y_values.push(self.evaluate_with_x(ast, x).unwrap_or(0.0));
+        // Pre-calculate all y values. Points outside of the domain are left out,
+        // a function that does not compile has no points at all.
+        let mut series = Vec::with_capacity(asts.len());
+        for function in asts.iter() {
+            let mut compiled = self.compile(function);
+            let mut y_values = Vec::with_capacity(x_values.len());
+            for &x in x_values.iter() {
+                let y = compiled
+                    .as_mut()
+                    .ok()
+                    .and_then(|compiled| compiled.call(x).ok())
+                    .filter(|y| y.is_finite());
+                y_values.push(y);
+            }
+            series.push(y_values);
         }
 
         // Create a new evaluator for root finding
-        let mut evaluator = Evaluator::new(self.vars);
+        let mut evaluator = Evaluator::new(self.vars, self.resolver).with_settings(self.settings);
         let mut root_finder = RootFinder::new(ast, &mut evaluator);
         let roots = root_finder
             .find_roots(Some(a), Some(b))
@@ -50,12 +90,15 @@ impl<'a> FunctionPlotter<'a> {
 
         // Pre-calculate root y-values
         let mut root_points = Vec::new();
-        for &root in roots.iter() {
-            let y = self.evaluate_with_x(ast, root).unwrap_or(0.0);
-            root_points.push((root, y));
+        if let Ok(mut compiled) = self.compile(ast) {
+            for root in roots.values() {
+                if let Some(y) = compiled.call(root).ok().filter(|y| y.is_finite()) {
+                    root_points.push((root, y));
+                }
+            }
         }
 
-        self.create_and_save_plot(a, b, x_values, y_values, root_points)
+        self.create_and_save_plot(a, b, x_values, series, root_points, output)
     }
 
     fn create_and_save_plot(
@@ -63,21 +106,38 @@ impl<'a> FunctionPlotter<'a> {
         a: f64,
         b: f64,
         x_values: Vec<f64>,
-        y_values: Vec<f64>,
+        series: Vec<Vec<Option<f64>>>,
         root_points: Vec<(f64, f64)>,
+        output: Option<&str>,
     ) -> Result<(), CompilerError> {
         // Calculate y range
-        let y_min = y_values.iter().copied().reduce(f64::min).unwrap_or(0.0);
-        let y_max = y_values.iter().copied().reduce(f64::max).unwrap_or(0.0);
+        let y_min = series
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .reduce(f64::min)
+            .unwrap_or(0.0);
+        let y_max = series
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .reduce(f64::max)
+            .unwrap_or(0.0);
         let y_padding = (y_max - y_min) * 0.1;
 
-        // Create plots directory
-        fs::create_dir_all("plots")
-            .map_err(|_| CompilerError::Plot(PlottingError::FileCreationError))?;
+        // Use the given path or generate a random filename in the plots directory
+        let file_path = match output {
+            Some(path) => path.to_string(),
+            None => {
+                fs::create_dir_all("plots")
+                    .map_err(|_| CompilerError::Plot(PlottingError::FileCreationError))?;
 
-        // Generate random filename
-        let random_name = format!("plot_{}.png", generate_random_4_digits());
-        let file_path = format!("plots/{}", random_name);
+                let random_name = format!("plot_{}.png", generate_random_4_digits());
+                format!("plots/{}", random_name)
+            }
+        };
 
         // Create the plot
         let root = BitMapBackend::new(&file_path, (1920, 1080)).into_drawing_area();
@@ -100,13 +160,23 @@ impl<'a> FunctionPlotter<'a> {
             .draw()
             .map_err(|_| CompilerError::Plot(PlottingError::GenericError))?;
 
-        // Draw function
-        chart
-            .draw_series(LineSeries::new(
-                x_values.into_iter().zip(y_values.into_iter()),
-                &RED,
-            ))
-            .map_err(|_| CompilerError::Plot(PlottingError::GenericError))?;
+        // Draw the functions, one line per run of defined points
+        let colors = [RED, BLUE, GREEN, MAGENTA, CYAN];
+        for (y_values, color) in series.into_iter().zip(colors.iter().cycle()) {
+            let mut segments: Vec<Vec<(f64, f64)>> = vec![Vec::new()];
+            for (&x, y) in x_values.iter().zip(y_values) {
+                match y {
+                    Some(y) => segments.last_mut().unwrap().push((x, y)),
+                    None if !segments.last().unwrap().is_empty() => segments.push(Vec::new()),
+                    None => {}
+                }
+            }
+            for segment in segments.into_iter().filter(|segment| !segment.is_empty()) {
+                chart
+                    .draw_series(LineSeries::new(segment, color))
+                    .map_err(|_| CompilerError::Plot(PlottingError::GenericError))?;
+            }
+        }
 
         // Draw roots
         if !root_points.is_empty() {
@@ -0,0 +1,582 @@
+use super::{ASTNode, CompilerError, Evaluator, TokenKind};
+use serde::Serialize;
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// Highest degree the recognizer accepts, x^1000 is left to the root scan
+const MAX_DEGREE: usize = 100;
+
+/// Upper bound of Aberth iterations, simple roots converge in about ten
+const MAX_ITERATIONS: usize = 500;
+
+/// Approximations closer than this, relative to their size, are always tried as one root
+const MIN_CLUSTER_RADIUS: f64 = 1e-2;
+
+/// Complex number, the roots of a real polynomial come in conjugate pairs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// Point on the circle of radius r at angle theta
+    fn polar(r: f64, theta: f64) -> Self {
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let norm = other.re * other.re + other.im * other.im;
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / norm,
+            (self.im * other.re - self.re * other.im) / norm,
+        )
+    }
+}
+
+/// 1.5, 2i, 1.5 - 2i
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.re, self.im) {
+            (re, 0.0) => write!(f, "{}", re + 0.0),
+            (0.0, im) => write!(f, "{}i", im),
+            (re, im) if im < 0.0 => write!(f, "{} - {}i", re, -im),
+            (re, im) => write!(f, "{} + {}i", re, im),
+        }
+    }
+}
+
+/// Polynomial in one variable, coefficients[i] belongs to var^i
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Polynomial {
+    pub variable: String,
+    pub coefficients: Vec<f64>,
+}
+
+impl Polynomial {
+    fn constant(variable: &str, value: f64) -> Self {
+        Self {
+            variable: variable.to_string(),
+            coefficients: vec![value],
+        }
+    }
+
+    fn with_coefficients(&self, coefficients: Vec<f64>) -> Self {
+        let mut polynomial = Self {
+            variable: self.variable.clone(),
+            coefficients,
+        };
+        polynomial.trim();
+        polynomial
+    }
+
+    /// Drops the zero coefficients of the highest powers
+    fn trim(&mut self) {
+        while self.coefficients.len() > 1 && self.coefficients.last() == Some(&0.0) {
+            self.coefficients.pop();
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    fn add(&self, other: &Polynomial, sign: f64) -> Polynomial {
+        let length = self.coefficients.len().max(other.coefficients.len());
+        let coefficients = (0..length)
+            .map(|i| {
+                let a = self.coefficients.get(i).copied().unwrap_or(0.0);
+                let b = other.coefficients.get(i).copied().unwrap_or(0.0);
+                a + sign * b
+            })
+            .collect();
+        self.with_coefficients(coefficients)
+    }
+
+    fn mul(&self, other: &Polynomial) -> Polynomial {
+        let mut coefficients = vec![0.0; self.coefficients.len() + other.coefficients.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] += a * b;
+            }
+        }
+        self.with_coefficients(coefficients)
+    }
+
+    fn scale(&self, factor: f64) -> Polynomial {
+        self.with_coefficients(self.coefficients.iter().map(|c| c * factor).collect())
+    }
+
+    /// p(z) and p'(z) with Horner's scheme
+    fn evaluate(&self, z: Complex) -> (Complex, Complex) {
+        let zero = Complex::new(0.0, 0.0);
+        self.coefficients
+            .iter()
+            .rev()
+            .fold((zero, zero), |(value, slope), &c| {
+                (value * z + Complex::new(c, 0.0), slope * z + value)
+            })
+    }
+
+    /// Bound of the rounding error of p(z) for |z| = r
+    fn rounding_error(&self, r: f64) -> f64 {
+        let magnitude = self
+            .coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |sum, c| sum * r + c.abs());
+        4.0 * f64::EPSILON * magnitude
+    }
+
+    /// All complex roots, repeated by their multiplicity. Zero roots are split off
+    /// exactly, the others are found together with the Aberth-Ehrlich method.
+    pub fn roots(&self) -> PolynomialRoots {
+        let zeros = self.coefficients.iter().take_while(|&&c| c == 0.0).count();
+        let reduced = self.with_coefficients(self.coefficients[zeros..].to_vec());
+
+        let (roots, iterations, converged) = reduced.aberth();
+        let mut roots = conjugate_pairs(reduced.merge_clusters(roots));
+        roots.extend(std::iter::repeat_n(Complex::new(0.0, 0.0), zeros));
+        roots.sort_by(|a, b| a.re.total_cmp(&b.re).then(b.im.total_cmp(&a.im)));
+
+        PolynomialRoots {
+            polynomial: self.clone(),
+            roots,
+            iterations,
+            converged,
+        }
+    }
+
+    /// Improves all approximations at once: the Newton step of each root is corrected by
+    /// the repulsion of the others, so they do not converge to the same root. Starts on a
+    /// circle around the centroid of the roots.
+    fn aberth(&self) -> (Vec<Complex>, usize, bool) {
+        let n = self.degree();
+        if n == 0 {
+            return (Vec::new(), 0, true);
+        }
+
+        let leading = self.coefficients[n];
+        let centre = Complex::new(-self.coefficients[n - 1] / (n as f64 * leading), 0.0);
+        let radius = match (self.evaluate(centre).0.abs() / leading.abs()).powf(1.0 / n as f64) {
+            r if r > 0.0 && r.is_finite() => r,
+            _ => 1.0,
+        };
+        // The offset keeps the start points off the real axis
+        let mut roots: Vec<Complex> = (0..n)
+            .map(|k| centre + Complex::polar(radius, 2.0 * PI * k as f64 / n as f64 + 0.4))
+            .collect();
+
+        for iteration in 1..=MAX_ITERATIONS {
+            let mut converged = true;
+            for k in 0..n {
+                // Below the rounding error of Horner's scheme p(z) is as good as 0
+                let (value, slope) = self.evaluate(roots[k]);
+                if value.abs() <= self.rounding_error(roots[k].abs()) {
+                    continue;
+                }
+
+                let newton = value / slope;
+                let repulsion = (0..n)
+                    .filter(|&j| j != k)
+                    .fold(Complex::new(0.0, 0.0), |sum, j| {
+                        sum + Complex::new(1.0, 0.0) / (roots[k] - roots[j])
+                    });
+                let step = newton / (Complex::new(1.0, 0.0) - newton * repulsion);
+                if !(step.re.is_finite() && step.im.is_finite()) {
+                    continue;
+                }
+
+                roots[k] = roots[k] - step;
+                if step.abs() > f64::EPSILON * 4.0 * roots[k].abs().max(f64::MIN_POSITIVE) {
+                    converged = false;
+                }
+            }
+            if converged {
+                return (self.real_parts(roots), iteration, true);
+            }
+        }
+
+        (self.real_parts(roots), MAX_ITERATIONS, false)
+    }
+
+    /// The imaginary part of a real root is rounding noise, larger for multiple roots.
+    /// It is dropped when p is not larger on the real axis. The same for the real part
+    /// of an imaginary root.
+    fn real_parts(&self, roots: Vec<Complex>) -> Vec<Complex> {
+        roots
+            .into_iter()
+            .map(|root| {
+                let tiny = 1e-12 * root.abs().max(1.0);
+                let real = Complex::new(root.re, 0.0);
+                let root = match root.im.abs() <= tiny
+                    || self.evaluate(real).0.abs() <= self.evaluate(root).0.abs()
+                {
+                    true => real,
+                    false => root,
+                };
+                match root.re.abs() <= tiny {
+                    true => Complex::new(0.0, root.im),
+                    false => root,
+                }
+            })
+            .collect()
+    }
+
+    /// A root of multiplicity m is only found to about the m-th root of the precision,
+    /// as m approximations around it. It is a simple root of the (m-1)-th derivative, so
+    /// Newton steps on that from their mean find it to full precision. The clusters start
+    /// as wide as the spread of a root of the full degree and are split again with a
+    /// smaller radius when p is smaller at the approximations than at the merged root.
+    fn merge_clusters(&self, roots: Vec<Complex>) -> Vec<Complex> {
+        let spread = 4.0 * f64::EPSILON.powf(1.0 / self.degree().max(1) as f64);
+        self.merge_within(roots, spread.max(MIN_CLUSTER_RADIUS))
+    }
+
+    /// Approximations closer than radius to any member, relative to their size, form a
+    /// cluster
+    fn merge_within(&self, roots: Vec<Complex>, radius: f64) -> Vec<Complex> {
+        let mut merged = Vec::new();
+        let mut rest = roots;
+        while let Some(root) = rest.pop() {
+            let distance = radius * root.abs().max(1.0);
+            let mut cluster = vec![root];
+            while let Some(i) = rest
+                .iter()
+                .position(|other| cluster.iter().any(|z| (*other - *z).abs() <= distance))
+            {
+                cluster.push(rest.remove(i));
+            }
+
+            let m = cluster.len();
+            match self.merge(&cluster) {
+                _ if m == 1 => merged.push(root),
+                Some(centre) => merged.extend(std::iter::repeat_n(centre, m)),
+                None if radius > MIN_CLUSTER_RADIUS => {
+                    merged.extend(self.merge_within(cluster, radius / 4.0))
+                }
+                None => merged.extend(cluster),
+            }
+        }
+        merged
+    }
+
+    /// The root of multiplicity cluster.len() the cluster approximates, None when p is
+    /// larger there than at the approximations
+    fn merge(&self, cluster: &[Complex]) -> Option<Complex> {
+        let m = cluster.len();
+        let sum = cluster
+            .iter()
+            .fold(Complex::new(0.0, 0.0), |sum, &z| sum + z);
+        let mut centre = sum / Complex::new(m as f64, 0.0);
+        let derivative = self.derivative(m - 1);
+        for _ in 0..MAX_ITERATIONS {
+            let (value, slope) = derivative.evaluate(centre);
+            let step = value / slope;
+            if value.abs() == 0.0 || !(step.re.is_finite() && step.im.is_finite()) {
+                break;
+            }
+            centre = centre - step;
+            if step.abs() <= f64::EPSILON * centre.abs() {
+                break;
+            }
+        }
+
+        let centre = self.real_parts(vec![centre])[0];
+        let worst = cluster
+            .iter()
+            .map(|&z| self.evaluate(z).0.abs())
+            .fold(0.0, f64::max);
+        (self.evaluate(centre).0.abs() <= worst).then_some(centre)
+    }
+
+    /// The k-th derivative
+    fn derivative(&self, k: usize) -> Polynomial {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .skip(k)
+            .map(|(i, c)| c * ((i - k + 1)..=i).map(|f| f as f64).product::<f64>())
+            .collect();
+        self.with_coefficients(coefficients)
+    }
+}
+
+/// 1, 0, -2, -5 for x^3 - 2x - 5
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .rev()
+            .map(|c| (c + 0.0).to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        write!(f, "{}", coefficients)
+    }
+}
+
+/// All roots of a polynomial, found without an interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PolynomialRoots {
+    pub polynomial: Polynomial,
+    pub roots: Vec<Complex>,
+    pub iterations: usize,
+    /// false when the iteration limit was reached first
+    pub converged: bool,
+}
+
+impl PolynomialRoots {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("polynomial roots always serialize")
+    }
+}
+
+/// Polynomial of degree 2 in x, coefficients 1, 0, 1:
+///   x = 0 + 1i
+///   x = 0 - 1i
+impl fmt::Display for PolynomialRoots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Polynomial of degree {} in {}, coefficients {}:",
+            self.polynomial.degree(),
+            self.polynomial.variable,
+            self.polynomial
+        )?;
+        for root in self.roots.iter() {
+            write!(f, "\n  {} = {}", self.polynomial.variable, root)?;
+        }
+        match self.converged {
+            true => write!(f, "\nAberth method, {} iterations", self.iterations),
+            false => write!(
+                f,
+                "\nAberth method did not converge within {} iterations",
+                self.iterations
+            ),
+        }
+    }
+}
+
+impl ASTNode {
+    /// Coefficients of the expression as a polynomial in var, None if it is not one.
+    /// User functions must be inlined first. Parts without var are evaluated, so other
+    /// variables get their values like in an evaluation.
+    pub fn polynomial(
+        &self,
+        var: &str,
+        evaluator: &mut Evaluator,
+    ) -> Result<Option<Polynomial>, CompilerError> {
+        if !self.depends_on(var) {
+            let value = evaluator.evaluate(self)?;
+            return Ok(value.is_finite().then(|| Polynomial::constant(var, value)));
+        }
+
+        let polynomial = match self {
+            ASTNode::Identifier(_, _) => Polynomial {
+                variable: var.to_string(),
+                coefficients: vec![0.0, 1.0],
+            },
+            ASTNode::UnaryOp(TokenKind::Plus, expr, _) => return expr.polynomial(var, evaluator),
+            ASTNode::UnaryOp(TokenKind::Minus, expr, _) => match expr.polynomial(var, evaluator)? {
+                Some(p) => p.scale(-1.0),
+                None => return Ok(None),
+            },
+            ASTNode::BinaryOp(left, op, right, _) => {
+                let Some(left) = left.polynomial(var, evaluator)? else {
+                    return Ok(None);
+                };
+                let Some(right) = right.polynomial(var, evaluator)? else {
+                    return Ok(None);
+                };
+                match (op, right.degree()) {
+                    (TokenKind::Plus, _) => left.add(&right, 1.0),
+                    (TokenKind::Minus, _) => left.add(&right, -1.0),
+                    (TokenKind::Multiply, _) => left.mul(&right),
+                    (TokenKind::Divide, 0) if right.coefficients[0] != 0.0 => {
+                        left.scale(1.0 / right.coefficients[0])
+                    }
+                    (TokenKind::Power, 0) => match natural_exponent(right.coefficients[0]) {
+                        Some(n) if left.degree() * n <= MAX_DEGREE => {
+                            (0..n).fold(Polynomial::constant(var, 1.0), |p, _| p.mul(&left))
+                        }
+                        _ => return Ok(None),
+                    },
+                    _ => return Ok(None),
+                }
+            }
+            ASTNode::FunctionCall(name, args, _)
+                if name.eq_ignore_ascii_case("sqr") && args.len() == 1 =>
+            {
+                match args[0].polynomial(var, evaluator)? {
+                    Some(p) => p.mul(&p),
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok((polynomial.degree() <= MAX_DEGREE).then_some(polynomial))
+    }
+}
+
+/// The complex roots of a real polynomial are conjugate pairs, every root above the real
+/// axis is paired with the closest one below and both get their mean
+fn conjugate_pairs(mut roots: Vec<Complex>) -> Vec<Complex> {
+    let mut paired = vec![false; roots.len()];
+    for i in 0..roots.len() {
+        if roots[i].im <= 0.0 || paired[i] {
+            continue;
+        }
+        let conjugate = Complex::new(roots[i].re, -roots[i].im);
+        let partner = (0..roots.len())
+            .filter(|&j| roots[j].im < 0.0 && !paired[j])
+            .min_by(|&a, &b| {
+                let a = (roots[a] - conjugate).abs();
+                let b = (roots[b] - conjugate).abs();
+                a.total_cmp(&b)
+            });
+        if let Some(j) = partner {
+            let re = (roots[i].re + roots[j].re) / 2.0;
+            let im = (roots[i].im - roots[j].im) / 2.0;
+            roots[i] = Complex::new(re, im);
+            roots[j] = Complex::new(re, -im);
+            paired[i] = true;
+            paired[j] = true;
+        }
+    }
+    roots
+}
+
+/// 3.0 -> 3, exponents of a polynomial are whole numbers from 0
+fn natural_exponent(value: f64) -> Option<usize> {
+    (value >= 0.0 && value.fract() == 0.0 && value <= MAX_DEGREE as f64).then_some(value as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::resolver::MapOnlyResolver;
+    use super::super::VariableManager;
+    use super::*;
+    use crate::utils::print::lex_parse_input;
+
+    fn roots(input: &str) -> PolynomialRoots {
+        let ast = lex_parse_input(input).unwrap();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        ast.polynomial("x", &mut evaluator)
+            .unwrap()
+            .expect("a polynomial")
+            .roots()
+    }
+
+    /// Every expected root, with its multiplicity, is matched by one of the roots
+    fn assert_roots(input: &str, expected: &[(Complex, usize)], tolerance: f64) {
+        let found = roots(input);
+        assert!(found.converged, "{}", found);
+        let total: usize = expected.iter().map(|(_, m)| m).sum();
+        assert_eq!(found.roots.len(), total, "{}", found);
+        for &(root, multiplicity) in expected {
+            let close = found
+                .roots
+                .iter()
+                .filter(|&&z| (z - root).abs() <= tolerance)
+                .count();
+            assert_eq!(close, multiplicity, "{} of {}\n{}", root, input, found);
+        }
+    }
+
+    #[test]
+    fn simple_roots() {
+        let sqrt2 = 2f64.sqrt();
+        assert_roots(
+            "x^3 - 2*x",
+            &[
+                (Complex::new(-sqrt2, 0.0), 1),
+                (Complex::new(0.0, 0.0), 1),
+                (Complex::new(sqrt2, 0.0), 1),
+            ],
+            1e-12,
+        );
+        assert_roots(
+            "x^2 + 1",
+            &[(Complex::new(0.0, 1.0), 1), (Complex::new(0.0, -1.0), 1)],
+            1e-12,
+        );
+    }
+
+    #[test]
+    fn roots_of_unity_stay_apart() {
+        let expected: Vec<(Complex, usize)> = (0..20)
+            .map(|k| (Complex::polar(1.0, 2.0 * PI * k as f64 / 20.0), 1))
+            .collect();
+        assert_roots("x^20 - 1", &expected, 1e-12);
+    }
+
+    #[test]
+    fn close_simple_roots_stay_apart() {
+        assert_roots(
+            "(x - 1)*(x - 1.001)",
+            &[(Complex::new(1.0, 0.0), 1), (Complex::new(1.001, 0.0), 1)],
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn multiple_roots_are_merged() {
+        assert_roots(
+            "(x - 1)^5*(x + 2)^3",
+            &[(Complex::new(1.0, 0.0), 5), (Complex::new(-2.0, 0.0), 3)],
+            1e-9,
+        );
+        assert_roots("(x - 1)^10", &[(Complex::new(1.0, 0.0), 10)], 1e-9);
+    }
+
+    #[test]
+    fn multiple_complex_roots_are_merged() {
+        // The approximations of a tenfold root are scattered about 3e-2 around it
+        assert_roots(
+            "(x^2 + 1)^10",
+            &[(Complex::new(0.0, 1.0), 10), (Complex::new(0.0, -1.0), 10)],
+            1e-9,
+        );
+    }
+}
@@ -49,12 +49,15 @@ impl ASTNode {
                 result
             }
 
-            // Handle a function call: sin(x), sqrt(x)
-            ASTNode::FunctionCall(func, arg, _) => {
-                let mut result = arg.postfix(); // Argument
+            // Handle a function call: sin(x), sqrt(x), f(x, y)
+            ASTNode::FunctionCall(func, args, _) => {
+                let mut result = args.iter().map(|arg| arg.postfix()).collect::<String>(); // Arguments
                 result.push_str(&format!("{} ", func)); // Append function name
                 result
             }
+
+            // Handle a placeholder for invalid input
+            ASTNode::Error(_) => "<error> ".to_string(),
         }
     }
 }
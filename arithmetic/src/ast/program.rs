@@ -0,0 +1,304 @@
+use super::{
+    dot::{dot_edge, dot_graph, dot_node},
+    mathml::math,
+    ASTNode, TextSpan, TokenKind,
+};
+use serde::{Deserialize, Serialize};
+
+/// A single line of a program: `x := 2 * pi`, `f(x) := x^2`, `2*x + 1 = 7` or an expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Statement {
+    Assign(String, ASTNode, TextSpan),
+    FunctionDef(String, Vec<String>, ASTNode, TextSpan),
+    /// lhs = rhs, the span is the one of `=`
+    Equation(ASTNode, ASTNode, TextSpan),
+    Expression(ASTNode),
+}
+
+/// lhs - rhs: it is 0 exactly where the equation lhs = rhs holds
+pub fn difference(lhs: &ASTNode, rhs: &ASTNode, span: &TextSpan) -> ASTNode {
+    ASTNode::BinaryOp(
+        Box::new(lhs.clone()),
+        TokenKind::Minus,
+        Box::new(rhs.clone()),
+        span.clone(),
+    )
+}
+
+/// Statements separated by newlines or `;`. The last statement is the result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+impl Statement {
+    /// returns string representation of the statement in tree format
+    pub fn stringify(&self) -> String {
+        match self {
+            Statement::Assign(name, expr, _) => {
+                let mut result = format!("└── :=\n    ├── {}\n", name);
+                result.push_str(&expr.stringify("    ".to_string(), false));
+                result
+            }
+            Statement::FunctionDef(name, params, body, _) => {
+                let mut result = format!("└── :=\n    ├── {}({})\n", name, params.join(", "));
+                result.push_str(&body.stringify("    ".to_string(), false));
+                result
+            }
+            Statement::Equation(lhs, rhs, _) => {
+                let mut result = "└── =\n".to_string();
+                result.push_str(&lhs.stringify("    ".to_string(), true));
+                result.push_str(&rhs.stringify("    ".to_string(), false));
+                result
+            }
+            Statement::Expression(expr) => expr.stringify("".to_string(), false),
+        }
+    }
+
+    /// returns the postfix notation of the statement
+    pub fn postfix(&self) -> String {
+        match self {
+            Statement::Assign(name, expr, _) => format!("{} {}:= ", name, expr.postfix()),
+            Statement::FunctionDef(name, params, body, _) => {
+                format!("{}({}) {}:= ", name, params.join(", "), body.postfix())
+            }
+            Statement::Equation(lhs, rhs, _) => format!("{}{}= ", lhs.postfix(), rhs.postfix()),
+            Statement::Expression(expr) => expr.postfix(),
+        }
+    }
+
+    /// returns the source text of the statement
+    pub fn infix(&self) -> String {
+        match self {
+            Statement::Assign(name, expr, _) => format!("{} := {}", name, expr.infix()),
+            Statement::FunctionDef(name, params, body, _) => {
+                format!("{}({}) := {}", name, params.join(", "), body.infix())
+            }
+            Statement::Equation(lhs, rhs, _) => format!("{} = {}", lhs.infix(), rhs.infix()),
+            Statement::Expression(expr) => expr.infix(),
+        }
+    }
+
+    /// Writes the statement as Graphviz nodes, definitions get a `:=` root
+    fn dot_nodes(&self, next_id: &mut usize, out: &mut String) {
+        let (target, expr, span) = match self {
+            Statement::Assign(name, expr, span) => (name.clone(), expr, span),
+            Statement::FunctionDef(name, params, body, span) => {
+                (format!("{}({})", name, params.join(", ")), body, span)
+            }
+            Statement::Equation(lhs, rhs, span) => {
+                let root = dot_node(next_id, out, "=", span);
+                let lhs = lhs.dot_nodes(next_id, out);
+                dot_edge(out, root, lhs);
+                let rhs = rhs.dot_nodes(next_id, out);
+                dot_edge(out, root, rhs);
+                return;
+            }
+            Statement::Expression(expr) => {
+                expr.dot_nodes(next_id, out);
+                return;
+            }
+        };
+
+        let root = dot_node(next_id, out, ":=", span);
+        let target = dot_node(next_id, out, &target, span);
+        dot_edge(out, root, target);
+        let expr = expr.dot_nodes(next_id, out);
+        dot_edge(out, root, expr);
+    }
+
+    /// returns the statement as LaTeX math
+    pub fn to_latex(&self) -> String {
+        match self {
+            Statement::Assign(name, expr, _) => format!("{} := {}", name, expr.to_latex()),
+            Statement::FunctionDef(name, params, body, _) => {
+                format!("{}({}) := {}", name, params.join(", "), body.to_latex())
+            }
+            Statement::Equation(lhs, rhs, _) => format!("{} = {}", lhs.to_latex(), rhs.to_latex()),
+            Statement::Expression(expr) => expr.to_latex(),
+        }
+    }
+
+    /// returns the statement as a MathML <math> element
+    pub fn to_mathml(&self) -> String {
+        let definition = |lhs: String, expr: &ASTNode| {
+            math(&format!("<mrow>{}<mo>:=</mo>{}</mrow>", lhs, expr.mathml()))
+        };
+
+        match self {
+            Statement::Assign(name, expr, _) => definition(format!("<mi>{}</mi>", name), expr),
+            Statement::FunctionDef(name, params, body, _) => {
+                let params = params
+                    .iter()
+                    .map(|param| format!("<mi>{}</mi>", param))
+                    .collect::<Vec<String>>()
+                    .join("<mo>,</mo>");
+                definition(
+                    format!("<mi>{}</mi><mo>(</mo>{}<mo>)</mo>", name, params),
+                    body,
+                )
+            }
+            Statement::Equation(lhs, rhs, _) => math(&format!(
+                "<mrow>{}<mo>=</mo>{}</mrow>",
+                lhs.mathml(),
+                rhs.mathml()
+            )),
+            Statement::Expression(expr) => expr.to_mathml(),
+        }
+    }
+}
+
+impl Program {
+    pub fn new(statements: Vec<Statement>) -> Self {
+        Self { statements }
+    }
+
+    /// Splits the program into the leading statements and the result expression.
+    /// A trailing function definition is kept as a statement and its body is the result.
+    /// The result of an equation lhs = rhs is lhs - rhs, so its roots are the solutions.
+    pub fn split_result(self) -> (Vec<Statement>, ASTNode) {
+        let mut statements = self.statements;
+        let last = statements
+            .pop()
+            .expect("the parser never returns an empty program");
+
+        let result = match last {
+            Statement::Assign(_, expr, _) => expr,
+            Statement::FunctionDef(_, _, ref body, _) => {
+                let body = body.clone();
+                statements.push(last);
+                body
+            }
+            Statement::Equation(lhs, rhs, span) => difference(&lhs, &rhs, &span),
+            Statement::Expression(expr) => expr,
+        };
+        (statements, result)
+    }
+
+    /// Source text of every statement, one per line
+    pub fn infix(&self) -> String {
+        self.statements
+            .iter()
+            .map(|statement| statement.infix())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// LaTeX of every statement, one per line
+    pub fn to_latex(&self) -> String {
+        self.statements
+            .iter()
+            .map(|statement| statement.to_latex())
+            .collect::<Vec<String>>()
+            .join(" \\\\\n")
+    }
+
+    /// One MathML <math> element per statement
+    pub fn to_mathml(&self) -> String {
+        self.statements
+            .iter()
+            .map(|statement| statement.to_mathml())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// One Graphviz graph with a tree per statement
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let mut next_id = 0;
+        for statement in self.statements.iter() {
+            statement.dot_nodes(&mut next_id, &mut out);
+        }
+        dot_graph(&out)
+    }
+
+    /// JSON serialization of the statements
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("programs always serialize")
+    }
+
+    /// Reads a program written by to_json
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// Tree representation of every statement
+    pub fn stringify(&self) -> String {
+        self.statements
+            .iter()
+            .map(|statement| statement.stringify())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Postfix notation of every statement, one per line
+    pub fn postfix(&self) -> String {
+        self.statements
+            .iter()
+            .map(|statement| statement.postfix())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::print::lex_parse_program;
+
+    fn infix(input: &str) -> Vec<String> {
+        let program = lex_parse_program(input).unwrap();
+        program.statements.iter().map(|s| s.infix()).collect()
+    }
+
+    #[test]
+    fn statements_are_separated_by_newlines_and_semicolons() {
+        assert_eq!(
+            infix("r := 2; area := pi * r^2\n\narea / r;"),
+            ["r := 2", "area := pi * r^2", "area / r"]
+        );
+        assert_eq!(
+            infix("f(x, y) := x*y\nf(2, 3) = 6"),
+            ["f(x, y) := x * y", "f(2, 3) = 6"]
+        );
+    }
+
+    #[test]
+    fn kinds_of_statements() {
+        let program = lex_parse_program("a := 1; g(t) := t + a; 2*a = 3; a + 1").unwrap();
+        assert!(matches!(
+            program.statements.as_slice(),
+            [
+                Statement::Assign(..),
+                Statement::FunctionDef(_, _, _, _),
+                Statement::Equation(..),
+                Statement::Expression(_)
+            ]
+        ));
+        assert_eq!(program.statements[1].postfix(), "g(t) t a + := ");
+        assert_eq!(program.statements[0].postfix(), "a 1 := ");
+    }
+
+    #[test]
+    fn only_names_and_calls_can_be_assigned_to() {
+        assert!(lex_parse_program("3 := 4").is_err());
+        assert!(lex_parse_program("f(2) := 4").is_err());
+        assert!(lex_parse_program("f(x, x) := x").is_err());
+        assert!(lex_parse_program(";;").is_err());
+    }
+
+    #[test]
+    fn result_of_the_program() {
+        let (statements, result) = lex_parse_program("a := 2; a * 3").unwrap().split_result();
+        assert_eq!((statements.len(), result.infix()), (1, "a * 3".to_string()));
+
+        let (statements, result) = lex_parse_program("b := 4").unwrap().split_result();
+        assert_eq!((statements.len(), result.infix()), (0, "4".to_string()));
+
+        let (statements, result) = lex_parse_program("f(x) := x^2").unwrap().split_result();
+        assert_eq!((statements.len(), result.infix()), (1, "x^2".to_string()));
+
+        let (_, result) = lex_parse_program("x^2 = 2").unwrap().split_result();
+        assert_eq!(result.infix(), "x^2 - 2");
+    }
+}
@@ -0,0 +1,198 @@
+use super::{get_and_parse_user_input, CompilerError, EvaluationError, Num, TextSpan};
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// Strategy used by the VariableManager when a variable has no stored value
+pub trait VariableResolver {
+    fn resolve(&mut self, name: &str, span: &TextSpan) -> Result<Num, CompilerError>;
+
+    /// Value of a setting the input does not give, such as an end of the interval.
+    /// Only a resolver that may prompt asks for it.
+    fn ask(&mut self, _name: &str) -> Option<f64> {
+        None
+    }
+}
+
+/// Converts a raw f64 into Integer when it has no fractional part and fits in an i64
+pub fn num_from_f64(value: f64) -> Num {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Num::Integer(value as i64)
+    } else {
+        Num::Float(value)
+    }
+}
+
+/// Only values already stored in the VariableManager are used. Unknown variables are an error.
+pub struct MapOnlyResolver;
+
+impl VariableResolver for MapOnlyResolver {
+    fn resolve(&mut self, name: &str, span: &TextSpan) -> Result<Num, CompilerError> {
+        Err(CompilerError::Eval(EvaluationError::UndefinedVariable(
+            name.to_string(),
+            span.clone(),
+        )))
+    }
+}
+
+/// Asks the user for the value on stdin. A closed stdin leaves the variable undefined.
+pub struct PromptResolver;
+
+impl VariableResolver for PromptResolver {
+    fn resolve(&mut self, name: &str, span: &TextSpan) -> Result<Num, CompilerError> {
+        match get_and_parse_user_input(name) {
+            Some(value) => Ok(num_from_f64(value)),
+            None => MapOnlyResolver.resolve(name, span),
+        }
+    }
+
+    /// Only asks on a terminal, piped input can not answer a question it does not know
+    fn ask(&mut self, name: &str) -> Option<f64> {
+        match io::stdin().is_terminal() {
+            true => get_and_parse_user_input(name),
+            false => None,
+        }
+    }
+}
+
+/// Reads the value from an environment variable: prefix + upper case name (x -> ARITH_X)
+pub struct EnvResolver {
+    prefix: String,
+}
+
+impl EnvResolver {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+impl VariableResolver for EnvResolver {
+    fn resolve(&mut self, name: &str, span: &TextSpan) -> Result<Num, CompilerError> {
+        let key = format!("{}{}", self.prefix, name.to_uppercase());
+
+        match env::var(&key)
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+        {
+            Some(value) => Ok(num_from_f64(value)),
+            None => MapOnlyResolver.resolve(name, span),
+        }
+    }
+}
+
+/// Tries each resolver in order and returns the first value found.
+pub struct ChainResolver {
+    resolvers: Vec<Box<dyn VariableResolver>>,
+}
+
+impl ChainResolver {
+    pub fn new(resolvers: Vec<Box<dyn VariableResolver>>) -> Self {
+        Self { resolvers }
+    }
+}
+
+impl VariableResolver for ChainResolver {
+    fn resolve(&mut self, name: &str, span: &TextSpan) -> Result<Num, CompilerError> {
+        for resolver in self.resolvers.iter_mut() {
+            if let Ok(value) = resolver.resolve(name, span) {
+                return Ok(value);
+            }
+        }
+
+        MapOnlyResolver.resolve(name, span)
+    }
+
+    fn ask(&mut self, name: &str) -> Option<f64> {
+        self.resolvers
+            .iter_mut()
+            .find_map(|resolver| resolver.ask(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Evaluator, VariableManager};
+    use super::*;
+    use crate::utils::print::lex_parse_program;
+
+    fn span() -> TextSpan {
+        TextSpan::new(0, 1, "x".to_string(), 1, 0)
+    }
+
+    #[test]
+    fn whole_values_become_integers_that_fit() {
+        assert_eq!(num_from_f64(3.0), Num::Integer(3));
+        assert_eq!(num_from_f64(-2.0), Num::Integer(-2));
+        assert_eq!(num_from_f64(2.5), Num::Float(2.5));
+        // i64 would saturate at 9223372036854775807
+        assert_eq!(num_from_f64(1e20), Num::Float(1e20));
+        assert_eq!(num_from_f64(-1e20), Num::Float(-1e20));
+        assert_eq!(num_from_f64(i64::MAX as f64), Num::Float(i64::MAX as f64));
+        assert!(matches!(num_from_f64(f64::NAN), Num::Float(value) if value.is_nan()));
+        assert_eq!(num_from_f64(f64::INFINITY), Num::Float(f64::INFINITY));
+    }
+
+    #[test]
+    fn large_assignment_keeps_its_value() {
+        let program = lex_parse_program("x := 1E20; x").unwrap();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let result = Evaluator::new(&mut vars, &mut resolver).evaluate_program(&program);
+        assert_eq!(result, Ok(Some(1e20)));
+    }
+
+    #[test]
+    fn map_only_resolver_reports_the_variable() {
+        match MapOnlyResolver.resolve("x", &span()) {
+            Err(CompilerError::Eval(EvaluationError::UndefinedVariable(name, _))) => {
+                assert_eq!(name, "x")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(MapOnlyResolver.ask("a"), None);
+    }
+
+    #[test]
+    fn env_resolver_reads_the_upper_case_name() {
+        // The prefix is unique to this test, tests run in parallel
+        env::set_var("RESOLVER_TEST_SPEED", " 2.5 ");
+        env::set_var("RESOLVER_TEST_BAD", "fast");
+        let mut resolver = EnvResolver::new("RESOLVER_TEST_");
+
+        assert_eq!(resolver.resolve("speed", &span()), Ok(Num::Float(2.5)));
+        assert!(resolver.resolve("bad", &span()).is_err());
+        assert!(resolver.resolve("missing", &span()).is_err());
+    }
+
+    /// Gives the same value for every name and answers questions
+    struct Constant(f64);
+
+    impl VariableResolver for Constant {
+        fn resolve(&mut self, _name: &str, _span: &TextSpan) -> Result<Num, CompilerError> {
+            Ok(num_from_f64(self.0))
+        }
+
+        fn ask(&mut self, _name: &str) -> Option<f64> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn chain_resolver_takes_the_first_value() {
+        env::set_var("CHAIN_TEST_Y", "7");
+        let mut chain = ChainResolver::new(vec![
+            Box::new(MapOnlyResolver),
+            Box::new(EnvResolver::new("CHAIN_TEST_")),
+            Box::new(Constant(1.0)),
+        ]);
+
+        assert_eq!(chain.resolve("y", &span()), Ok(Num::Integer(7)));
+        assert_eq!(chain.resolve("z", &span()), Ok(Num::Integer(1)));
+        assert_eq!(chain.ask("a"), Some(1.0));
+
+        let mut empty = ChainResolver::new(Vec::new());
+        assert!(empty.resolve("z", &span()).is_err());
+        assert_eq!(empty.ask("a"), None);
+    }
+}
@@ -1,19 +1,167 @@
-use super::{get_and_parse_user_input, ASTNode, CompilerError, Evaluator, RootFinderError};
+use super::root_report::{RootKind, RootReport, RootScan};
+use super::{ASTNode, CompilerError, Evaluator, RootFinderError};
+use crate::vm::CompiledFunction;
+use serde::Serialize;
+use std::fmt;
+
+/// Method that refines a bracket [a, b] in which f changes its sign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RootMethod {
+    /// Halves the bracket: slow, but it never fails
+    Bisection,
+    /// Newton-Raphson from the middle of the bracket. Uses the symbolic derivative,
+    /// or a central difference where it can not be evaluated.
+    Newton,
+    /// Secant through the ends of the bracket
+    Secant,
+    /// Brent's method: inverse quadratic interpolation, secant and bisection steps
+    Brent,
+    /// Regula falsi with the Illinois modification against one-sided convergence
+    Illinois,
+    /// Newton steps that fall back to bisection when they leave the bracket
+    #[default]
+    Hybrid,
+}
+
+impl RootMethod {
+    /// "bisection", "newton", "secant", "brent", "illinois" (or "regula-falsi"), "hybrid"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bisection" => Some(RootMethod::Bisection),
+            "newton" => Some(RootMethod::Newton),
+            "secant" => Some(RootMethod::Secant),
+            "brent" => Some(RootMethod::Brent),
+            "illinois" | "regula-falsi" => Some(RootMethod::Illinois),
+            "hybrid" => Some(RootMethod::Hybrid),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RootMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RootMethod::Bisection => write!(f, "bisection"),
+            RootMethod::Newton => write!(f, "newton"),
+            RootMethod::Secant => write!(f, "secant"),
+            RootMethod::Brent => write!(f, "brent"),
+            RootMethod::Illinois => write!(f, "illinois"),
+            RootMethod::Hybrid => write!(f, "hybrid"),
+        }
+    }
+}
+
+/// Parameters of the scan and of the refinement of every bracket
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RootConfig {
+    pub method: RootMethod,
+    /// Width of the interval, or step, at which a method stops
+    pub tolerance: f64,
+    pub max_iterations: usize,
+    /// Distance of the samples of the scan
+    pub step_size: f64,
+}
+
+impl Default for RootConfig {
+    fn default() -> Self {
+        Self {
+            method: RootMethod::default(),
+            tolerance: 1e-9,
+            max_iterations: 10000,
+            step_size: 0.01,
+        }
+    }
+}
+
+/// A sample of f, None where it could not be evaluated or is NaN
+type Sample = (f64, Option<f64>);
 
 pub struct RootFinder<'a> {
     ast: &'a ASTNode,                 // Expression to find roots for
-    evaluator: &'a mut Evaluator<'a>, // Evaluator for f(x) = 0
+    evaluator: &'a mut Evaluator<'a>, // Variables, resolver and settings for f(x) = 0
+    /// Bytecode of the expression, compiled on the first evaluation
+    function: Option<Result<CompiledFunction, CompilerError>>,
+    /// Bytecode of the derivative, compiled when Newton's method first needs it
+    derivative: Option<Result<CompiledFunction, CompilerError>>,
+    config: RootConfig,
+    /// The unknown, x by default
+    variable: String,
 }
 
 #[allow(unused_assignments)]
 impl<'a> RootFinder<'a> {
     pub fn new(ast: &'a ASTNode, evaluator: &'a mut Evaluator<'a>) -> Self {
-        Self { ast, evaluator }
+        Self {
+            ast,
+            evaluator,
+            function: None,
+            derivative: None,
+            config: RootConfig::default(),
+            variable: "x".to_string(),
+        }
+    }
+
+    /// Finds the roots in var instead of x, the other variables keep their values
+    pub fn with_variable(mut self, var: &str) -> Self {
+        self.variable = var.to_lowercase();
+        self
+    }
+
+    /// Sets the method, the tolerance, the iteration limit and the step of the scan
+    pub fn with_config(mut self, config: RootConfig) -> Self {
+        self.config = config;
+        self
     }
 
-    /// Evaluates the expression at a given x value
+    /// Evaluates the expression at a given x value. The expression is compiled once,
+    /// a compile error is returned for every x like an evaluation error would be.
     pub fn evaluate_at(&mut self, x: f64) -> Result<f64, CompilerError> {
-        self.evaluator.evaluate_with_x(self.ast, x)
+        let function = self.function.get_or_insert_with(|| {
+            CompiledFunction::new_in(
+                self.ast,
+                &self.variable,
+                self.evaluator.vars,
+                self.evaluator.resolver,
+                self.evaluator.settings,
+            )
+        });
+
+        match function {
+            Ok(function) => function.call(x),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// f'(x) from the symbolic derivative. Where it fails to compile or is not finite,
+    /// a central difference is used instead.
+    fn derivative_at(&mut self, x: f64) -> Result<f64, CompilerError> {
+        let derivative = self.derivative.get_or_insert_with(|| {
+            let settings = self.evaluator.settings;
+            let derivative = self
+                .ast
+                .inline_functions(self.evaluator.vars)?
+                .derivative_in(&self.variable, settings.angle_mode)?
+                .simplify_with(settings);
+            CompiledFunction::new_in(
+                &derivative,
+                &self.variable,
+                self.evaluator.vars,
+                self.evaluator.resolver,
+                settings,
+            )
+        });
+
+        if let Ok(derivative) = derivative {
+            if let Ok(slope) = derivative.call(x) {
+                if slope.is_finite() {
+                    return Ok(slope);
+                }
+            }
+        }
+
+        let h = f64::EPSILON.cbrt() * x.abs().max(1.0);
+        Ok((self.evaluate_at(x + h)? - self.evaluate_at(x - h)?) / (2.0 * h))
     }
 
     /// Checks if there's a sign change between two points
@@ -38,33 +186,31 @@ impl<'a> RootFinder<'a> {
         (a - padding, b + padding)
     }
 
-    /// Rounds a value to 3 decimal places
-    fn round_to_precision(value: f64) -> f64 {
-        (value * 1000.0).round() / 1000.0
-    }
-
-    /// Checks if a value is within tolerance of any value in the list
-    fn is_duplicate(value: f64, values: &[f64], tolerance: f64) -> bool {
-        values.iter().any(|&x| (x - value).abs() <= tolerance)
-    }
-
-    /// Find a single root using bisection method
+    /// Find a single root using bisection method, with the number of iterations
     pub fn find_root_bisection(
         &mut self,
         a: f64,
         b: f64,
         tolerance: f64,
         max_iterations: usize,
-    ) -> Result<f64, CompilerError> {
+    ) -> Result<(f64, usize), CompilerError> {
         Self::validate_interval(a, b)?;
 
         let mut left = a;
         let mut right = b;
         let mut iterations = 0;
 
-        if !self.has_sign_change(left, right)? {
+        let fa = self.evaluate_at(a)?;
+        let fb = self.evaluate_at(b)?;
+        if fa * fb > 0.0 || fa.is_nan() || fb.is_nan() {
             return Err(CompilerError::Root(RootFinderError::NoRootInInterval));
         }
+        if fa == 0.0 {
+            return Ok((a, 0));
+        }
+        if fb == 0.0 {
+            return Ok((b, 0));
+        }
 
         while (right - left).abs() > tolerance && iterations < max_iterations {
             iterations += 1;
@@ -72,7 +218,7 @@ impl<'a> RootFinder<'a> {
             let f_mid = self.evaluate_at(mid)?;
 
             if f_mid.abs() <= tolerance {
-                return Ok(mid);
+                return Ok((mid, iterations));
             }
 
             if self.has_sign_change(left, mid)? {
@@ -85,70 +231,777 @@ impl<'a> RootFinder<'a> {
         if iterations >= max_iterations {
             Err(CompilerError::Root(RootFinderError::MaxIterationsReached))
         } else {
-            Ok((left + right) / 2.0)
+            Ok(((left + right) / 2.0, iterations))
+        }
+    }
+
+    /// Find a single root in [a, b] with the method of the configuration, with the
+    /// number of iterations. f must change its sign in the interval.
+    pub fn find_root(&mut self, a: f64, b: f64) -> Result<(f64, usize), CompilerError> {
+        let RootConfig {
+            method,
+            tolerance,
+            max_iterations,
+            ..
+        } = self.config;
+
+        if method == RootMethod::Bisection {
+            return self.find_root_bisection(a, b, tolerance, max_iterations);
+        }
+        Self::validate_interval(a, b)?;
+
+        let fa = self.evaluate_at(a)?;
+        let fb = self.evaluate_at(b)?;
+        if fa * fb > 0.0 || fa.is_nan() || fb.is_nan() {
+            return Err(CompilerError::Root(RootFinderError::NoRootInInterval));
+        }
+        if fa == 0.0 {
+            return Ok((a, 0));
+        }
+        if fb == 0.0 {
+            return Ok((b, 0));
+        }
+
+        let bracket = (a, fa, b, fb);
+        match method {
+            RootMethod::Newton => self.find_root_newton(a, b, tolerance, max_iterations),
+            RootMethod::Secant => self.find_root_secant(bracket, tolerance, max_iterations),
+            RootMethod::Brent => self.find_root_brent(bracket, tolerance, max_iterations),
+            RootMethod::Illinois => self.find_root_illinois(bracket, tolerance, max_iterations),
+            RootMethod::Hybrid | RootMethod::Bisection => {
+                self.find_root_hybrid(bracket, tolerance, max_iterations)
+            }
         }
     }
 
-    /// Find all roots in an interval
-    pub fn find_all_roots(
+    /// Newton-Raphson from the middle of [a, b]. The steps may leave the interval,
+    /// but the root they converge to has to lie in it.
+    fn find_root_newton(
         &mut self,
-        tolerance: f64,
-        max_iterations: usize,
-        step_size: f64,
         a: f64,
         b: f64,
-    ) -> Result<Vec<f64>, CompilerError> {
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<(f64, usize), CompilerError> {
+        let mut x = (a + b) / 2.0;
+
+        for iteration in 1..=max_iterations {
+            let fx = self.evaluate_at(x)?;
+            if fx == 0.0 {
+                return Self::within(x, iteration, a, b);
+            }
+
+            let next = x - fx / self.derivative_at(x)?;
+            if !next.is_finite() {
+                return Err(CompilerError::Root(RootFinderError::Diverged));
+            }
+            if (next - x).abs() <= tolerance {
+                return Self::within(next, iteration, a, b);
+            }
+            x = next;
+        }
+
+        Err(CompilerError::Root(RootFinderError::MaxIterationsReached))
+    }
+
+    /// Secant method started from the ends of the bracket. Like Newton's method, the
+    /// root has to lie in the bracket.
+    fn find_root_secant(
+        &mut self,
+        (a, fa, b, fb): (f64, f64, f64, f64),
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<(f64, usize), CompilerError> {
+        let (mut x0, mut f0, mut x1, mut f1) = (a, fa, b, fb);
+
+        for iteration in 1..=max_iterations {
+            let x2 = x1 - f1 * (x1 - x0) / (f1 - f0);
+            if !x2.is_finite() {
+                return Err(CompilerError::Root(RootFinderError::Diverged));
+            }
+
+            let f2 = self.evaluate_at(x2)?;
+            if f2 == 0.0 || (x2 - x1).abs() <= tolerance {
+                return Self::within(x2, iteration, a, b);
+            }
+            (x0, f0, x1, f1) = (x1, f1, x2, f2);
+        }
+
+        Err(CompilerError::Root(RootFinderError::MaxIterationsReached))
+    }
+
+    /// The root of an open method, if it did not leave the bracket [a, b]
+    fn within(root: f64, iterations: usize, a: f64, b: f64) -> Result<(f64, usize), CompilerError> {
+        match (a..=b).contains(&root) {
+            true => Ok((root, iterations)),
+            false => Err(CompilerError::Root(RootFinderError::Diverged)),
+        }
+    }
+
+    /// Regula falsi keeps a bracket around the root. When the same end is kept twice
+    /// in a row, the Illinois modification halves its value so that end moves too.
+    fn find_root_illinois(
+        &mut self,
+        (a, fa, b, fb): (f64, f64, f64, f64),
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<(f64, usize), CompilerError> {
+        let (mut left, mut f_left, mut right, mut f_right) = (a, fa, b, fb);
+        // Which end was replaced last: -1 left, 1 right
+        let mut side = 0;
+        let mut previous = left;
+
+        for iteration in 1..=max_iterations {
+            let x = (left * f_right - right * f_left) / (f_right - f_left);
+            let fx = self.evaluate_at(x)?;
+            if fx == 0.0 || (x - previous).abs() <= tolerance {
+                return Ok((x, iteration));
+            }
+            previous = x;
+
+            if fx * f_right > 0.0 {
+                right = x;
+                f_right = fx;
+                if side == 1 {
+                    f_left /= 2.0;
+                }
+                side = 1;
+            } else {
+                left = x;
+                f_left = fx;
+                if side == -1 {
+                    f_right /= 2.0;
+                }
+                side = -1;
+            }
+        }
+
+        Err(CompilerError::Root(RootFinderError::MaxIterationsReached))
+    }
+
+    /// Brent's method as in Numerical Recipes: b is the best estimate, [b, c] the
+    /// bracket and a the previous estimate
+    fn find_root_brent(
+        &mut self,
+        (a, fa, b, fb): (f64, f64, f64, f64),
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<(f64, usize), CompilerError> {
+        let (mut a, mut fa, mut b, mut fb) = (a, fa, b, fb);
+        let (mut c, mut fc) = (b, fb);
+        let mut d = b - a;
+        let mut e = d;
+
+        for iteration in 1..=max_iterations {
+            if (fb > 0.0) == (fc > 0.0) {
+                (c, fc) = (a, fa);
+                d = b - a;
+                e = d;
+            }
+            if fc.abs() < fb.abs() {
+                (a, fa) = (b, fb);
+                (b, fb) = (c, fc);
+                (c, fc) = (a, fa);
+            }
+
+            let tol = 2.0 * f64::EPSILON * b.abs() + 0.5 * tolerance;
+            let middle = 0.5 * (c - b);
+            if middle.abs() <= tol || fb == 0.0 {
+                return Ok((b, iteration));
+            }
+
+            if e.abs() >= tol && fa.abs() > fb.abs() {
+                // Inverse quadratic interpolation, or the secant when a == c
+                let s = fb / fa;
+                let (mut p, mut q) = if a == c {
+                    (2.0 * middle * s, 1.0 - s)
+                } else {
+                    let q = fa / fc;
+                    let r = fb / fc;
+                    (
+                        s * (2.0 * middle * q * (q - r) - (b - a) * (r - 1.0)),
+                        (q - 1.0) * (r - 1.0) * (s - 1.0),
+                    )
+                };
+                if p > 0.0 {
+                    q = -q;
+                }
+                p = p.abs();
+
+                if 2.0 * p < (3.0 * middle * q - (tol * q).abs()).min((e * q).abs()) {
+                    e = d;
+                    d = p / q;
+                } else {
+                    d = middle;
+                    e = d;
+                }
+            } else {
+                d = middle;
+                e = d;
+            }
+
+            (a, fa) = (b, fb);
+            b += if d.abs() > tol {
+                d
+            } else {
+                tol.copysign(middle)
+            };
+            fb = self.evaluate_at(b)?;
+        }
+
+        Err(CompilerError::Root(RootFinderError::MaxIterationsReached))
+    }
+
+    /// Newton steps inside a bracket that shrinks with every step. A bisection step is
+    /// taken when the Newton step leaves the bracket or does not halve the last step.
+    fn find_root_hybrid(
+        &mut self,
+        (a, fa, b, _): (f64, f64, f64, f64),
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<(f64, usize), CompilerError> {
+        // f(low) < 0 < f(high), low may be the right end
+        let (mut low, mut high) = if fa < 0.0 { (a, b) } else { (b, a) };
+        let mut x = (a + b) / 2.0;
+        let mut step = b - a;
+        let mut previous_step = step;
+        let mut fx = self.evaluate_at(x)?;
+        let mut slope = self.derivative_at(x)?;
+
+        for iteration in 1..=max_iterations {
+            if fx == 0.0 {
+                return Ok((x, iteration));
+            }
+
+            let newton = x - fx / slope;
+            let inside = (newton - low) * (newton - high) < 0.0;
+            if inside && (2.0 * fx).abs() <= (previous_step * slope).abs() {
+                previous_step = step;
+                step = x - newton;
+                x = newton;
+            } else {
+                previous_step = step;
+                step = (high - low) / 2.0;
+                x = low + step;
+            }
+            if step.abs() <= tolerance {
+                return Ok((x, iteration));
+            }
+
+            fx = self.evaluate_at(x)?;
+            slope = self.derivative_at(x)?;
+            if fx < 0.0 {
+                low = x;
+            } else {
+                high = x;
+            }
+        }
+
+        Err(CompilerError::Root(RootFinderError::MaxIterationsReached))
+    }
+
+    /// Find all roots in an interval. The interval is sampled every step_size: a sign
+    /// change between two samples is refined with the method and kept as a crossing if
+    /// |f| shrinks towards the result, otherwise it is a pole. Samples where f can not be
+    /// evaluated or is not finite are skipped, a sign change across them is a pole too.
+    /// A local minimum of |f| without a sign change is followed down, it is a tangent
+    /// root if |f| reaches tolerance. If no sample can be evaluated, the error of the
+    /// first one is returned.
+    pub fn find_all_roots(&mut self, a: f64, b: f64) -> Result<RootScan, CompilerError> {
         Self::validate_interval(a, b)?;
+        let RootConfig {
+            tolerance,
+            max_iterations,
+            step_size,
+            ..
+        } = self.config;
+        if !(tolerance > 0.0 && step_size > 0.0 && max_iterations > 0) {
+            return Err(CompilerError::Root(RootFinderError::InvalidConfig));
+        }
 
-        let mut roots = Vec::new();
+        let mut scan = RootScan::new(&self.variable, (a, b), self.config);
         let (expanded_a, expanded_b) = Self::expand_interval(a, b);
-        let mut left = expanded_a;
 
         // Check boundary points
-        for boundary in &[a, b] {
-            if let Ok(value) = self.evaluate_at(*boundary) {
-                if value.abs() <= tolerance && !Self::is_duplicate(*boundary, &roots, tolerance) {
-                    roots.push(*boundary);
+        for boundary in [a, b] {
+            if let Ok(value) = self.evaluate_at(boundary) {
+                if value.abs() <= tolerance {
+                    let kind = self.classify_zero(boundary, step_size);
+                    let report = self.report(boundary, kind, "sample", 0, (boundary, boundary));
+                    scan.add_root(report);
                 }
             }
         }
 
-        while left < expanded_b {
-            let right = (left + step_size).min(expanded_b);
+        // Only the last three samples are kept, the interval is scanned as it is sampled.
+        // Evaluation errors, NaN and infinities leave a gap in the samples
+        let mut window: [Option<Sample>; 3] = [None; 3];
+        let mut last = None;
+        let mut first_error = None;
+        let mut evaluated = false;
+        for i in 0.. {
+            // Multiples of the step, adding it up would drift
+            let x = (expanded_a + i as f64 * step_size).min(expanded_b);
+            let value = match self.evaluate_at(x) {
+                Ok(value) => {
+                    evaluated = true;
+                    Some(value).filter(|value| value.is_finite())
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                    None
+                }
+            };
+            window = [window[1], window[2], Some((x, value))];
 
-            // Skip evaluation errors and continue with next interval
-            if let Ok(true) = self.has_sign_change(left, right) {
-                if let Ok(root) = self.find_root_bisection(left, right, tolerance, max_iterations) {
-                    if root >= a && root <= b {
-                        let rounded_root = Self::round_to_precision(root);
-                        if !Self::is_duplicate(rounded_root, &roots, tolerance) {
-                            roots.push(rounded_root);
-                        }
-                    }
+            // The bracket spans the gap since the last sample with a value
+            if let Some(value) = value {
+                if let Some(previous) = last {
+                    self.scan_bracket(&mut scan, previous, (x, value));
                 }
+                last = Some((x, value));
+            }
+            if let [Some(left), Some(middle), Some(right)] = window {
+                self.scan_minimum(&mut scan, left, middle, right);
+            }
+            if x >= expanded_b {
+                break;
+            }
+        }
+        if let (false, Some(error)) = (evaluated, first_error) {
+            return Err(error);
+        }
+
+        scan.sort();
+        Ok(scan)
+    }
+
+    /// A sign change between two samples is refined into a crossing or a pole. When the
+    /// method fails, on an error or a non-finite value at the pole, the change of sign is
+    /// located by bisection instead of dropping the bracket.
+    fn scan_bracket(&mut self, scan: &mut RootScan, left: (f64, f64), right: (f64, f64)) {
+        let ((left, f_left), (right, f_right)) = (left, right);
+        if f_left * f_right >= 0.0 {
+            return;
+        }
+
+        let (root, iterations, method) = match self.find_root(left, right) {
+            Ok((root, iterations)) => (root, iterations, self.config.method.to_string()),
+            Err(_) => {
+                let (root, iterations) = self.locate_sign_change(left, f_left, right);
+                (root, iterations, RootMethod::Bisection.to_string())
             }
+        };
+        if !scan.contains(root) {
+            return;
+        }
+        if self.is_pole(root, left, right) {
+            scan.add_pole(root);
+        } else {
+            let report = self.report(root, RootKind::Crossing, &method, iterations, (left, right));
+            scan.add_root(report);
+        }
+    }
+
+    /// Bisection of a sign change that tolerates errors and non-finite values: a midpoint
+    /// where f has no finite value is where f jumps over zero.
+    fn locate_sign_change(&mut self, mut left: f64, f_left: f64, mut right: f64) -> (f64, usize) {
+        let RootConfig {
+            tolerance,
+            max_iterations,
+            ..
+        } = self.config;
+
+        let mut iterations = 0;
+        while right - left > tolerance && iterations < max_iterations {
+            iterations += 1;
+            let mid = (left + right) / 2.0;
+            match self.evaluate_at(mid) {
+                Ok(0.0) => return (mid, iterations),
+                Ok(value) if value.is_finite() => match value * f_left < 0.0 {
+                    true => right = mid,
+                    false => left = mid,
+                },
+                _ => return (mid, iterations),
+            }
+        }
+
+        ((left + right) / 2.0, iterations)
+    }
+
+    /// A sample that is a root, or a local minimum of |f| that is followed down to a tangent root
+    fn scan_minimum(&mut self, scan: &mut RootScan, left: Sample, middle: Sample, right: Sample) {
+        let ((left, Some(f_left)), (x, Some(fx)), (right, Some(f_right))) = (left, middle, right)
+        else {
+            return;
+        };
+
+        // A sample that is a root: the neighbours tell whether f crosses
+        if fx == 0.0 {
+            if scan.contains(x) {
+                let kind = match f_left * f_right < 0.0 {
+                    true => RootKind::Crossing,
+                    false => RootKind::Tangent,
+                };
+                scan.add_root(self.report(x, kind, "sample", 0, (left, right)));
+            }
+            return;
+        }
+
+        let same_sign = f_left * fx > 0.0 && fx * f_right > 0.0;
+        let minimum = fx.abs() < f_left.abs() && fx.abs() <= f_right.abs();
+        if !(same_sign && minimum) {
+            return;
+        }
+        let RootConfig {
+            tolerance,
+            max_iterations,
+            ..
+        } = self.config;
+        if let Ok((root, iterations)) = self.minimize_abs(left, right, tolerance, max_iterations) {
+            let touches_zero = self
+                .evaluate_at(root)
+                .is_ok_and(|value| value.abs() <= tolerance);
+            if touches_zero && scan.contains(root) {
+                let report = self.report(
+                    root,
+                    RootKind::Tangent,
+                    "golden section",
+                    iterations,
+                    (left, right),
+                );
+                scan.add_root(report);
+            }
+        }
+    }
+
+    /// Report of a root with its residual and multiplicity
+    fn report(
+        &mut self,
+        root: f64,
+        kind: RootKind,
+        method: &str,
+        iterations: usize,
+        bracket: (f64, f64),
+    ) -> RootReport {
+        RootReport {
+            value: root,
+            residual: self.evaluate_at(root).unwrap_or(f64::NAN),
+            kind,
+            method: method.to_string(),
+            iterations,
+            bracket,
+            multiplicity: self.multiplicity(root),
+        }
+    }
+
+    /// Near a root of multiplicity m, |f(root + h)| grows like h^m, so m is the base 2
+    /// logarithm of |f(root + 2h) / f(root + h)|. It is averaged over both sides, with
+    /// h a hundredth of the step.
+    fn multiplicity(&mut self, root: f64) -> u32 {
+        let h = self.config.step_size / 100.0;
+        let estimates: Vec<f64> = [h, -h]
+            .into_iter()
+            .filter_map(|h| {
+                let near = self.evaluate_at(root + h).ok()?;
+                let far = self.evaluate_at(root + 2.0 * h).ok()?;
+                Some((far / near).abs().log2()).filter(|m| m.is_finite())
+            })
+            .collect();
+
+        if estimates.is_empty() {
+            return 1;
+        }
+        let mean = estimates.iter().sum::<f64>() / estimates.len() as f64;
+        mean.round().max(1.0) as u32
+    }
+
+    /// A sign change is a pole when |f| does not shrink towards the refined point:
+    /// tan(x) at 90, 1/x at 0 or a step like floor(x) - 0.5 at 1. f is compared a
+    /// quarter of the bracket to the left and to the right of the point.
+    fn is_pole(&mut self, root: f64, left: f64, right: f64) -> bool {
+        let value = match self.evaluate_at(root) {
+            Ok(value) if value.is_finite() => value.abs(),
+            _ => return true,
+        };
+        if value == 0.0 {
+            return false;
+        }
+
+        let offset = (right - left) / 4.0;
+        [root - offset, root + offset]
+            .into_iter()
+            .filter_map(|x| self.evaluate_at(x).ok().filter(|near| near.is_finite()))
+            .any(|near| value >= near.abs())
+    }
+
+    /// Crossing when f has opposite signs one step to the left and to the right
+    fn classify_zero(&mut self, x: f64, step_size: f64) -> RootKind {
+        let left = self.evaluate_at(x - step_size);
+        let right = self.evaluate_at(x + step_size);
+        match (left, right) {
+            (Ok(left), Ok(right)) if left * right >= 0.0 => RootKind::Tangent,
+            _ => RootKind::Crossing,
+        }
+    }
+
+    /// Golden section search for the minimum of |f| in [a, b]
+    fn minimize_abs(
+        &mut self,
+        a: f64,
+        b: f64,
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<(f64, usize), CompilerError> {
+        const INVERSE_PHI: f64 = 0.618_033_988_749_894_9;
+
+        let (mut low, mut high) = (a, b);
+        let mut c = high - INVERSE_PHI * (high - low);
+        let mut d = low + INVERSE_PHI * (high - low);
+        let mut fc = self.evaluate_at(c)?.abs();
+        let mut fd = self.evaluate_at(d)?.abs();
 
-            left = right;
+        let mut iterations = 0;
+        while high - low > tolerance && iterations < max_iterations {
+            iterations += 1;
+            if fc < fd {
+                (high, d, fd) = (d, c, fc);
+                c = high - INVERSE_PHI * (high - low);
+                fc = self.evaluate_at(c)?.abs();
+            } else {
+                (low, c, fc) = (c, d, fd);
+                d = low + INVERSE_PHI * (high - low);
+                fd = self.evaluate_at(d)?.abs();
+            }
         }
 
-        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        Ok(roots)
+        Ok(((low + high) / 2.0, iterations))
     }
 
-    /// friendly method with default parameters
+    /// friendly method that asks the resolver for a missing end of the interval.
+    /// Without an answer the interval is missing.
     pub fn find_roots(
         &mut self,
         a: Option<f64>,
         b: Option<f64>,
-    ) -> Result<Vec<f64>, CompilerError> {
-        let a = a.unwrap_or_else(|| get_and_parse_user_input("a"));
-        let b = b.unwrap_or_else(|| get_and_parse_user_input("b"));
+    ) -> Result<RootScan, CompilerError> {
+        let missing = || CompilerError::Root(RootFinderError::MissingInterval);
+        let a = a
+            .or_else(|| self.evaluator.resolver.ask("a"))
+            .ok_or_else(missing)?;
+        let b = b
+            .or_else(|| self.evaluator.resolver.ask("b"))
+            .ok_or_else(missing)?;
+
+        self.find_all_roots(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::resolver::MapOnlyResolver;
+    use super::super::{AngleMode, DomainMode, EvalSettings, VariableManager};
+    use super::*;
+    use crate::errors::eval::EvaluationError;
+    use crate::utils::print::{lex_parse_input, lex_parse_program};
+    use std::f64::consts::PI;
+
+    fn scan(input: &str, a: f64, b: f64, domain_mode: DomainMode) -> RootScan {
+        let ast = lex_parse_input(input).unwrap();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let settings = EvalSettings {
+            angle_mode: AngleMode::Radians,
+            domain_mode,
+        };
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver).with_settings(settings);
+        RootFinder::new(&ast, &mut evaluator)
+            .find_all_roots(a, b)
+            .unwrap()
+    }
+
+    fn close(values: &[f64], expected: &[f64], tolerance: f64) -> bool {
+        values.len() == expected.len()
+            && values
+                .iter()
+                .zip(expected)
+                .all(|(value, expected)| (value - expected).abs() <= tolerance)
+    }
 
-        // Default parameters
-        const TOLERANCE: f64 = 1e-9;
-        const MAX_ITERATIONS: usize = 10000;
-        const STEP_SIZE: f64 = 0.01;
+    #[test]
+    fn pole_on_a_sample_is_rejected_in_both_modes() {
+        // x = 0 is a sample: an error in strict mode, infinite in lenient mode
+        for mode in [DomainMode::Strict, DomainMode::Lenient] {
+            let scan = scan("1/x", -1.0, 1.0, mode);
+            assert!(scan.roots.is_empty(), "{:?}: {:?}", mode, scan.roots);
+            assert!(
+                close(&scan.poles, &[0.0], 1e-6),
+                "{:?}: {:?}",
+                mode,
+                scan.poles
+            );
+        }
+    }
+
+    #[test]
+    fn pole_between_samples_is_rejected() {
+        for mode in [DomainMode::Strict, DomainMode::Lenient] {
+            let scan = scan("1/(x-0.505)", -1.0, 1.0, mode);
+            assert!(scan.roots.is_empty(), "{:?}: {:?}", mode, scan.roots);
+            assert!(
+                close(&scan.poles, &[0.505], 1e-6),
+                "{:?}: {:?}",
+                mode,
+                scan.poles
+            );
+        }
+    }
+
+    #[test]
+    fn tan_has_roots_and_poles() {
+        let scan = scan("tan(x)", -2.0, 4.0, DomainMode::Strict);
+        assert!(
+            close(&scan.values(), &[0.0, PI], 1e-9),
+            "{:?}",
+            scan.values()
+        );
+        assert!(
+            close(&scan.poles, &[-PI / 2.0, PI / 2.0], 1e-6),
+            "{:?}",
+            scan.poles
+        );
+    }
+
+    #[test]
+    fn tangent_root_is_found_without_a_sign_change() {
+        let scan = scan("(x-0.3)^2", -1.0, 1.0, DomainMode::Strict);
+        assert_eq!(scan.roots.len(), 1, "{:?}", scan.roots);
+        let root = &scan.roots[0];
+        assert!((root.value - 0.3).abs() < 1e-4, "{}", root.value);
+        assert_eq!((root.kind, root.multiplicity), (RootKind::Tangent, 2));
+        assert!(scan.poles.is_empty());
+    }
+
+    #[test]
+    fn tangent_root_on_a_sample() {
+        let scan = scan("x^2", -1.0, 1.0, DomainMode::Strict);
+        assert_eq!(scan.values(), vec![0.0]);
+        assert_eq!(scan.roots[0].kind, RootKind::Tangent);
+    }
+
+    fn find(input: &str, method: RootMethod, a: f64, b: f64) -> (f64, usize) {
+        let ast = lex_parse_input(input).unwrap();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        let config = RootConfig {
+            method,
+            ..RootConfig::default()
+        };
+        RootFinder::new(&ast, &mut evaluator)
+            .with_config(config)
+            .find_root(a, b)
+            .unwrap()
+    }
+
+    const METHODS: [RootMethod; 6] = [
+        RootMethod::Bisection,
+        RootMethod::Newton,
+        RootMethod::Secant,
+        RootMethod::Brent,
+        RootMethod::Illinois,
+        RootMethod::Hybrid,
+    ];
+
+    #[test]
+    fn every_method_converges() {
+        for method in METHODS {
+            let (root, _) = find("x^2 - 2", method, 0.0, 2.0);
+            assert!((root - 2f64.sqrt()).abs() < 1e-9, "{}: {}", method, root);
+        }
+    }
+
+    #[test]
+    fn iteration_counts() {
+        // bisection halves the interval down to the tolerance, the others converge faster
+        let counts: Vec<_> = METHODS
+            .iter()
+            .map(|&method| (method, find("x^2 - 2", method, 0.0, 2.0).1))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                (RootMethod::Bisection, 30),
+                (RootMethod::Newton, 5),
+                (RootMethod::Secant, 7),
+                (RootMethod::Brent, 8),
+                (RootMethod::Illinois, 8),
+                (RootMethod::Hybrid, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn root_on_a_bracket_end_takes_no_iterations() {
+        for method in METHODS {
+            assert_eq!(find("x^3 - x", method, 0.5, 1.0), (1.0, 0), "{}", method);
+            assert_eq!(find("x^3 - x", method, 1.0, 1.5), (1.0, 0), "{}", method);
+        }
+    }
+
+    #[test]
+    fn crossings_are_refined() {
+        let scan = scan("x^3 - x", -2.0, 2.0, DomainMode::Strict);
+        assert!(
+            close(&scan.values(), &[-1.0, 0.0, 1.0], 1e-9),
+            "{:?}",
+            scan.values()
+        );
+        assert!(scan.poles.is_empty());
+    }
+
+    #[test]
+    fn scan_returns_the_error_when_no_sample_evaluates() {
+        let program = lex_parse_program("f(x) := f(x) + 1\nf(x)").unwrap();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        let (statements, ast) = program.split_result();
+        for statement in &statements {
+            evaluator.execute_statement(statement).unwrap();
+        }
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        let error = RootFinder::new(&ast, &mut evaluator)
+            .find_all_roots(-1.0, 1.0)
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                CompilerError::Eval(EvaluationError::RecursionLimit(..))
+            ),
+            "{:?}",
+            error
+        );
+
+        let ast = lex_parse_input("x + y").unwrap();
+        let mut vars = VariableManager::new();
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        let error = RootFinder::new(&ast, &mut evaluator)
+            .find_all_roots(-1.0, 1.0)
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                CompilerError::Eval(EvaluationError::UndefinedVariable(..))
+            ),
+            "{:?}",
+            error
+        );
+    }
 
-        self.find_all_roots(TOLERANCE, MAX_ITERATIONS, STEP_SIZE, a, b)
+    #[test]
+    fn scan_skips_errors_when_some_samples_evaluate() {
+        let scan = scan("sqrt(x) - 0.5", -1.0, 1.0, DomainMode::Strict);
+        assert!(close(&scan.values(), &[0.25], 1e-9), "{:?}", scan.values());
     }
 }
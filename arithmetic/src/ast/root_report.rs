@@ -0,0 +1,334 @@
+use super::root::RootConfig;
+use serde::Serialize;
+use std::fmt;
+
+/// How f meets zero at a root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RootKind {
+    /// f changes its sign
+    Crossing,
+    /// f touches zero without changing its sign, like x^2 at 0
+    Tangent,
+}
+
+impl fmt::Display for RootKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RootKind::Crossing => f.pad("crossing"),
+            RootKind::Tangent => f.pad("tangent"),
+        }
+    }
+}
+
+/// One root and how it was found
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RootReport {
+    /// The root, not rounded
+    pub value: f64,
+    /// f(value), NaN when f can not be evaluated there
+    pub residual: f64,
+    pub kind: RootKind,
+    /// The root finding method for crossings, "golden section" for tangent roots and
+    /// "sample" for a point of the scan where f is 0
+    pub method: String,
+    pub iterations: usize,
+    /// Interval the method started from
+    pub bracket: (f64, f64),
+    /// Estimated from how fast |f| grows next to the root: 1 for a simple root,
+    /// 2 for a double root like x^2 at 0, ...
+    pub multiplicity: u32,
+}
+
+/// Roots found in an interval, sorted, and the sign changes rejected as poles
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RootScan {
+    /// The unknown of f
+    pub variable: String,
+    pub interval: (f64, f64),
+    pub config: RootConfig,
+    pub roots: Vec<RootReport>,
+    /// Sign changes where f jumps over zero instead of crossing it
+    pub poles: Vec<f64>,
+}
+
+impl RootScan {
+    pub fn new(variable: &str, interval: (f64, f64), config: RootConfig) -> Self {
+        Self {
+            variable: variable.to_string(),
+            interval,
+            config,
+            roots: Vec::new(),
+            poles: Vec::new(),
+        }
+    }
+
+    /// Whether x lies in the scanned interval
+    pub fn contains(&self, x: f64) -> bool {
+        x >= self.interval.0 && x <= self.interval.1
+    }
+
+    /// The values of the roots
+    pub fn values(&self) -> Vec<f64> {
+        self.roots.iter().map(|root| root.value).collect()
+    }
+
+    /// Adds the root unless one closer than a tenth of the step is already known
+    pub fn add_root(&mut self, root: RootReport) {
+        if !self.is_duplicate(root.value, &self.values()) {
+            self.roots.push(root);
+        }
+    }
+
+    pub fn add_pole(&mut self, pole: f64) {
+        if !self.is_duplicate(pole, &self.poles) {
+            self.poles.push(pole);
+        }
+    }
+
+    fn is_duplicate(&self, value: f64, values: &[f64]) -> bool {
+        let distance = self.config.step_size / 10.0;
+        values.iter().any(|&x| (x - value).abs() <= distance)
+    }
+
+    /// Sorts the roots and poles by their value
+    pub fn sort(&mut self) {
+        self.roots.sort_by(|a, b| a.value.total_cmp(&b.value));
+        self.poles.sort_by(|a, b| a.total_cmp(b));
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("root reports always serialize")
+    }
+}
+
+/// One line per root with its residual and how it was found, then the rejected poles:
+///
+/// ```text
+/// Roots in [-3, 3] with hybrid:
+///   x = -1.414213562373095     f(x) = -4.441e-16 crossing  multiplicity 1  hybrid, 3 iterations from [-1.42, -1.41]
+///   x = 1.414213562373095      f(x) = -4.441e-16 crossing  multiplicity 1  hybrid, 3 iterations from [1.41, 1.42]
+/// ```
+impl fmt::Display for RootScan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.roots.is_empty() {
+            write!(f, "No roots found in the given interval.")?;
+        } else {
+            write!(
+                f,
+                "Roots in [{}, {}] with {}:",
+                self.interval.0, self.interval.1, self.config.method
+            )?;
+            for root in self.roots.iter() {
+                write!(
+                    f,
+                    "\n  {} = {:<22} f({}) = {:<10.3e} {:<9} multiplicity {}  {}, {} iterations from [{}, {}]",
+                    self.variable,
+                    format_value(root.value),
+                    self.variable,
+                    root.residual + 0.0,
+                    root.kind,
+                    root.multiplicity,
+                    root.method,
+                    root.iterations,
+                    format_value(round_bracket(root.bracket.0)),
+                    format_value(round_bracket(root.bracket.1))
+                )?;
+            }
+        }
+
+        if !self.poles.is_empty() {
+            let poles = self
+                .poles
+                .iter()
+                .map(|&pole| format_value(pole))
+                .collect::<Vec<String>>()
+                .join(", ");
+            write!(f, "\nRejected poles: {}", poles)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scientific notation for values that would need many zeros
+fn format_value(value: f64) -> String {
+    match value == 0.0 || (1e-4..1e15).contains(&value.abs()) {
+        true => value.to_string(),
+        false => format!("{:e}", value),
+    }
+}
+
+/// The samples of the scan add up the step, rounding hides the error: 1.4100000000000001 -> 1.41
+fn round_bracket(value: f64) -> f64 {
+    (value * 1e12).round() / 1e12 + 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::resolver::MapOnlyResolver;
+    use super::super::root::RootFinder;
+    use super::super::{Evaluator, VariableManager};
+    use super::*;
+    use crate::utils::print::lex_parse_input;
+    use std::f64::consts::SQRT_2;
+
+    fn root(value: f64, residual: f64, kind: RootKind, multiplicity: u32) -> RootReport {
+        RootReport {
+            value,
+            residual,
+            kind,
+            method: "hybrid".to_string(),
+            iterations: 3,
+            bracket: (value - 0.005, value + 0.005),
+            multiplicity,
+        }
+    }
+
+    fn sqrt_2_scan() -> RootScan {
+        // The roots of x^2 - 2 as hybrid finds them, one ulp closer to 0 than sqrt(2)
+        let value = SQRT_2 - f64::EPSILON;
+        let residual = value * value - 2.0;
+        let mut scan = RootScan::new("x", (-3.0, 3.0), RootConfig::default());
+        scan.add_root(root(value, residual, RootKind::Crossing, 1));
+        scan.add_root(root(-value, residual, RootKind::Crossing, 1));
+        scan.roots[0].bracket = (1.41, 1.42);
+        scan.roots[1].bracket = (-1.42, -1.4100000000000001);
+        scan.sort();
+        scan
+    }
+
+    #[test]
+    fn display_matches_the_documented_layout() {
+        assert_eq!(
+            sqrt_2_scan().to_string(),
+            "Roots in [-3, 3] with hybrid:\n  \
+             x = -1.414213562373095     f(x) = -4.441e-16 crossing  multiplicity 1  hybrid, 3 iterations from [-1.42, -1.41]\n  \
+             x = 1.414213562373095      f(x) = -4.441e-16 crossing  multiplicity 1  hybrid, 3 iterations from [1.41, 1.42]"
+        );
+    }
+
+    #[test]
+    fn display_of_poles_and_of_no_roots() {
+        let mut scan = RootScan::new("t", (-1.0, 1.0), RootConfig::default());
+        assert_eq!(scan.to_string(), "No roots found in the given interval.");
+
+        scan.add_pole(0.5);
+        scan.add_pole(-1e-7);
+        scan.sort();
+        assert_eq!(
+            scan.to_string(),
+            "No roots found in the given interval.\nRejected poles: -1e-7, 0.5"
+        );
+
+        scan.add_root(root(0.0, -0.0, RootKind::Tangent, 2));
+        assert_eq!(
+            scan.to_string(),
+            "Roots in [-1, 1] with hybrid:\n  \
+             t = 0                      f(t) = 0.000e0    tangent   multiplicity 2  hybrid, 3 iterations from [-0.005, 0.005]\n\
+             Rejected poles: -1e-7, 0.5"
+        );
+    }
+
+    #[test]
+    fn to_json() {
+        let mut scan = RootScan::new("x", (0.0, 3.0), RootConfig::default());
+        scan.add_root(root(1.5, 0.0, RootKind::Tangent, 2));
+        scan.add_pole(2.0);
+        assert_eq!(
+            scan.to_json(),
+            r#"{
+  "variable": "x",
+  "interval": [
+    0.0,
+    3.0
+  ],
+  "config": {
+    "method": "hybrid",
+    "tolerance": 1e-9,
+    "max_iterations": 10000,
+    "step_size": 0.01
+  },
+  "roots": [
+    {
+      "value": 1.5,
+      "residual": 0.0,
+      "kind": "tangent",
+      "method": "hybrid",
+      "iterations": 3,
+      "bracket": [
+        1.495,
+        1.505
+      ],
+      "multiplicity": 2
+    }
+  ],
+  "poles": [
+    2.0
+  ]
+}"#
+        );
+    }
+
+    #[test]
+    fn duplicates_within_a_tenth_of_the_step_are_dropped() {
+        // step 0.01: values closer than 0.001 are the same root or pole
+        let mut scan = RootScan::new("x", (-1.0, 1.0), RootConfig::default());
+        for value in [0.5, 0.5005, 0.499, 0.502] {
+            scan.add_root(root(value, 0.0, RootKind::Crossing, 1));
+            scan.add_pole(-value);
+        }
+        assert_eq!(scan.values(), vec![0.5, 0.499, 0.502]);
+        assert_eq!(scan.poles, vec![-0.5, -0.499, -0.502]);
+
+        let config = RootConfig {
+            step_size: 1.0,
+            ..RootConfig::default()
+        };
+        let mut scan = RootScan::new("x", (-1.0, 1.0), config);
+        scan.add_root(root(0.5, 0.0, RootKind::Crossing, 1));
+        scan.add_root(root(0.55, 0.0, RootKind::Crossing, 1));
+        assert_eq!(scan.values(), vec![0.5]);
+    }
+
+    #[test]
+    fn sort_orders_roots_and_poles() {
+        let mut scan = RootScan::new("x", (-3.0, 3.0), RootConfig::default());
+        for value in [2.0, -0.0, -2.5, 1.0] {
+            scan.add_root(root(value, 0.0, RootKind::Crossing, 1));
+        }
+        scan.add_pole(1.5);
+        scan.add_pole(-1.5);
+        scan.sort();
+        assert_eq!(scan.values(), vec![-2.5, -0.0, 1.0, 2.0]);
+        assert_eq!(scan.poles, vec![-1.5, 1.5]);
+        assert!(scan.contains(3.0) && scan.contains(-3.0) && !scan.contains(3.1));
+    }
+
+    #[test]
+    fn multiplicity_is_estimated_from_the_growth_of_f() {
+        for (input, expected) in [
+            ("x - 0.3", 1),
+            ("(x - 0.3)^2", 2),
+            ("(x - 0.3)^3", 3),
+            ("(x - 0.3)^5", 5),
+            ("sin(x - 0.3)", 1),
+            ("1 - cos(x - 0.3)", 2),
+        ] {
+            let ast = lex_parse_input(input).unwrap();
+            let mut vars = VariableManager::new();
+            let mut resolver = MapOnlyResolver;
+            let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+            let scan = RootFinder::new(&ast, &mut evaluator)
+                .find_all_roots(-1.0, 1.0)
+                .unwrap();
+            let multiplicities: Vec<_> = scan.roots.iter().map(|r| r.multiplicity).collect();
+            assert_eq!(
+                multiplicities,
+                vec![expected],
+                "{}: {:?}",
+                input,
+                scan.roots
+            );
+        }
+    }
+}
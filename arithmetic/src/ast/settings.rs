@@ -0,0 +1,39 @@
+use super::AngleMode;
+use std::fmt;
+
+/// What happens when a function is used outside of its domain: sqrt(-1), ln(0), ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DomainMode {
+    /// Raise an evaluation error
+    Strict,
+    /// Keep the IEEE result (NaN, Infinity) and collect a warning
+    #[default]
+    Lenient,
+}
+
+impl DomainMode {
+    /// "strict" or "lenient"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "strict" => Some(DomainMode::Strict),
+            "lenient" => Some(DomainMode::Lenient),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DomainMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainMode::Strict => write!(f, "strict"),
+            DomainMode::Lenient => write!(f, "lenient"),
+        }
+    }
+}
+
+/// Options of the evaluator shared by evaluation, root finding and plotting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EvalSettings {
+    pub angle_mode: AngleMode,
+    pub domain_mode: DomainMode,
+}
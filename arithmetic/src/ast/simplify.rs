@@ -0,0 +1,639 @@
+use super::{
+    resolver::MapOnlyResolver, ASTNode, Builtin, DomainMode, EvalSettings, Evaluator, Num,
+    TextSpan, TokenKind, VariableManager,
+};
+
+/// Upper bound of simplification passes, each pass only makes the tree smaller
+const MAX_PASSES: usize = 64;
+
+/// Algebraic simplification: constant folding, identities (x+0, x*1, x*0, x^1, x^0),
+/// double negation, like terms (2x + 3x = 5x, x*x = x^2) and flat `+`/`*` chains.
+impl ASTNode {
+    /// Simplifies until nothing changes. Constants are folded by the evaluator
+    /// with the given angle unit; sub-expressions outside of their domain are kept.
+    pub fn simplify_with(&self, settings: EvalSettings) -> ASTNode {
+        let mut node = self.clone();
+        let mut text = node.postfix();
+
+        for _ in 0..MAX_PASSES {
+            let next = node.simplify_once(settings);
+            let next_text = next.postfix();
+            if next_text == text {
+                break;
+            }
+            node = next;
+            text = next_text;
+        }
+
+        node
+    }
+
+    fn simplify_once(&self, settings: EvalSettings) -> ASTNode {
+        let span = self.span().clone();
+
+        // Children first
+        let node = match self {
+            ASTNode::BinaryOp(left, op, right, span) => ASTNode::BinaryOp(
+                Box::new(left.simplify_once(settings)),
+                op.clone(),
+                Box::new(right.simplify_once(settings)),
+                span.clone(),
+            ),
+            ASTNode::UnaryOp(op, expr, span) => ASTNode::UnaryOp(
+                op.clone(),
+                Box::new(expr.simplify_once(settings)),
+                span.clone(),
+            ),
+            ASTNode::FunctionCall(name, args, span) => ASTNode::FunctionCall(
+                name.clone(),
+                args.iter().map(|arg| arg.simplify_once(settings)).collect(),
+                span.clone(),
+            ),
+            _ => self.clone(),
+        };
+
+        if let Some(value) = fold(&node, settings) {
+            return number(value, &span);
+        }
+
+        match node {
+            ASTNode::UnaryOp(TokenKind::Minus, expr, _) => negate(*expr, &span),
+            ASTNode::BinaryOp(_, TokenKind::Plus | TokenKind::Minus, _, _) => {
+                collect_terms(&node, &span)
+            }
+            ASTNode::BinaryOp(_, TokenKind::Multiply, _, _) => collect_factors(&node, &span),
+            ASTNode::BinaryOp(left, TokenKind::Divide, right, _) => {
+                match (value_of(&left), value_of(&right)) {
+                    (Some(l), r) if l.is_zero() && r.is_none_or(|r| !r.is_zero()) => {
+                        number(Value::integer(0), &span)
+                    }
+                    (_, Some(r)) if r.is_one() => *left,
+                    _ => ASTNode::BinaryOp(left, TokenKind::Divide, right, span),
+                }
+            }
+            ASTNode::BinaryOp(base, TokenKind::Power, exponent, _) => {
+                match (value_of(&base), value_of(&exponent)) {
+                    (_, Some(e)) if e.is_zero() => number(Value::integer(1), &span),
+                    (_, Some(e)) if e.is_one() => *base,
+                    (Some(b), _) if b.is_one() => number(Value::integer(1), &span),
+                    _ => ASTNode::BinaryOp(base, TokenKind::Power, exponent, span),
+                }
+            }
+            node => node,
+        }
+    }
+}
+
+/// Value of a constant sub-expression. Arithmetic on numbers is exact, see `Value`,
+/// everything else is evaluated the same way as the program. Sub-expressions with
+/// variables, pi, e, user functions or domain errors are kept.
+fn fold(node: &ASTNode, settings: EvalSettings) -> Option<Value> {
+    if !is_foldable(node) {
+        return None;
+    }
+    if let Some(value) = exact(node) {
+        return Some(value).filter(|value| value.to_f64().is_finite());
+    }
+
+    let mut vars = VariableManager::new();
+    let mut resolver = MapOnlyResolver;
+    let settings = EvalSettings {
+        domain_mode: DomainMode::Strict,
+        ..settings
+    };
+    let mut evaluator = Evaluator::new(&mut vars, &mut resolver).with_settings(settings);
+
+    evaluator
+        .evaluate(node)
+        .ok()
+        .filter(|value| value.is_finite())
+        .map(Value::from_f64)
+}
+
+/// +, -, *, / and integer powers of numbers
+fn exact(node: &ASTNode) -> Option<Value> {
+    match node {
+        ASTNode::Number(_, _) => value_of(node),
+        ASTNode::UnaryOp(TokenKind::Minus, expr, _) => exact(expr).map(Value::neg),
+        ASTNode::BinaryOp(left, op, right, _) => {
+            let (left, right) = (exact(left)?, exact(right)?);
+            match op {
+                TokenKind::Plus => Some(left.add(right)),
+                TokenKind::Minus => Some(left.add(right.neg())),
+                TokenKind::Multiply => Some(left.mul(right)),
+                TokenKind::Divide => left.div(right),
+                TokenKind::Power => match right {
+                    Value::Exact(exponent, 1) => left.powi(exponent),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_foldable(node: &ASTNode) -> bool {
+    match node {
+        ASTNode::Number(_, _) | ASTNode::Mantissa(_, _) => true,
+        ASTNode::Constant(_, _) | ASTNode::Identifier(_, _) | ASTNode::Error(_) => false,
+        ASTNode::BinaryOp(left, _, right, _) => is_foldable(left) && is_foldable(right),
+        ASTNode::UnaryOp(_, expr, _) => is_foldable(expr),
+        ASTNode::FunctionCall(name, args, _) => {
+            Builtin::lookup(name).is_some() && args.iter().all(is_foldable)
+        }
+    }
+}
+
+/// Value of a number literal, negative numbers are `-(n)` and fractions `n / d`
+fn value_of(node: &ASTNode) -> Option<Value> {
+    match node {
+        ASTNode::Number(Num::Integer(i), _) => Some(Value::integer(*i)),
+        ASTNode::Number(Num::Float(f), _) => Some(Value::from_f64(*f)),
+        ASTNode::UnaryOp(TokenKind::Minus, expr, _) => value_of(expr).map(Value::neg),
+        ASTNode::BinaryOp(left, TokenKind::Divide, right, _) => match (&**left, &**right) {
+            (ASTNode::Number(Num::Integer(n), _), ASTNode::Number(Num::Integer(d), _)) => {
+                Value::ratio(*n as i128, *d as i128)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The literal of a value: an integer, a decimal when it has a finite decimal
+/// expansion (3/10 is 0.3) and the fraction n / d otherwise
+fn number(value: Value, span: &TextSpan) -> ASTNode {
+    if value.is_negative() {
+        return ASTNode::UnaryOp(
+            TokenKind::Minus,
+            Box::new(number(value.neg(), span)),
+            span.clone(),
+        );
+    }
+
+    let num = match value {
+        Value::Exact(n, 1) => Num::Integer(n),
+        Value::Exact(n, d) if is_decimal(d) => Num::Float(n as f64 / d as f64),
+        Value::Exact(n, d) => {
+            let integer = |i| ASTNode::Number(Num::Integer(i), span.clone());
+            return binary(integer(n), TokenKind::Divide, integer(d), span);
+        }
+        Value::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Num::Integer(f as i64),
+        Value::Float(f) => Num::Float(f),
+    };
+    ASTNode::Number(num, span.clone())
+}
+
+/// A denominator without prime factors other than 2 and 5
+fn is_decimal(mut d: i64) -> bool {
+    for factor in [2, 5] {
+        while d % factor == 0 {
+            d /= factor;
+        }
+    }
+    d == 1
+}
+
+fn negate(node: ASTNode, span: &TextSpan) -> ASTNode {
+    match node {
+        // --x = x
+        ASTNode::UnaryOp(TokenKind::Minus, inner, _) => *inner,
+        _ => match value_of(&node) {
+            Some(value) => number(value.neg(), span),
+            None => ASTNode::UnaryOp(TokenKind::Minus, Box::new(node), span.clone()),
+        },
+    }
+}
+
+/// Sum of coefficient·term pairs: a - 2*b + 3 = [(1, a), (-2, b), (3, 1)]
+fn flatten_sum(node: &ASTNode, sign: Value, terms: &mut Vec<(Value, Option<ASTNode>)>) {
+    match node {
+        ASTNode::BinaryOp(left, TokenKind::Plus, right, _) => {
+            flatten_sum(left, sign, terms);
+            flatten_sum(right, sign, terms);
+        }
+        ASTNode::BinaryOp(left, TokenKind::Minus, right, _) => {
+            flatten_sum(left, sign, terms);
+            flatten_sum(right, sign.neg(), terms);
+        }
+        ASTNode::UnaryOp(TokenKind::Minus, expr, _) if value_of(node).is_none() => {
+            flatten_sum(expr, sign.neg(), terms);
+        }
+        _ => {
+            let (coefficient, term) = split_coefficient(node);
+            terms.push((sign.mul(coefficient), term));
+        }
+    }
+}
+
+/// Product of factors with the numbers multiplied into one coefficient
+fn flatten_product(node: &ASTNode, coefficient: &mut Value, factors: &mut Vec<ASTNode>) {
+    match node {
+        ASTNode::BinaryOp(left, TokenKind::Multiply, right, _) => {
+            flatten_product(left, coefficient, factors);
+            flatten_product(right, coefficient, factors);
+        }
+        ASTNode::UnaryOp(TokenKind::Minus, expr, _) if value_of(node).is_none() => {
+            *coefficient = coefficient.neg();
+            flatten_product(expr, coefficient, factors);
+        }
+        _ => match value_of(node) {
+            Some(value) => *coefficient = coefficient.mul(value),
+            None => factors.push(node.clone()),
+        },
+    }
+}
+
+/// 3*x*y = (3, x*y), x = (1, x), 5 = (5, None)
+fn split_coefficient(node: &ASTNode) -> (Value, Option<ASTNode>) {
+    let mut coefficient = Value::integer(1);
+    let mut factors = Vec::new();
+    flatten_product(node, &mut coefficient, &mut factors);
+
+    let span = node.span();
+    let term = factors
+        .into_iter()
+        .reduce(|left, right| binary(left, TokenKind::Multiply, right, span));
+    (coefficient, term)
+}
+
+/// Factors in a fixed order, x*y and y*x are like terms
+fn term_key(term: &ASTNode) -> String {
+    let mut coefficient = Value::integer(1);
+    let mut factors = Vec::new();
+    flatten_product(term, &mut coefficient, &mut factors);
+
+    let mut keys: Vec<String> = factors.iter().map(|factor| factor.postfix()).collect();
+    keys.sort();
+    keys.join("* ")
+}
+
+/// Collects like terms of a `+`/`-` chain: x + 2*x - 3 + 1 = 3*x - 2
+fn collect_terms(node: &ASTNode, span: &TextSpan) -> ASTNode {
+    let mut terms = Vec::new();
+    flatten_sum(node, Value::integer(1), &mut terms);
+
+    let mut constant = Value::integer(0);
+    let mut combined: Vec<(Value, ASTNode, String)> = Vec::new();
+    for (coefficient, term) in terms {
+        let Some(term) = term else {
+            constant = constant.add(coefficient);
+            continue;
+        };
+
+        let key = term_key(&term);
+        match combined.iter_mut().find(|(_, _, other)| *other == key) {
+            Some((sum, _, _)) => *sum = sum.add(coefficient),
+            None => combined.push((coefficient, term, key)),
+        }
+    }
+
+    let mut result: Option<ASTNode> = None;
+    let parts = combined
+        .into_iter()
+        .filter(|(coefficient, _, _)| !coefficient.is_zero())
+        .map(|(coefficient, term, _)| (coefficient, Some(term)))
+        .chain((!constant.is_zero()).then_some((constant, None)));
+
+    for (coefficient, term) in parts {
+        let magnitude = scale(coefficient.abs(), term, span);
+        result = Some(match result {
+            None if coefficient.is_negative() => negate(magnitude, span),
+            None => magnitude,
+            Some(sum) if coefficient.is_negative() => {
+                binary(sum, TokenKind::Minus, magnitude, span)
+            }
+            Some(sum) => binary(sum, TokenKind::Plus, magnitude, span),
+        });
+    }
+
+    result.unwrap_or_else(|| number(Value::integer(0), span))
+}
+
+/// Collects the factors of a `*` chain: 2*x*3*x = 6*x^2
+fn collect_factors(node: &ASTNode, span: &TextSpan) -> ASTNode {
+    let mut coefficient = Value::integer(1);
+    let mut factors = Vec::new();
+    flatten_product(node, &mut coefficient, &mut factors);
+
+    if coefficient.is_zero() {
+        return number(coefficient, span);
+    }
+
+    // Same base: the exponents are added
+    let mut powers: Vec<(ASTNode, Value, String)> = Vec::new();
+    let mut others: Vec<ASTNode> = Vec::new();
+    for factor in factors {
+        let (base, exponent) = match &factor {
+            ASTNode::BinaryOp(base, TokenKind::Power, exponent, _) => match value_of(exponent) {
+                Some(exponent) => ((**base).clone(), exponent),
+                None => {
+                    others.push(factor);
+                    continue;
+                }
+            },
+            _ => (factor.clone(), Value::integer(1)),
+        };
+
+        let key = base.postfix();
+        match powers.iter_mut().find(|(_, _, other)| *other == key) {
+            Some((_, sum, _)) => *sum = sum.add(exponent),
+            None => powers.push((base, exponent, key)),
+        }
+    }
+
+    let product = powers
+        .into_iter()
+        .filter(|(_, exponent, _)| !exponent.is_zero())
+        .map(|(base, exponent, _)| match exponent.is_one() {
+            true => base,
+            false => binary(base, TokenKind::Power, number(exponent, span), span),
+        })
+        .chain(others)
+        .reduce(|left, right| binary(left, TokenKind::Multiply, right, span));
+
+    match product {
+        Some(product) if coefficient.is_negative() => {
+            negate(scale(coefficient.neg(), Some(product), span), span)
+        }
+        Some(product) => scale(coefficient, Some(product), span),
+        None => number(coefficient, span),
+    }
+}
+
+/// coefficient·term without the factor 1
+fn scale(coefficient: Value, term: Option<ASTNode>, span: &TextSpan) -> ASTNode {
+    match term {
+        None => number(coefficient, span),
+        Some(term) if coefficient.is_one() => term,
+        Some(term) => prepend(number(coefficient, span), term),
+    }
+}
+
+/// factor * (a * b) as the chain factor * a * b
+fn prepend(factor: ASTNode, product: ASTNode) -> ASTNode {
+    match product {
+        ASTNode::BinaryOp(left, TokenKind::Multiply, right, span) => ASTNode::BinaryOp(
+            Box::new(prepend(factor, *left)),
+            TokenKind::Multiply,
+            right,
+            span,
+        ),
+        _ => {
+            let span = product.span().clone();
+            binary(factor, TokenKind::Multiply, product, &span)
+        }
+    }
+}
+
+fn binary(left: ASTNode, op: TokenKind, right: ASTNode, span: &TextSpan) -> ASTNode {
+    ASTNode::BinaryOp(Box::new(left), op, Box::new(right), span.clone())
+}
+
+/// Number of the simplifier. Integers, quotients of integers and decimal literals are
+/// exact, so 1/3 + 1/3 = 2/3 and 0.1 + 0.2 = 0.3. Values of functions and exact values
+/// that do not fit in i64 are floats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    /// Numerator and denominator, reduced. The denominator is positive.
+    Exact(i64, i64),
+    Float(f64),
+}
+
+impl Value {
+    fn integer(n: i64) -> Self {
+        Value::Exact(n, 1)
+    }
+
+    /// n / d, a float when it does not fit. None for d = 0.
+    fn ratio(n: i128, d: i128) -> Option<Self> {
+        if d == 0 {
+            return None;
+        }
+        let divisor = gcd(n.unsigned_abs(), d.unsigned_abs()) as i128 * d.signum();
+        let (n, d) = (n / divisor, d / divisor);
+
+        Some(match (i64::try_from(n), i64::try_from(d)) {
+            (Ok(n), Ok(d)) => Value::Exact(n, d),
+            _ => Value::Float(n as f64 / d as f64),
+        })
+    }
+
+    /// Decimals are exact as they are written: 0.1 is 1/10
+    fn from_f64(value: f64) -> Self {
+        let text = value.abs().to_string();
+        let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+        let digits = format!("{}{}", whole, fraction).parse::<i64>();
+
+        match (digits, 10i64.checked_pow(fraction.len() as u32)) {
+            (Ok(n), Some(d)) if value.is_finite() => {
+                Value::ratio(n as i128 * value.signum() as i128, d as i128)
+                    .unwrap_or(Value::Float(value))
+            }
+            _ => Value::Float(value),
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        match self {
+            Value::Exact(n, d) => n as f64 / d as f64,
+            Value::Float(f) => f,
+        }
+    }
+
+    fn add(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Exact(a, b), Value::Exact(c, d)) => {
+                let (a, b, c, d) = (a as i128, b as i128, c as i128, d as i128);
+                Value::ratio(a * d + c * b, b * d).unwrap()
+            }
+            _ => Value::Float(self.to_f64() + other.to_f64()),
+        }
+    }
+
+    fn mul(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Exact(a, b), Value::Exact(c, d)) => {
+                Value::ratio(a as i128 * c as i128, b as i128 * d as i128).unwrap()
+            }
+            _ => Value::Float(self.to_f64() * other.to_f64()),
+        }
+    }
+
+    /// None for a division by zero
+    fn div(self, other: Value) -> Option<Value> {
+        match (self, other) {
+            _ if other.is_zero() => None,
+            (Value::Exact(a, b), Value::Exact(c, d)) => {
+                Value::ratio(a as i128 * d as i128, b as i128 * c as i128)
+            }
+            _ => Some(Value::Float(self.to_f64() / other.to_f64())),
+        }
+    }
+
+    /// Small exponents only, the others are left to the evaluator
+    fn powi(self, exponent: i64) -> Option<Value> {
+        if exponent.unsigned_abs() > 64 {
+            return None;
+        }
+        let base = match exponent < 0 {
+            true => Value::integer(1).div(self)?,
+            false => self,
+        };
+        Some((0..exponent.unsigned_abs()).fold(Value::integer(1), |power, _| power.mul(base)))
+    }
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Exact(n, d) => Value::ratio(-(n as i128), d as i128).unwrap(),
+            Value::Float(f) => Value::Float(-f),
+        }
+    }
+
+    fn abs(self) -> Value {
+        match self.is_negative() {
+            true => self.neg(),
+            false => self,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        matches!(self, Value::Exact(0, _) | Value::Float(0.0))
+    }
+
+    fn is_one(self) -> bool {
+        matches!(self, Value::Exact(1, 1) | Value::Float(1.0))
+    }
+
+    fn is_negative(self) -> bool {
+        match self {
+            Value::Exact(n, _) => n < 0,
+            Value::Float(f) => f < 0.0,
+        }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::AngleMode;
+    use super::*;
+    use crate::utils::print::lex_parse_input;
+
+    fn simplify(input: &str) -> String {
+        lex_parse_input(input)
+            .unwrap()
+            .simplify_with(EvalSettings::default())
+            .infix()
+    }
+
+    #[test]
+    fn identities() {
+        assert_eq!(simplify("x * 1"), "x");
+        assert_eq!(simplify("1 * x + 0"), "x");
+        assert_eq!(simplify("0 * x"), "0");
+        assert_eq!(simplify("x - x"), "0");
+        assert_eq!(simplify("x / 1"), "x");
+        assert_eq!(simplify("0 / x"), "0");
+        assert_eq!(simplify("x^1 + y^0"), "x + 1");
+        assert_eq!(simplify("1^x"), "1");
+        assert_eq!(simplify("--x"), "x");
+    }
+
+    #[test]
+    fn like_terms_and_factors() {
+        assert_eq!(simplify("2*x + 3*x + 0*y"), "5 * x");
+        assert_eq!(simplify("x*y - 2*y*x + 3"), "-(x * y) + 3");
+        assert_eq!(simplify("x + 1 - 2 + x"), "2 * x - 1");
+        assert_eq!(simplify("x*x*x"), "x^3");
+        assert_eq!(simplify("2*x*3*x^-1"), "6");
+        assert_eq!(simplify("(x + 1) * (x + 1)"), "(x + 1)^2");
+    }
+
+    #[test]
+    fn constants_are_folded_exactly() {
+        assert_eq!(simplify("2 + 3 * 4"), "14");
+        assert_eq!(simplify("1/3"), "1 / 3");
+        assert_eq!(simplify("1/3 + 1/3"), "2 / 3");
+        assert_eq!(simplify("6/9 * x + x/3"), "2 / 3 * x + x / 3");
+        assert_eq!(simplify("1/3 * x + 2/3 * x"), "x");
+        assert_eq!(simplify("x + 0.1 + 0.2"), "x + 0.3");
+        assert_eq!(simplify("1/4 + 2^-1"), "0.75");
+        assert_eq!(simplify("x^(1/2) * x^(1/2)"), "x");
+    }
+
+    #[test]
+    fn functions_are_folded_by_the_evaluator() {
+        let settings = EvalSettings {
+            angle_mode: AngleMode::Radians,
+            ..EvalSettings::default()
+        };
+        let simplify_in = |input: &str| {
+            lex_parse_input(input)
+                .unwrap()
+                .simplify_with(settings)
+                .infix()
+        };
+        // The angle unit of the settings is used
+        assert_eq!(simplify("cos(180) * x"), "-x");
+        assert_eq!(simplify_in("cos(180) * x"), "-(0.5984600690578581 * x)");
+        assert_eq!(simplify_in("sqrt(4) + x"), "x + 2");
+        assert_eq!(simplify_in("cos(0) * x"), "x");
+        assert_eq!(simplify_in("max(1, 2, 3) * 2"), "6");
+    }
+
+    #[test]
+    fn what_is_not_folded() {
+        assert_eq!(simplify("2 * pi"), "2 * pi");
+        assert_eq!(simplify("1/0 + x"), "1 / 0 + x");
+        assert_eq!(simplify("sqrt(-1)"), "sqrt(-1)");
+        assert_eq!(simplify("ln(0) * 2"), "2 * ln(0)");
+    }
+
+    #[test]
+    fn simplification_reaches_a_fixed_point() {
+        let inputs = [
+            "x * (2 * (3 * (x * 1)))",
+            "((x + 0) * 1 - 0) / 1",
+            "-(-(-(x)))",
+            "1/3 * x - 2/6 * x + 0.5 * y",
+            "sin(x)^1 * sin(x) + 2 * sin(x)^2",
+        ];
+        for input in inputs {
+            let once = lex_parse_input(input)
+                .unwrap()
+                .simplify_with(EvalSettings::default());
+            let twice = once.simplify_with(EvalSettings::default());
+            assert_eq!(once.infix(), twice.infix(), "{}", input);
+            // The printed result parses to the same tree
+            assert_eq!(simplify(&once.infix()), once.infix(), "{}", input);
+        }
+        assert_eq!(simplify("x * (2 * (3 * (x * 1)))"), "6 * x^2");
+        assert_eq!(simplify("sin(x)^1 * sin(x) + 2 * sin(x)^2"), "3 * sin(x)^2");
+    }
+
+    #[test]
+    fn exact_values() {
+        assert_eq!(Value::ratio(6, -4), Some(Value::Exact(-3, 2)));
+        assert_eq!(Value::ratio(0, 7), Some(Value::Exact(0, 1)));
+        assert_eq!(Value::ratio(1, 0), None);
+        assert_eq!(Value::from_f64(0.1), Value::Exact(1, 10));
+        assert_eq!(Value::from_f64(-2.5), Value::Exact(-5, 2));
+        assert_eq!(Value::from_f64(1e20), Value::Float(1e20));
+        assert_eq!(Value::from_f64(1e-30), Value::Float(1e-30));
+
+        // Too large for i64: the value is kept as a float
+        let big = Value::integer(i64::MAX).mul(Value::integer(4));
+        assert_eq!(big, Value::Float(i64::MAX as f64 * 4.0));
+        assert_eq!(Value::integer(2).powi(-3), Some(Value::Exact(1, 8)));
+        assert_eq!(Value::integer(0).powi(-1), None);
+        assert_eq!(Value::integer(2).powi(100), None);
+    }
+}
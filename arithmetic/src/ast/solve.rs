@@ -0,0 +1,608 @@
+use super::{
+    polynomial::Complex,
+    root::{RootConfig, RootFinder, RootMethod},
+    root_report::RootScan,
+    ASTNode, CompilerError, Evaluator, Num, RootFinderError, TextSpan, TokenKind, VariableManager,
+};
+use std::fmt;
+
+/// How the solutions of an equation were found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolveMethod {
+    /// The unknown occurs once, the operations around it are undone one by one
+    Isolation,
+    /// lhs - rhs is a polynomial of degree 1 in the unknown
+    Linear,
+    /// lhs - rhs is a polynomial of a higher degree, all of its roots are found
+    Polynomial,
+    /// The root finder on lhs - rhs in an interval
+    Numeric(RootMethod),
+}
+
+impl fmt::Display for SolveMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveMethod::Isolation => write!(f, "isolation"),
+            SolveMethod::Linear => write!(f, "linear equation"),
+            SolveMethod::Polynomial => write!(f, "polynomial roots"),
+            SolveMethod::Numeric(method) => write!(f, "root finder ({})", method),
+        }
+    }
+}
+
+/// A value of the unknown for which the equation holds
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub value: f64,
+    /// The unknown isolated as an expression of the other variables
+    pub form: Option<ASTNode>,
+    /// lhs - rhs at the value
+    pub residual: f64,
+}
+
+/// Every solution found for the unknown
+#[derive(Debug, Clone)]
+pub struct Solutions {
+    pub variable: String,
+    pub method: SolveMethod,
+    pub solutions: Vec<Solution>,
+    /// Roots of a polynomial that are not real
+    pub complex: Vec<Complex>,
+    /// lhs - rhs is 0 whatever the unknown is
+    pub identity: bool,
+    /// Interval searched by the root finder
+    pub interval: Option<(f64, f64)>,
+}
+
+impl Solutions {
+    fn new(variable: &str, method: SolveMethod) -> Self {
+        Self {
+            variable: variable.to_string(),
+            method,
+            solutions: Vec::new(),
+            complex: Vec::new(),
+            identity: false,
+            interval: None,
+        }
+    }
+
+    /// Adds the solution in order unless it is already known, a double root is listed once
+    fn add(&mut self, solution: Solution) {
+        if !self
+            .solutions
+            .iter()
+            .any(|known| known.value == solution.value)
+        {
+            self.solutions.push(solution);
+            self.solutions.sort_by(|a, b| a.value.total_cmp(&b.value));
+        }
+    }
+
+    /// The roots of the scan are the solutions, the poles are not
+    pub fn from_scan(variable: &str, scan: &RootScan) -> Self {
+        let mut solutions = Self::new(variable, SolveMethod::Numeric(scan.config.method));
+        solutions.interval = Some(scan.interval);
+        for root in scan.roots.iter() {
+            solutions.add(Solution {
+                value: root.value,
+                form: None,
+                residual: root.residual,
+            });
+        }
+        solutions
+    }
+}
+
+/// Solved for x by isolation:
+///   x = 6 / a = 2
+impl fmt::Display for Solutions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.identity {
+            return write!(f, "Every value of {} is a solution.", self.variable);
+        }
+        if self.solutions.is_empty() && self.complex.is_empty() {
+            return match self.interval {
+                Some((a, b)) => write!(f, "No solution for {} in [{}, {}].", self.variable, a, b),
+                None => write!(f, "No solution for {}.", self.variable),
+            };
+        }
+
+        write!(f, "Solved for {} by {}:", self.variable, self.method)?;
+        for solution in self.solutions.iter() {
+            write!(f, "\n  {} = ", self.variable)?;
+            if let Some(form) = &solution.form {
+                write!(f, "{} = ", form.infix())?;
+            }
+            write!(f, "{}", solution.value + 0.0)?;
+            if solution.residual != 0.0 {
+                write!(f, "  (residual {:.3e})", solution.residual)?;
+            }
+        }
+        for root in self.complex.iter() {
+            write!(f, "\n  {} = {}  (complex)", self.variable, root)?;
+        }
+        Ok(())
+    }
+}
+
+/// Solves `ast = 0` for var, the result of lhs = rhs is lhs - rhs. User functions must be
+/// inlined first. The root finder is only used, in the interval a, b, when var can not be
+/// isolated and ast is not a polynomial. Without an interval that is an error.
+pub fn solve<'a>(
+    ast: &'a ASTNode,
+    var: &str,
+    evaluator: &'a mut Evaluator<'a>,
+    (a, b): (Option<f64>, Option<f64>),
+    config: RootConfig,
+) -> Result<Solutions, CompilerError> {
+    if !ast.depends_on(var) {
+        return Err(CompilerError::Root(RootFinderError::NoUnknown));
+    }
+    if let Some(solutions) = solve_symbolic(ast, var, evaluator)? {
+        return Ok(solutions);
+    }
+    if a.is_none() && b.is_none() {
+        return Err(CompilerError::Root(RootFinderError::NoSymbolicSolution));
+    }
+
+    let mut root_finder = RootFinder::new(ast, evaluator)
+        .with_config(config)
+        .with_variable(var);
+    Ok(Solutions::from_scan(var, &root_finder.find_roots(a, b)?))
+}
+
+/// The variable of the expression without a stored value, x when all of them have one
+pub fn unknown(ast: &ASTNode, vars: &VariableManager) -> Result<String, CompilerError> {
+    let variables = ast.variables();
+    let free: Vec<String> = variables
+        .iter()
+        .filter(|name| vars.get_stored(name).is_none())
+        .cloned()
+        .collect();
+
+    match (free.as_slice(), variables.len()) {
+        ([var], _) => Ok(var.clone()),
+        ([], 1) => Ok(variables.into_iter().next().unwrap_or_default()),
+        ([], _) if variables.contains("x") => Ok("x".to_string()),
+        ([], _) => Err(CompilerError::Root(RootFinderError::NoUnknown)),
+        _ => Err(CompilerError::Root(RootFinderError::SeveralUnknowns(free))),
+    }
+}
+
+/// The variable of f = 0 for roots: the unknown of [`unknown`], x when f has no variables
+pub fn roots_unknown(ast: &ASTNode, vars: &VariableManager) -> Result<String, CompilerError> {
+    match unknown(ast, vars) {
+        Err(CompilerError::Root(RootFinderError::NoUnknown)) => Ok("x".to_string()),
+        result => result,
+    }
+}
+
+/// Solves `ast = 0` for var without an interval: polynomials exactly or with their roots,
+/// the rest by isolating var. None when neither applies and the root finder is needed.
+/// The stored value of var is kept.
+pub fn solve_symbolic(
+    ast: &ASTNode,
+    var: &str,
+    evaluator: &mut Evaluator,
+) -> Result<Option<Solutions>, CompilerError> {
+    let previous = evaluator.vars.get_stored(var);
+    let solutions = solve_with(ast, var, evaluator);
+    match previous {
+        Some(value) => evaluator.vars.set(var.to_string(), value),
+        None => {
+            evaluator.vars.remove(var);
+        }
+    }
+    solutions
+}
+
+fn solve_with(
+    ast: &ASTNode,
+    var: &str,
+    evaluator: &mut Evaluator,
+) -> Result<Option<Solutions>, CompilerError> {
+    let polynomial = ast.polynomial(var, evaluator)?;
+
+    // 2x + 1 - 7 = 0 and sqrt(x) - 3 = 0
+    if polynomial.as_ref().is_none_or(|p| p.degree() == 1) && occurrences(ast, var) == 1 {
+        let mut solutions = Solutions::new(var, SolveMethod::Isolation);
+        let zero = ASTNode::Number(Num::Integer(0), ast.span().clone());
+        for form in isolate(ast, zero, var, evaluator).unwrap_or_default() {
+            let form = form.simplify_with(evaluator.settings);
+            let Ok(value) = evaluator.evaluate(&form) else {
+                continue;
+            };
+            if let Some(residual) = check(ast, var, value, evaluator) {
+                // Only worth showing when it has the other variables
+                let form = (!form.variables().is_empty()).then_some(form);
+                solutions.add(Solution {
+                    value,
+                    form,
+                    residual,
+                });
+            }
+        }
+        if !solutions.solutions.is_empty() {
+            return Ok(Some(solutions));
+        }
+    }
+
+    let Some(polynomial) = polynomial else {
+        return Ok(None);
+    };
+
+    let c = &polynomial.coefficients;
+    match polynomial.degree() {
+        0 => {
+            let mut solutions = Solutions::new(var, SolveMethod::Linear);
+            solutions.identity = c[0] == 0.0;
+            Ok(Some(solutions))
+        }
+        1 => {
+            let mut solutions = Solutions::new(var, SolveMethod::Linear);
+            let value = -c[0] / c[1];
+            let residual = check(ast, var, value, evaluator).unwrap_or(f64::NAN);
+            solutions.add(Solution {
+                value,
+                form: None,
+                residual,
+            });
+            Ok(Some(solutions))
+        }
+        _ => {
+            let mut solutions = Solutions::new(var, SolveMethod::Polynomial);
+            for root in polynomial.roots().roots {
+                if root.im != 0.0 {
+                    solutions.complex.push(root);
+                    continue;
+                }
+                let residual = evaluator.evaluate_with(ast, var, root.re)?;
+                solutions.add(Solution {
+                    value: root.re,
+                    form: None,
+                    residual,
+                });
+            }
+            Ok(Some(solutions))
+        }
+    }
+}
+
+/// f(value) when value solves f = 0: f is 0 there or changes its sign around it.
+/// Isolation can give values that do not, like 4 for sqrt(x) = -2.
+fn check(ast: &ASTNode, var: &str, value: f64, evaluator: &mut Evaluator) -> Option<f64> {
+    if !value.is_finite() {
+        return None;
+    }
+    let residual = evaluator.evaluate_with(ast, var, value).ok()?;
+    if residual == 0.0 || residual.abs() <= 1e-12 {
+        return Some(residual);
+    }
+
+    let h = 1e-6 * value.abs().max(1.0);
+    let left = evaluator.evaluate_with(ast, var, value - h).ok()?;
+    let right = evaluator.evaluate_with(ast, var, value + h).ok()?;
+    (left * right <= 0.0 && residual.abs() <= left.abs().max(right.abs())).then_some(residual)
+}
+
+/// Number of times var appears in the expression
+fn occurrences(node: &ASTNode, var: &str) -> usize {
+    match node {
+        ASTNode::Identifier(name, _) => usize::from(name.eq_ignore_ascii_case(var)),
+        ASTNode::BinaryOp(left, _, right, _) => occurrences(left, var) + occurrences(right, var),
+        ASTNode::UnaryOp(_, expr, _) => occurrences(expr, var),
+        ASTNode::FunctionCall(_, args, _) => args.iter().map(|arg| occurrences(arg, var)).sum(),
+        _ => 0,
+    }
+}
+
+/// The expressions var can be equal to for `node = target`, where var occurs once in node.
+/// Operations are undone from the outside in: u + b = t gives u = t - b. Even powers and
+/// abs give two candidates, periodic functions only their principal value.
+fn isolate(
+    node: &ASTNode,
+    target: ASTNode,
+    var: &str,
+    evaluator: &mut Evaluator,
+) -> Option<Vec<ASTNode>> {
+    let span = node.span().clone();
+    let t = target;
+    let (inner, targets) = match node {
+        ASTNode::Identifier(_, _) => return Some(vec![t]),
+        ASTNode::UnaryOp(TokenKind::Plus, u, _) => (u.as_ref(), vec![t]),
+        ASTNode::UnaryOp(TokenKind::Minus, u, _) => (u.as_ref(), vec![negate(t, &span)]),
+        ASTNode::BinaryOp(a, op, b, _) => {
+            let in_a = a.depends_on(var);
+            let (u, other) = match in_a {
+                true => (a.as_ref(), b.as_ref().clone()),
+                false => (b.as_ref(), a.as_ref().clone()),
+            };
+            let targets = match (op, in_a) {
+                (TokenKind::Plus, _) => vec![binary(t, TokenKind::Minus, other, &span)],
+                (TokenKind::Minus, true) => vec![binary(t, TokenKind::Plus, other, &span)],
+                (TokenKind::Minus, false) => vec![binary(other, TokenKind::Minus, t, &span)],
+                (TokenKind::Multiply, _) => vec![binary(t, TokenKind::Divide, other, &span)],
+                (TokenKind::Divide, true) => vec![binary(t, TokenKind::Multiply, other, &span)],
+                (TokenKind::Divide, false) => vec![binary(other, TokenKind::Divide, t, &span)],
+                (TokenKind::Power, true) => nth_roots(other, t, &span, evaluator)?,
+                (TokenKind::Power, false) => vec![logarithm(t, other, &span)],
+                _ => return None,
+            };
+            (u, targets)
+        }
+        ASTNode::FunctionCall(name, args, _) => {
+            let name = name.to_lowercase();
+            match (name.as_str(), args.as_slice()) {
+                ("pow", [base, exponent]) => {
+                    let power = binary(base.clone(), TokenKind::Power, exponent.clone(), &span);
+                    return isolate(&power, t, var, evaluator);
+                }
+                // root(n, u) = t gives u = t^n
+                ("root", [n, u]) if !n.depends_on(var) => {
+                    (u, vec![binary(t, TokenKind::Power, n.clone(), &span)])
+                }
+                ("log", [u, base]) if !base.depends_on(var) => {
+                    (u, vec![binary(base.clone(), TokenKind::Power, t, &span)])
+                }
+                (_, [u]) => (u, inverse(&name, t, &span)?),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let mut forms = Vec::new();
+    for target in targets {
+        forms.extend(isolate(inner, target, var, evaluator)?);
+    }
+    Some(forms)
+}
+
+/// u^n = t: the n-th root, with both signs for even n
+fn nth_roots(
+    n: ASTNode,
+    t: ASTNode,
+    span: &TextSpan,
+    evaluator: &mut Evaluator,
+) -> Option<Vec<ASTNode>> {
+    let exponent = evaluator.evaluate(&n).ok()?;
+    if exponent == 0.0 || !exponent.is_finite() {
+        return None;
+    }
+
+    let root = call("root", vec![n, t], span);
+    match exponent.fract() == 0.0 && exponent % 2.0 == 0.0 {
+        true => Some(vec![root.clone(), negate(root, span)]),
+        false => Some(vec![root]),
+    }
+}
+
+/// base^u = t gives u = ln(t) / ln(base)
+fn logarithm(t: ASTNode, base: ASTNode, span: &TextSpan) -> ASTNode {
+    binary(
+        call("ln", vec![t], span),
+        TokenKind::Divide,
+        call("ln", vec![base], span),
+        span,
+    )
+}
+
+/// f(u) = t gives u = f^-1(t) for the built-in functions that have an inverse
+fn inverse(name: &str, t: ASTNode, span: &TextSpan) -> Option<Vec<ASTNode>> {
+    let one = |inverse: &str| Some(vec![call(inverse, vec![t.clone()], span)]);
+    let both = |inverse: &str| {
+        let value = call(inverse, vec![t.clone()], span);
+        Some(vec![value.clone(), negate(value, span)])
+    };
+    let power = |base: ASTNode, exponent: ASTNode| {
+        Some(vec![binary(base, TokenKind::Power, exponent, span)])
+    };
+
+    match name {
+        "sin" => one("arcsin"),
+        "cos" => one("arccos"),
+        "tan" => one("arctan"),
+        "arcsin" => one("sin"),
+        "arccos" => one("cos"),
+        "arctan" => one("tan"),
+        "sinh" => one("arcsinh"),
+        "cosh" => both("arccosh"),
+        "tanh" => one("arctanh"),
+        "arcsinh" => one("sinh"),
+        "arccosh" => one("cosh"),
+        "arctanh" => one("tanh"),
+        "exp" => one("ln"),
+        "ln" => one("exp"),
+        "log" => power(number(10, span), t.clone()),
+        "log2" => power(number(2, span), t.clone()),
+        "sqrt" => power(t.clone(), number(2, span)),
+        "cbrt" => power(t.clone(), number(3, span)),
+        "sqr" => both("sqrt"),
+        "abs" => Some(vec![t.clone(), negate(t.clone(), span)]),
+        _ => None,
+    }
+}
+
+fn number(value: i64, span: &TextSpan) -> ASTNode {
+    ASTNode::Number(Num::Integer(value), span.clone())
+}
+
+fn binary(left: ASTNode, op: TokenKind, right: ASTNode, span: &TextSpan) -> ASTNode {
+    ASTNode::BinaryOp(Box::new(left), op, Box::new(right), span.clone())
+}
+
+fn call(name: &str, args: Vec<ASTNode>, span: &TextSpan) -> ASTNode {
+    ASTNode::FunctionCall(name.to_string(), args, span.clone())
+}
+
+fn negate(node: ASTNode, span: &TextSpan) -> ASTNode {
+    ASTNode::UnaryOp(TokenKind::Minus, Box::new(node), span.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::resolver::MapOnlyResolver;
+    use super::*;
+    use crate::utils::print::{lex_parse_input, lex_parse_program};
+
+    /// Solves the equation of the input for x, without an interval unless one is given
+    fn solve_x(
+        input: &str,
+        interval: (Option<f64>, Option<f64>),
+    ) -> Result<Solutions, CompilerError> {
+        let (_, ast) = lex_parse_program(input).unwrap().split_result();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        solve(&ast, "x", &mut evaluator, interval, RootConfig::default())
+    }
+
+    fn values(solutions: &Solutions) -> Vec<f64> {
+        solutions.solutions.iter().map(|s| s.value).collect()
+    }
+
+    fn close(values: &[f64], expected: &[f64]) -> bool {
+        values.len() == expected.len()
+            && values
+                .iter()
+                .zip(expected)
+                .all(|(value, expected)| (value - expected).abs() <= 1e-12)
+    }
+
+    #[test]
+    fn isolation_undoes_the_operations() {
+        let solutions = solve_x("2*x + 3 = 7", (None, None)).unwrap();
+        assert_eq!(solutions.method, SolveMethod::Isolation);
+        assert!(close(&values(&solutions), &[2.0]), "{}", solutions);
+
+        let solutions = solve_x("sqrt(x) = 3", (None, None)).unwrap();
+        assert_eq!(solutions.method, SolveMethod::Isolation);
+        assert!(close(&values(&solutions), &[9.0]), "{}", solutions);
+
+        // Both candidates of abs, in the order they are isolated
+        let solutions = solve_x("abs(x - 1) = 2", (None, None)).unwrap();
+        let mut found = values(&solutions);
+        found.sort_by(f64::total_cmp);
+        assert!(close(&found, &[-1.0, 3.0]), "{}", solutions);
+    }
+
+    #[test]
+    fn isolation_rejects_candidates_that_do_not_solve() {
+        // Squaring gives 4, but sqrt(4) is 2
+        let (_, ast) = lex_parse_program("sqrt(x) = -2").unwrap().split_result();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        assert!(solve_symbolic(&ast, "x", &mut evaluator).unwrap().is_none());
+        assert!(vars.get_stored("x").is_none());
+
+        assert!(matches!(
+            solve_x("sqrt(x) = -2", (None, None)),
+            Err(CompilerError::Root(RootFinderError::NoSymbolicSolution))
+        ));
+        let solutions = solve_x("sqrt(x) = -2", (Some(0.0), Some(10.0))).unwrap();
+        assert!(solutions.solutions.is_empty(), "{}", solutions);
+    }
+
+    #[test]
+    fn linear_equation_with_the_unknown_twice() {
+        let solutions = solve_x("3*x - 1 = x + 4", (None, None)).unwrap();
+        assert_eq!(solutions.method, SolveMethod::Linear);
+        assert!(close(&values(&solutions), &[2.5]), "{}", solutions);
+        assert_eq!(solutions.solutions[0].residual, 0.0);
+    }
+
+    #[test]
+    fn polynomial_has_real_and_complex_roots() {
+        let solutions = solve_x("x^2 = 2", (None, None)).unwrap();
+        assert_eq!(solutions.method, SolveMethod::Polynomial);
+        let sqrt2 = 2f64.sqrt();
+        assert!(
+            close(&values(&solutions), &[-sqrt2, sqrt2]),
+            "{}",
+            solutions
+        );
+
+        let solutions = solve_x("x^3 = -x", (None, None)).unwrap();
+        assert!(close(&values(&solutions), &[0.0]), "{}", solutions);
+        assert_eq!(
+            solutions.complex,
+            vec![Complex::new(0.0, 1.0), Complex::new(0.0, -1.0)]
+        );
+    }
+
+    #[test]
+    fn identity_and_contradiction() {
+        let solutions = solve_x("2*x - x = x", (None, None)).unwrap();
+        assert!(solutions.identity);
+        assert!(solutions.to_string().starts_with("Every value of x"));
+
+        let solutions = solve_x("x - x = 1", (None, None)).unwrap();
+        assert!(!solutions.identity);
+        assert!(solutions.solutions.is_empty());
+    }
+
+    #[test]
+    fn without_an_interval_the_root_finder_is_not_used() {
+        for input in ["sin(x) = 2", "x/x = 1", "abs(x) = -1", "cos(x) = x"] {
+            assert!(
+                matches!(
+                    solve_x(input, (None, None)),
+                    Err(CompilerError::Root(RootFinderError::NoSymbolicSolution))
+                ),
+                "{}",
+                input
+            );
+        }
+        let solutions = solve_x("x^3 + x = 1 + sin(x)", (Some(0.0), Some(2.0))).unwrap();
+        assert!(matches!(solutions.method, SolveMethod::Numeric(_)));
+        assert_eq!(solutions.solutions.len(), 1, "{}", solutions);
+    }
+
+    #[test]
+    fn unknown_is_the_variable_without_a_value() {
+        let mut vars = VariableManager::new();
+        let unknown_of =
+            |input: &str, vars: &VariableManager| unknown(&lex_parse_input(input).unwrap(), vars);
+
+        match unknown_of("a*y + b", &vars) {
+            Err(CompilerError::Root(RootFinderError::SeveralUnknowns(mut names))) => {
+                names.sort();
+                assert_eq!(names, ["a", "b", "y"]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        vars.set("a".to_string(), Num::Float(3.0));
+        vars.set("b".to_string(), Num::Float(1.0));
+        assert_eq!(unknown_of("a*y + b", &vars), Ok("y".to_string()));
+        // Every variable has a value: x if it occurs, otherwise it is not clear
+        vars.set("y".to_string(), Num::Float(2.0));
+        vars.set("x".to_string(), Num::Float(2.0));
+        assert_eq!(unknown_of("a*x + y", &vars), Ok("x".to_string()));
+        assert!(matches!(
+            unknown_of("a*y + b", &vars),
+            Err(CompilerError::Root(RootFinderError::NoUnknown))
+        ));
+        assert!(matches!(
+            unknown_of("5", &vars),
+            Err(CompilerError::Root(RootFinderError::NoUnknown))
+        ));
+    }
+
+    #[test]
+    fn roots_use_the_unknown() {
+        let mut vars = VariableManager::new();
+        vars.set("a".to_string(), Num::Float(2.0));
+        let unknown = |input: &str, vars: &VariableManager| {
+            roots_unknown(&lex_parse_input(input).unwrap(), vars)
+        };
+
+        assert_eq!(unknown("t^2 - a", &vars), Ok("t".to_string()));
+        assert_eq!(unknown("5", &vars), Ok("x".to_string()));
+        assert!(matches!(
+            unknown("x*y", &vars),
+            Err(CompilerError::Root(RootFinderError::SeveralUnknowns(_)))
+        ));
+    }
+}
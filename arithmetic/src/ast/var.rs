@@ -1,34 +1,37 @@
-use super::{get_and_parse_user_input, Num};
+use super::{CompilerError, Num, TextSpan, UserFunction, VariableResolver};
 use std::collections::HashMap;
 
+/// Stores the values of variables and the user defined functions
 pub struct VariableManager {
     variables: HashMap<String, Num>,
+    functions: HashMap<String, UserFunction>,
 }
 
 impl VariableManager {
     pub fn new() -> Self {
         Self {
             variables: HashMap::new(),
+            functions: HashMap::new(),
         }
     }
 
-    /// get value of a variable from the user.
-    pub fn get(&mut self, var_name: &str) -> Num {
+    /// get value of a variable. Unknown variables are looked up with the resolver and stored.
+    pub fn get(
+        &mut self,
+        var_name: &str,
+        span: &TextSpan,
+        resolver: &mut dyn VariableResolver,
+    ) -> Result<Num, CompilerError> {
         let normalized_name = var_name.to_lowercase();
 
         if let Some(value) = self.variables.get(&normalized_name) {
-            return value.clone();
+            return Ok(value.clone());
         }
 
-        let value = get_and_parse_user_input(var_name);
-        let num_value = if value.fract() == 0.0 {
-            Num::Integer(value as i64)
-        } else {
-            Num::Float(value)
-        };
+        let num_value = resolver.resolve(var_name, span)?;
 
         self.variables.insert(normalized_name, num_value.clone());
-        num_value
+        Ok(num_value)
     }
 
     /// set the value of a variable.
@@ -37,8 +40,43 @@ impl VariableManager {
         self.variables.insert(normalized_name, value);
     }
 
-    /// clear the variable and values stored
+    /// returns the stored value without asking the resolver
+    pub fn get_stored(&self, var_name: &str) -> Option<Num> {
+        self.variables.get(&var_name.to_lowercase()).cloned()
+    }
+
+    /// remove a variable and return its value
+    pub fn remove(&mut self, var_name: &str) -> Option<Num> {
+        self.variables.remove(&var_name.to_lowercase())
+    }
+
+    /// returns the stored variables sorted by name
+    pub fn entries(&self) -> Vec<(&String, &Num)> {
+        let mut entries: Vec<(&String, &Num)> = self.variables.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    /// define or replace a user function
+    pub fn define_function(&mut self, name: String, function: UserFunction) {
+        self.functions.insert(name.to_lowercase(), function);
+    }
+
+    /// returns the user function with the given name
+    pub fn get_function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(&name.to_lowercase())
+    }
+
+    /// returns the user functions sorted by name
+    pub fn function_entries(&self) -> Vec<(&String, &UserFunction)> {
+        let mut entries: Vec<(&String, &UserFunction)> = self.functions.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    /// clear the variables, functions and values stored
     pub fn clear(&mut self) {
         self.variables.clear();
+        self.functions.clear();
     }
 }
@@ -1,20 +1,82 @@
 #![allow(dead_code)]
 use super::{
-    root::RootFinder, ASTNode, CompilerError, Evaluator, FunctionPlotter, VariableManager,
+    polynomial::PolynomialRoots,
+    root::{RootConfig, RootFinder},
+    root_report::RootScan,
+    solve::{self, Solutions},
+    ASTNode, AngleMode, CompilerError, DomainMode, EvalSettings, EvaluationError, Evaluator,
+    FunctionPlotter, Program, VariableManager, VariableResolver,
 };
+use crate::vm::Bytecode;
+
 pub struct ASTWrapper {
     pub ast: ASTNode,
     pub vars: VariableManager,
+    pub resolver: Box<dyn VariableResolver>,
+    pub settings: EvalSettings,
+    /// Domain problems of the last evaluation in lenient mode
+    pub warnings: Vec<EvaluationError>,
 }
 
 impl ASTWrapper {
-    pub fn new(tree: ASTNode) -> Self {
+    /// The resolver decides what happens with variables that have no value: prompt, error, ...
+    pub fn new(tree: ASTNode, resolver: Box<dyn VariableResolver>) -> Self {
         Self {
             ast: tree,
             vars: VariableManager::new(),
+            resolver,
+            settings: EvalSettings::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Same as new but starts with already known variable values
+    pub fn with_vars(
+        tree: ASTNode,
+        vars: VariableManager,
+        resolver: Box<dyn VariableResolver>,
+    ) -> Self {
+        Self {
+            ast: tree,
+            vars,
+            resolver,
+            settings: EvalSettings::default(),
+            warnings: Vec::new(),
         }
     }
 
+    /// Runs the leading statements of the program so their values are stored.
+    /// The last statement becomes the AST of the wrapper.
+    pub fn from_program(
+        program: Program,
+        mut vars: VariableManager,
+        mut resolver: Box<dyn VariableResolver>,
+        settings: EvalSettings,
+    ) -> Result<Self, CompilerError> {
+        let (statements, tree) = program.split_result();
+
+        let mut evaluator = Evaluator::new(&mut vars, resolver.as_mut()).with_settings(settings);
+        for statement in statements.iter() {
+            evaluator.execute_statement(statement)?;
+        }
+        let warnings = std::mem::take(&mut evaluator.warnings);
+
+        let mut wrapper = Self::with_vars(tree, vars, resolver);
+        wrapper.settings = settings;
+        wrapper.warnings = warnings;
+        Ok(wrapper)
+    }
+
+    /// Sets the unit of the trigonometric functions for evaluation, roots and plots
+    pub fn set_angle_mode(&mut self, angle_mode: AngleMode) {
+        self.settings.angle_mode = angle_mode;
+    }
+
+    /// Strict mode fails on domain errors, lenient mode only records warnings
+    pub fn set_domain_mode(&mut self, domain_mode: DomainMode) {
+        self.settings.domain_mode = domain_mode;
+    }
+
     /// clear the Variable Manager values
     pub fn clear_variables(&mut self) {
         self.vars.clear();
@@ -25,6 +87,11 @@ impl ASTWrapper {
         self.ast.stringify("".to_string(), false)
     }
 
+    /// returns the expression as source text
+    pub fn ast_infix_string(&mut self) -> String {
+        self.ast.infix()
+    }
+
     /// returns ast tree postfix representation.
     pub fn ast_postfix_string(&mut self) -> String {
         self.ast.postfix()
@@ -32,42 +99,133 @@ impl ASTWrapper {
 
     /// evaluate with a certain value for x
     pub fn evaluate_with_x(&mut self, x: f64) -> Result<f64, CompilerError> {
-        let mut evaluator = Evaluator::new(&mut self.vars);
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
         evaluator.evaluate_with_x(&self.ast, x)
     }
 
     /// evaluate the ast tree using a stack
     pub fn eval_ast(&mut self) -> Result<f64, CompilerError> {
-        let mut evaluator = Evaluator::new(&mut self.vars);
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
 
-        match evaluator.evaluate(&self.ast) {
-            Ok(result) => Ok(result),
-            Err(e) => Err(e),
+        let result = evaluator.evaluate(&self.ast);
+        for warning in evaluator.warnings.drain(..) {
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
         }
+        result
+    }
+
+    /// Compiles the expression to bytecode. The stored variables become the defaults
+    /// of their slots.
+    pub fn bytecode(&self) -> Result<Bytecode, CompilerError> {
+        Ok(Bytecode::compile(&self.ast, &self.vars)?.with_values(&self.vars))
+    }
+
+    /// Returns the report of the roots found within an interval and the rejected poles.
+    /// config selects the method, tolerance, iteration limit and scan step. The variable
+    /// is the unknown that solve would choose.
+    pub fn roots_report(
+        &mut self,
+        a: Option<f64>,
+        b: Option<f64>,
+        config: RootConfig,
+    ) -> Result<RootScan, CompilerError> {
+        let var = solve::roots_unknown(&self.ast.inline_functions(&self.vars)?, &self.vars)?;
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+        let mut root_finder = RootFinder::new(&self.ast, &mut evaluator)
+            .with_config(config)
+            .with_variable(&var);
+
+        root_finder.find_roots(a, b)
+    }
+
+    /// All complex roots when the expression is a polynomial in its unknown, None otherwise
+    pub fn polynomial_roots(&mut self) -> Result<Option<PolynomialRoots>, CompilerError> {
+        let ast = self.ast.inline_functions(&self.vars)?;
+        let var = solve::roots_unknown(&ast, &self.vars)?;
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+
+        Ok(ast
+            .polynomial(&var, &mut evaluator)?
+            .filter(|polynomial| polynomial.degree() > 0)
+            .map(|polynomial| polynomial.roots()))
+    }
+
+    /// Solves the equation `ast = 0`, the result of lhs = rhs is lhs - rhs. Without var the
+    /// only variable that has no value is the unknown. The root finder is only used, in
+    /// the interval a, b, when the unknown can not be isolated.
+    pub fn solve(
+        &mut self,
+        var: Option<&str>,
+        a: Option<f64>,
+        b: Option<f64>,
+        config: RootConfig,
+    ) -> Result<Solutions, CompilerError> {
+        let ast = self.ast.inline_functions(&self.vars)?;
+        let var = match var {
+            Some(var) => var.to_lowercase(),
+            None => solve::unknown(&ast, &self.vars)?,
+        };
+
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+        solve::solve(&ast, &var, &mut evaluator, (a, b), config)
     }
 
-    /// Returns a string representation of the roots found within an interval
+    /// Returns a string representation of the roots found within an interval, with
+    /// their residual, kind and multiplicity and the rejected poles.
     pub fn roots_string(
         &mut self,
         a: Option<f64>,
         b: Option<f64>,
+        config: RootConfig,
     ) -> Result<String, CompilerError> {
-        let mut evaluator = Evaluator::new(&mut self.vars);
-        let mut root_finder = RootFinder::new(&self.ast, &mut evaluator);
+        Ok(self.roots_report(a, b, config)?.to_string())
+    }
 
-        let roots = root_finder.find_roots(a, b)?;
+    /// Derivative of the expression with respect to var. User functions are inlined
+    /// and the angle unit of the settings is used. The result is simplified.
+    pub fn derivative(&self, var: &str) -> Result<ASTNode, CompilerError> {
+        Ok(self
+            .ast
+            .inline_functions(&self.vars)?
+            .derivative_in(var, self.settings.angle_mode)?
+            .simplify_with(self.settings))
+    }
 
-        let roots_str = roots
-            .iter()
-            .map(|root| format!("{:.3}", root))
-            .collect::<Vec<String>>()
-            .join(", ");
-        Ok(roots_str)
+    /// Replaces the expression by its simplified form
+    pub fn simplify(&mut self) {
+        self.ast = self.ast.simplify_with(self.settings);
     }
 
-    /// plot the function within the given interval.
-    pub fn plot(&mut self, a: Option<f64>, b: Option<f64>) -> Result<(), CompilerError> {
-        let mut plotter = FunctionPlotter::new(&mut self.vars);
-        plotter.plot_function(&self.ast, a, b)
+    /// plot the function and its derivative with respect to var in one image
+    pub fn plot_with_derivative(
+        &mut self,
+        var: &str,
+        a: Option<f64>,
+        b: Option<f64>,
+        output: Option<&str>,
+    ) -> Result<(), CompilerError> {
+        let functions = [self.ast.clone(), self.derivative(var)?];
+        let mut plotter = FunctionPlotter::new(&mut self.vars, self.resolver.as_mut())
+            .with_settings(self.settings);
+        plotter.plot_functions(&functions, a, b, output)
+    }
+
+    /// plot the function within the given interval. output is the image path.
+    pub fn plot(
+        &mut self,
+        a: Option<f64>,
+        b: Option<f64>,
+        output: Option<&str>,
+    ) -> Result<(), CompilerError> {
+        let mut plotter = FunctionPlotter::new(&mut self.vars, self.resolver.as_mut())
+            .with_settings(self.settings);
+        plotter.plot_function(&self.ast, a, b, output)
     }
 }
@@ -0,0 +1,361 @@
+use crate::ast::angle::AngleMode;
+use std::f64::consts::PI;
+
+/// A built-in function. The lexer, the parser and the evaluator all use this table.
+pub struct Builtin {
+    pub name: &'static str,
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    pub apply: fn(&[f64], AngleMode) -> f64,
+    /// Returns the argument that is outside of the domain, if any
+    pub check: fn(&[f64], AngleMode) -> Option<f64>,
+}
+
+impl Builtin {
+    const fn new(
+        name: &'static str,
+        min_args: usize,
+        max_args: Option<usize>,
+        apply: fn(&[f64], AngleMode) -> f64,
+    ) -> Self {
+        Self {
+            name,
+            min_args,
+            max_args,
+            apply,
+            check: |_, _| None,
+        }
+    }
+
+    /// Adds a domain check to the function
+    const fn with_check(mut self, check: fn(&[f64], AngleMode) -> Option<f64>) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Function with exactly one argument
+    const fn unary(name: &'static str, apply: fn(&[f64], AngleMode) -> f64) -> Self {
+        Self::new(name, 1, Some(1), apply)
+    }
+
+    /// Function with exactly two arguments
+    const fn binary(name: &'static str, apply: fn(&[f64], AngleMode) -> f64) -> Self {
+        Self::new(name, 2, Some(2), apply)
+    }
+
+    /// Finds a built-in function by its (lower case) name
+    pub fn lookup(name: &str) -> Option<&'static Builtin> {
+        BUILTINS.iter().find(|builtin| builtin.name == name)
+    }
+
+    /// Checks the number of arguments of a call
+    pub fn accepts(&self, arg_count: usize) -> bool {
+        arg_count >= self.min_args && self.max_args.is_none_or(|max| arg_count <= max)
+    }
+
+    /// Human readable number of arguments: "1", "1 to 2", "at least 2"
+    pub fn expected_args(&self) -> String {
+        match self.max_args {
+            Some(max) if max == self.min_args => self.min_args.to_string(),
+            Some(max) => format!("{} to {}", self.min_args, max),
+            None => format!("at least {}", self.min_args),
+        }
+    }
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    // Trigonometric functions, the argument is an angle
+    Builtin::unary("sin", |a, m| m.angle_to_radians(a[0]).sin()),
+    Builtin::unary("cos", |a, m| m.angle_to_radians(a[0]).cos()),
+    Builtin::unary("tan", |a, m| m.angle_to_radians(a[0]).tan())
+        .with_check(|a, m| outside(a[0], is_zero(m.angle_to_radians(a[0]).cos()))),
+    Builtin::unary("cotan", |a, m| 1.0 / m.angle_to_radians(a[0]).tan())
+        .with_check(|a, m| outside(a[0], is_zero(m.angle_to_radians(a[0]).sin()))),
+    // Inverse trigonometric functions, the result is an angle
+    Builtin::unary("arcsin", |a, m| m.radians_to_angle(a[0].asin()))
+        .with_check(|a, _| outside(a[0], a[0].abs() > 1.0)),
+    Builtin::unary("arccos", |a, m| m.radians_to_angle(a[0].acos()))
+        .with_check(|a, _| outside(a[0], a[0].abs() > 1.0)),
+    Builtin::unary("arctan", |a, m| m.radians_to_angle(a[0].atan())),
+    Builtin::unary("arccotan", |a, m| {
+        m.radians_to_angle(PI / 2.0 - a[0].atan())
+    }),
+    Builtin::binary("atan2", |a, m| m.radians_to_angle(a[0].atan2(a[1]))),
+    // Hyperbolic functions
+    Builtin::unary("sinh", |a, _| a[0].sinh()),
+    Builtin::unary("cosh", |a, _| a[0].cosh()),
+    Builtin::unary("tanh", |a, _| a[0].tanh()),
+    Builtin::unary("arcsinh", |a, _| a[0].asinh()),
+    Builtin::unary("arccosh", |a, _| a[0].acosh()).with_check(|a, _| outside(a[0], a[0] < 1.0)),
+    Builtin::unary("arctanh", |a, _| a[0].atanh())
+        .with_check(|a, _| outside(a[0], a[0].abs() >= 1.0)),
+    // Logarithmic and exponential functions
+    Builtin::unary("ln", |a, _| a[0].ln()).with_check(|a, _| outside(a[0], a[0] <= 0.0)),
+    Builtin::new("log", 1, Some(2), |a, _| match a {
+        [x, base] => x.log(*base),
+        _ => a[0].log10(),
+    })
+    .with_check(|a, _| match a {
+        [x, _] if *x <= 0.0 => Some(*x),
+        [_, base] => outside(*base, *base <= 0.0 || *base == 1.0),
+        _ => outside(a[0], a[0] <= 0.0),
+    }),
+    Builtin::unary("log2", |a, _| a[0].log2()).with_check(|a, _| outside(a[0], a[0] <= 0.0)),
+    Builtin::unary("exp", |a, _| a[0].exp()),
+    // Powers and roots
+    Builtin::unary("sqrt", |a, _| a[0].sqrt()).with_check(|a, _| outside(a[0], a[0] < 0.0)),
+    Builtin::unary("sqr", |a, _| a[0] * a[0]),
+    Builtin::unary("cbrt", |a, _| a[0].cbrt()),
+    Builtin::binary("pow", |a, _| a[0].powf(a[1])).with_check(|a, _| power_check(a[0], a[1])),
+    Builtin::binary("root", |a, _| nth_root(a[0], a[1])).with_check(|a, _| match a {
+        [n, _] if *n == 0.0 => Some(*n),
+        [n, x] => outside(*x, *x < 0.0 && !is_odd_integer(*n)),
+        _ => None,
+    }),
+    Builtin::binary("hypot", |a, _| a[0].hypot(a[1])),
+    // Rounding
+    Builtin::unary("floor", |a, _| a[0].floor()),
+    Builtin::unary("ceil", |a, _| a[0].ceil()),
+    Builtin::new("round", 1, Some(2), |a, _| match a {
+        [x, digits] => {
+            let factor = 10f64.powi(*digits as i32);
+            (x * factor).round() / factor
+        }
+        _ => a[0].round(),
+    }),
+    Builtin::unary("trunc", |a, _| a[0].trunc()),
+    Builtin::unary("abs", |a, _| a[0].abs()),
+    Builtin::unary("sign", |a, _| sign(a[0])),
+    // Several arguments
+    Builtin::new("min", 2, None, |a, _| {
+        a.iter().copied().fold(f64::INFINITY, f64::min)
+    }),
+    Builtin::new("max", 2, None, |a, _| {
+        a.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+    }),
+    // Special functions
+    Builtin::unary("factorial", |a, _| factorial(a[0]))
+        .with_check(|a, _| outside(a[0], a[0] < 0.0 && a[0].fract() == 0.0)),
+    Builtin::unary("gamma", |a, _| gamma(a[0]))
+        .with_check(|a, _| outside(a[0], a[0] <= 0.0 && a[0].fract() == 0.0)),
+    // ψ is the derivative of ln Γ. It is a built-in so that derivatives of gamma and
+    // factorial can be evaluated, compiled and printed as source that parses again.
+    Builtin::unary("digamma", |a, _| digamma(a[0]))
+        .with_check(|a, _| outside(a[0], a[0] <= 0.0 && a[0].fract() == 0.0)),
+];
+
+/// Domain of x ^ y: no 0 ^ negative, no negative ^ fraction. The base is the offending argument.
+pub fn power_check(x: f64, y: f64) -> Option<f64> {
+    outside(
+        x,
+        (x == 0.0 && y < 0.0) || (x < 0.0 && y.is_finite() && y.fract() != 0.0),
+    )
+}
+
+/// Some(arg) when the condition marks it as outside of the domain
+fn outside(arg: f64, condition: bool) -> Option<f64> {
+    condition.then_some(arg)
+}
+
+/// Poles of tan and cotan are not hit exactly because of the rounding of pi
+fn is_zero(x: f64) -> bool {
+    x.abs() < 1e-12
+}
+
+fn is_odd_integer(n: f64) -> bool {
+    n.fract() == 0.0 && n % 2.0 != 0.0
+}
+
+/// n-th root. Odd roots of negative numbers are real: root(3, -8) = -2
+fn nth_root(n: f64, x: f64) -> f64 {
+    if x < 0.0 && is_odd_integer(n) {
+        -(-x).powf(1.0 / n)
+    } else {
+        x.powf(1.0 / n)
+    }
+}
+
+/// -1, 0 or 1
+fn sign(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x.signum()
+    }
+}
+
+/// n! for integers, gamma(n + 1) otherwise
+fn factorial(n: f64) -> f64 {
+    if n.fract() != 0.0 {
+        return gamma(n + 1.0);
+    }
+    if n < 0.0 {
+        return f64::NAN;
+    }
+
+    (2..=(n.min(171.0) as u64)).fold(1.0, |acc, i| acc * i as f64)
+}
+
+/// Gamma function: Lanczos approximation with the reflection formula for x < 0.5
+fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    // Poles at 0, -1, -2, ...
+    if x <= 0.0 && x.fract() == 0.0 {
+        return f64::NAN;
+    }
+
+    if x < 0.5 {
+        return PI / ((PI * x).sin() * gamma(1.0 - x));
+    }
+
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64));
+
+    (2.0 * PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * series
+}
+
+/// Digamma function ψ(x) = Γ'(x)/Γ(x): recurrence up to x >= 6, then the asymptotic series
+fn digamma(x: f64) -> f64 {
+    // Poles at 0, -1, -2, ...
+    if x <= 0.0 && x.fract() == 0.0 {
+        return f64::NAN;
+    }
+
+    // Reflection: ψ(1 - x) - ψ(x) = π·cot(πx)
+    if x < 0.0 {
+        return digamma(1.0 - x) - PI / (PI * x).tan();
+    }
+
+    let mut x = x;
+    let mut result = 0.0;
+    while x < 6.0 {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+
+    let inv2 = 1.0 / (x * x);
+    result + x.ln()
+        - 0.5 / x
+        - inv2 * (1.0 / 12.0 - inv2 * (1.0 / 120.0 - inv2 * (1.0 / 252.0 - inv2 / 240.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Euler–Mascheroni constant, ψ(1) = -γ
+    const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+
+    // The asymptotic series from x >= 6 is good to about 1e-10
+    #[test]
+    fn digamma_known_values() {
+        assert!((digamma(1.0) + EULER_GAMMA).abs() < 1e-9);
+        assert!((digamma(0.5) + EULER_GAMMA + 2.0 * 2f64.ln()).abs() < 1e-9);
+        // Reflection for negative arguments
+        assert!((digamma(-0.5) - 0.036_489_973_978_576_52).abs() < 1e-9);
+    }
+
+    #[test]
+    fn digamma_recurrence_and_poles() {
+        for x in [0.3, 1.7, 4.2, 11.5] {
+            assert!((digamma(x + 1.0) - digamma(x) - 1.0 / x).abs() < 1e-9);
+        }
+        assert!(digamma(0.0).is_nan());
+        assert!(digamma(-3.0).is_nan());
+    }
+
+    #[test]
+    fn digamma_is_the_logarithmic_derivative_of_gamma() {
+        let h = 1e-6;
+        for x in [0.7, 2.5, 6.3] {
+            let slope = (gamma(x + h).ln() - gamma(x - h).ln()) / (2.0 * h);
+            assert!((digamma(x) - slope).abs() < 1e-6);
+        }
+    }
+
+    fn call(name: &str, args: &[f64]) -> f64 {
+        let builtin = Builtin::lookup(name).unwrap();
+        assert!(builtin.accepts(args.len()), "{}", name);
+        assert_eq!((builtin.check)(args, AngleMode::Radians), None, "{}", name);
+        (builtin.apply)(args, AngleMode::Radians)
+    }
+
+    #[test]
+    fn names_are_unique_and_lower_case() {
+        for (i, builtin) in BUILTINS.iter().enumerate() {
+            assert_eq!(builtin.name, builtin.name.to_lowercase());
+            assert!(BUILTINS[i + 1..]
+                .iter()
+                .all(|other| other.name != builtin.name));
+            assert_eq!(Builtin::lookup(builtin.name).unwrap().name, builtin.name);
+        }
+        assert!(Builtin::lookup("SIN").is_none());
+        assert!(Builtin::lookup("foo").is_none());
+    }
+
+    #[test]
+    fn number_of_arguments() {
+        let sin = Builtin::lookup("sin").unwrap();
+        assert!(!sin.accepts(0) && sin.accepts(1) && !sin.accepts(2));
+        assert_eq!(sin.expected_args(), "1");
+
+        let log = Builtin::lookup("log").unwrap();
+        assert!(log.accepts(1) && log.accepts(2) && !log.accepts(3));
+        assert_eq!(log.expected_args(), "1 to 2");
+
+        let max = Builtin::lookup("max").unwrap();
+        assert!(!max.accepts(1) && max.accepts(2) && max.accepts(10));
+        assert_eq!(max.expected_args(), "at least 2");
+    }
+
+    #[test]
+    fn sample_values() {
+        assert_eq!(call("log", &[1000.0]), 3.0);
+        assert_eq!(call("log", &[8.0, 2.0]), 3.0);
+        assert_eq!(call("root", &[3.0, -8.0]), -2.0);
+        assert_eq!(call("hypot", &[3.0, 4.0]), 5.0);
+        assert_eq!(call("round", &[1.23456, 2.0]), 1.23);
+        assert_eq!(call("sign", &[-0.0]), 0.0);
+        assert_eq!(call("min", &[3.0, -1.0, 2.0]), -1.0);
+        assert_eq!(call("factorial", &[5.0]), 120.0);
+        assert!((call("gamma", &[0.5]) - PI.sqrt()).abs() < 1e-12);
+        assert!((call("factorial", &[0.5]) - PI.sqrt() / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn domain_checks() {
+        let check = |name: &str, args: &[f64]| {
+            (Builtin::lookup(name).unwrap().check)(args, AngleMode::Degrees)
+        };
+        assert_eq!(check("sqrt", &[-1.0]), Some(-1.0));
+        assert_eq!(check("ln", &[0.0]), Some(0.0));
+        assert_eq!(check("log", &[8.0, 1.0]), Some(1.0));
+        assert_eq!(check("log", &[-8.0, 2.0]), Some(-8.0));
+        assert_eq!(check("tan", &[90.0]), Some(90.0));
+        assert_eq!(check("tan", &[45.0]), None);
+        assert_eq!(check("root", &[2.0, -8.0]), Some(-8.0));
+        assert_eq!(check("root", &[3.0, -8.0]), None);
+        assert_eq!(check("gamma", &[-2.0]), Some(-2.0));
+        assert_eq!(check("gamma", &[-2.5]), None);
+        assert_eq!(power_check(0.0, -1.0), Some(0.0));
+        assert_eq!(power_check(-8.0, 0.5), Some(-8.0));
+        assert_eq!(power_check(-8.0, 3.0), None);
+    }
+}
@@ -0,0 +1,392 @@
+use super::{
+    num_from_f64, AngleMode, DomainMode, EvalSettings, Formats, Language, Num, RootConfig,
+    RootMethod,
+};
+use crate::codegen::is_valid_name;
+
+pub const USAGE: &str = "Usage: arithmetic <command> [expression] [options]
+
+Commands:
+  lex        print the token stream
+  parse      print the AST tree
+  postfix    print the postfix notation
+  infix      print the expression with minimal parentheses
+  eval       evaluate the expression
+  derive     print the derivative of the expression
+  simplify   fold constants and simplify the expression
+  compile    compile to bytecode, written to --output or printed as a listing
+  disasm     print the listing of a bytecode file
+  exec       run a bytecode file
+  codegen    print the expression as a C or Rust function
+  ir         print the IR of the expression and the code after every optimization pass
+  asm        print the AST, the optimized IR and x86-64 assembly of the expression
+  roots      find the roots of f(x) in an interval, all complex roots of a polynomial
+             when no interval is given
+  solve      solve an equation lhs = rhs for one variable
+  plot       plot f(x) in an interval
+  repl       start an interactive session
+  demo       run the demo on src/inputs/input.txt and src/inputs/plot.txt
+  help       print this message
+
+The expression is read from the argument, from --file or from stdin.
+disasm and exec take the path of a bytecode file instead.
+
+Options:
+  -f, --file <path>        read the expression from a file
+  -v, --var <name=value>   set a variable, can be repeated
+  -i, --interval <a,b>     interval used by roots, solve and plot
+  -o, --output <path>      image path used by plot, bytecode path used by compile,
+                           source path used by codegen and asm
+  -d, --derivative         plot also draws the derivative
+  -w, --wrt <name>         variable of the derivative, x by default
+      --latex              parse, postfix, infix, derive and simplify also print LaTeX
+      --mathml             parse, postfix, infix, derive and simplify also print MathML
+      --json               lex, parse and roots print JSON
+      --dot                parse prints a Graphviz graph
+      --from-json          the input is a program in the JSON of parse --json
+  -a, --angle <unit>       unit of the trigonometric functions: deg (default), rad, grad
+  -u, --unknown <name>     variable solve solves for, by default the one without a value
+  -m, --method <name>      root finding method: hybrid (default), bisection, newton,
+                           secant, brent, illinois
+      --tolerance <t>      roots stop when the step is below t, 1e-9 by default
+      --max-iterations <n> iteration limit of the root finding method, 10000 by default
+      --step <s>           distance between the samples of the root scan, 0.01 by default
+      --strict             fail on domain errors such as sqrt(-1) instead of warning
+  -l, --lang <language>    language of codegen: c (default), rust
+  -n, --name <name>        name of the function written by codegen and asm, f by default
+      --no-prompt          fail on unknown variables instead of asking for them
+      --no-color           print diagnostics without colours";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Lex,
+    Parse,
+    Postfix,
+    Infix,
+    Eval,
+    Derive,
+    Simplify,
+    Compile,
+    Disasm,
+    Exec,
+    Codegen,
+    Ir,
+    Asm,
+    Roots,
+    Solve,
+    Plot,
+    Repl,
+    Demo,
+    Help,
+}
+
+/// Parsed command line arguments
+#[derive(Debug)]
+pub struct CliOptions {
+    pub command: Command,
+    pub expression: Option<String>,
+    pub file: Option<String>,
+    pub vars: Vec<(String, Num)>,
+    pub interval: Option<(f64, f64)>,
+    pub output: Option<String>,
+    pub derivative: bool,
+    pub wrt: String,
+    pub lang: Language,
+    pub name: String,
+    pub unknown: Option<String>,
+    pub roots: RootConfig,
+    pub settings: EvalSettings,
+    pub formats: Formats,
+    pub from_json: bool,
+    pub no_prompt: bool,
+    pub no_color: bool,
+}
+
+impl CliOptions {
+    /// Parses the arguments without the program name
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let command = match args.first().map(|s| s.as_str()) {
+            Some("lex") => Command::Lex,
+            Some("parse") => Command::Parse,
+            Some("postfix") => Command::Postfix,
+            Some("infix") => Command::Infix,
+            Some("eval") => Command::Eval,
+            Some("derive") => Command::Derive,
+            Some("simplify") => Command::Simplify,
+            Some("compile") => Command::Compile,
+            Some("disasm") => Command::Disasm,
+            Some("exec") => Command::Exec,
+            Some("codegen") => Command::Codegen,
+            Some("ir") => Command::Ir,
+            Some("asm") => Command::Asm,
+            Some("roots") => Command::Roots,
+            Some("solve") => Command::Solve,
+            Some("plot") => Command::Plot,
+            Some("repl") => Command::Repl,
+            Some("demo") => Command::Demo,
+            Some("help") | Some("-h") | Some("--help") | None => Command::Help,
+            Some(other) => return Err(format!("Unknown command '{}'.", other)),
+        };
+
+        let mut options = Self {
+            command,
+            expression: None,
+            file: None,
+            vars: Vec::new(),
+            interval: None,
+            output: None,
+            derivative: false,
+            wrt: "x".to_string(),
+            lang: Language::default(),
+            name: "f".to_string(),
+            unknown: None,
+            roots: RootConfig::default(),
+            settings: EvalSettings::default(),
+            formats: Formats::default(),
+            from_json: false,
+            no_prompt: false,
+            no_color: false,
+        };
+
+        let mut rest = args.iter().skip(1);
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "-f" | "--file" => options.file = Some(Self::value(arg, rest.next())?),
+                "-v" | "--var" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.vars.push(Self::parse_var(&value)?);
+                }
+                "-i" | "--interval" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.interval = Some(Self::parse_interval(&value)?);
+                }
+                "-o" | "--output" => options.output = Some(Self::value(arg, rest.next())?),
+                "-d" | "--derivative" => options.derivative = true,
+                "-w" | "--wrt" => options.wrt = Self::value(arg, rest.next())?.to_lowercase(),
+                "-a" | "--angle" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.settings.angle_mode = AngleMode::parse(&value)
+                        .ok_or_else(|| format!("Invalid angle unit '{}'.", value))?;
+                }
+                "-l" | "--lang" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.lang = Language::parse(&value)
+                        .ok_or_else(|| format!("Invalid language '{}'.", value))?;
+                }
+                "-u" | "--unknown" => options.unknown = Some(Self::value(arg, rest.next())?),
+                "-m" | "--method" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.roots.method = RootMethod::parse(&value)
+                        .ok_or_else(|| format!("Invalid root finding method '{}'.", value))?;
+                }
+                "--tolerance" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.roots.tolerance = Self::parse_positive(arg, &value)?;
+                }
+                "--step" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.roots.step_size = Self::parse_positive(arg, &value)?;
+                }
+                "--max-iterations" => {
+                    let value = Self::value(arg, rest.next())?;
+                    options.roots.max_iterations =
+                        value
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|&n| n > 0)
+                            .ok_or_else(|| format!("Invalid value '{}' for '{}'.", value, arg))?;
+                }
+                "-n" | "--name" => options.name = Self::value(arg, rest.next())?,
+                "--strict" => options.settings.domain_mode = DomainMode::Strict,
+                "--latex" => options.formats.latex = true,
+                "--mathml" => options.formats.mathml = true,
+                "--json" => options.formats.json = true,
+                "--dot" => options.formats.dot = true,
+                "--from-json" => options.from_json = true,
+                "--no-prompt" => options.no_prompt = true,
+                "--no-color" => options.no_color = true,
+                _ => {
+                    if options.expression.is_some() {
+                        return Err(format!("Unexpected argument '{}'.", arg));
+                    }
+                    options.expression = Some(arg.clone());
+                }
+            }
+        }
+
+        if options.expression.is_some() && options.file.is_some() {
+            return Err("Give either an expression or --file, not both.".to_string());
+        }
+        if !is_valid_name(&options.name, options.lang) {
+            return Err(format!(
+                "Invalid function name '{}' for {}.",
+                options.name, options.lang
+            ));
+        }
+
+        Ok(options)
+    }
+
+    /// Returns the value that follows a flag
+    fn value(flag: &str, value: Option<&String>) -> Result<String, String> {
+        value
+            .cloned()
+            .ok_or_else(|| format!("Missing value for '{}'.", flag))
+    }
+
+    /// x=3 -> ("x", 3)
+    fn parse_var(value: &str) -> Result<(String, Num), String> {
+        let (name, number) = value
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid variable '{}', expected name=value.", value))?;
+
+        let number = number
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid value for variable '{}'.", name))?;
+
+        Ok((name.trim().to_string(), num_from_f64(number)))
+    }
+
+    /// A finite number greater than 0
+    fn parse_positive(flag: &str, value: &str) -> Result<f64, String> {
+        value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite() && *number > 0.0)
+            .ok_or_else(|| format!("Invalid value '{}' for '{}'.", value, flag))
+    }
+
+    /// -10,10 -> (-10, 10)
+    fn parse_interval(value: &str) -> Result<(f64, f64), String> {
+        let invalid = || format!("Invalid interval '{}', expected a,b.", value);
+
+        let (a, b) = value.split_once(',').ok_or_else(invalid)?;
+        let a = a.trim().parse::<f64>().map_err(|_| invalid())?;
+        let b = b.trim().parse::<f64>().map_err(|_| invalid())?;
+
+        Ok((a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        CliOptions::parse(&args)
+    }
+
+    #[test]
+    fn var_is_a_name_and_a_number() {
+        assert_eq!(
+            CliOptions::parse_var("x=3"),
+            Ok(("x".to_string(), Num::Integer(3)))
+        );
+        assert_eq!(
+            CliOptions::parse_var(" rate = 0.5 "),
+            Ok(("rate".to_string(), Num::Float(0.5)))
+        );
+        assert_eq!(
+            CliOptions::parse_var("big=1e20"),
+            Ok(("big".to_string(), Num::Float(1e20)))
+        );
+        assert!(CliOptions::parse_var("x").is_err());
+        assert!(CliOptions::parse_var("x=fast").is_err());
+        assert!(CliOptions::parse_var("x=").is_err());
+    }
+
+    #[test]
+    fn vars_can_be_repeated() {
+        let options = parse(&["eval", "x + y", "-v", "x=1", "--var", "y=-2.5"]).unwrap();
+        assert_eq!(options.command, Command::Eval);
+        assert_eq!(options.expression.as_deref(), Some("x + y"));
+        assert_eq!(
+            options.vars,
+            vec![
+                ("x".to_string(), Num::Integer(1)),
+                ("y".to_string(), Num::Float(-2.5))
+            ]
+        );
+    }
+
+    #[test]
+    fn interval_and_root_options() {
+        let options = parse(&[
+            "roots",
+            "x^2 - 2",
+            "-i",
+            "-3, 3",
+            "-m",
+            "brent",
+            "--tolerance",
+            "1e-6",
+            "--step",
+            "0.5",
+            "--max-iterations",
+            "20",
+        ])
+        .unwrap();
+        assert_eq!(options.interval, Some((-3.0, 3.0)));
+        assert_eq!(options.roots.method, RootMethod::Brent);
+        assert_eq!(options.roots.tolerance, 1e-6);
+        assert_eq!(options.roots.step_size, 0.5);
+        assert_eq!(options.roots.max_iterations, 20);
+
+        assert!(parse(&["roots", "x", "-i", "3"]).is_err());
+        assert!(parse(&["roots", "x", "--step", "0"]).is_err());
+        assert!(parse(&["roots", "x", "--tolerance", "inf"]).is_err());
+        assert!(parse(&["roots", "x", "--max-iterations", "0"]).is_err());
+        assert!(parse(&["roots", "x", "-m", "guess"]).is_err());
+    }
+
+    #[test]
+    fn defaults_and_switches() {
+        let options = parse(&["eval"]).unwrap();
+        assert_eq!(options.expression, None);
+        assert_eq!(options.wrt, "x");
+        assert_eq!(options.name, "f");
+        assert_eq!(options.settings, EvalSettings::default());
+        assert!(!options.no_prompt && !options.no_color);
+
+        let options = parse(&[
+            "eval",
+            "sin(x)",
+            "-a",
+            "rad",
+            "--strict",
+            "--no-prompt",
+            "--no-color",
+            "-w",
+            "T",
+        ])
+        .unwrap();
+        assert_eq!(options.settings.angle_mode, AngleMode::Radians);
+        assert_eq!(options.settings.domain_mode, DomainMode::Strict);
+        assert!(options.no_prompt && options.no_color);
+        assert_eq!(options.wrt, "t");
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        assert_eq!(
+            parse(&["frobnicate"]).unwrap_err(),
+            "Unknown command 'frobnicate'."
+        );
+        assert_eq!(
+            parse(&["eval", "--var"]).unwrap_err(),
+            "Missing value for '--var'."
+        );
+        assert_eq!(
+            parse(&["eval", "1", "2"]).unwrap_err(),
+            "Unexpected argument '2'."
+        );
+        assert!(parse(&["eval", "1", "-f", "input.txt"]).is_err());
+        assert!(parse(&["eval", "1", "-a", "turns"]).is_err());
+        assert!(parse(&["codegen", "1", "-n", "fn", "-l", "rust"]).is_err());
+        assert_eq!(parse(&[]).unwrap().command, Command::Help);
+        assert_eq!(parse(&["--help"]).unwrap().command, Command::Help);
+    }
+}
@@ -0,0 +1,285 @@
+use super::{
+    args::{CliOptions, Command, USAGE},
+    repl::Repl,
+    ChainResolver, EnvResolver, Program, PromptResolver, VariableManager, VariableResolver,
+};
+use crate::errors::diagnostic::color_enabled;
+use crate::utils::print::{
+    print_assembly, print_ast, print_bytecode, print_codegen, print_derivative, print_disassembly,
+    print_error, print_evaluation, print_execution, print_infix, print_ir, print_lexer,
+    print_lexer_json, print_plot, print_postfix, print_roots, print_simplified, print_solve,
+};
+use crate::utils::ultimate::{ultimate_ast_postfix_eval, ultimate_root_plot};
+use crate::vm::Bytecode;
+use std::fs;
+use std::io::{self, Read};
+
+/// Exit code for invalid arguments or unreadable input
+const USAGE_EXIT_CODE: i32 = 2;
+
+/// Runs the command line interface and returns the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let options = match CliOptions::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return USAGE_EXIT_CODE;
+        }
+    };
+
+    // NO_COLOR and redirected output also turn the colours off
+    let color = !options.no_color && color_enabled();
+
+    match options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Command::Demo => return run_demo(color),
+        Command::Disasm | Command::Exec => return run_bytecode(&options, color),
+        Command::Repl => {
+            let mut repl = Repl::new(build_vars(&options), build_resolver(!options.no_prompt))
+                .with_settings(options.settings)
+                .with_color(color);
+            repl.run();
+            return 0;
+        }
+        _ => {}
+    }
+
+    let (input, from_stdin) = match read_input(&options) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("{}", message);
+            return USAGE_EXIT_CODE;
+        }
+    };
+
+    // A JSON tree is turned back into source text, diagnostics point into that text
+    let input = match options.from_json {
+        true => match Program::from_json(&input) {
+            Ok(program) => program.infix(),
+            Err(message) => {
+                eprintln!("Invalid JSON input: {}", message);
+                return USAGE_EXIT_CODE;
+            }
+        },
+        false => input,
+    };
+
+    // stdin can not be used for prompting when the expression came from it
+    let resolver = build_resolver(!options.no_prompt && !from_stdin);
+    let vars = build_vars(&options);
+    let settings = options.settings;
+    let formats = options.formats;
+    let interval = match options.interval {
+        Some((a, b)) => (Some(a), Some(b)),
+        None => (None, None),
+    };
+
+    let result = match options.command {
+        Command::Lex if formats.json => print_lexer_json(&input),
+        Command::Lex => print_lexer(&input),
+        Command::Parse => print_ast(&input, formats),
+        Command::Postfix => print_postfix(&input, formats),
+        Command::Infix => print_infix(&input, formats),
+        Command::Eval => print_evaluation(&input, vars, resolver, settings, color),
+        Command::Derive => {
+            print_derivative(&input, vars, resolver, settings, &options.wrt, formats)
+        }
+        Command::Simplify => print_simplified(&input, vars, resolver, settings, formats),
+        Command::Compile => {
+            print_bytecode(&input, vars, resolver, settings, options.output.as_deref())
+        }
+        Command::Codegen => print_codegen(
+            &input,
+            vars,
+            resolver,
+            settings,
+            (options.lang, options.name.as_str()),
+            options.output.as_deref(),
+        ),
+        Command::Ir => print_ir(&input, vars, resolver, settings),
+        Command::Asm => print_assembly(
+            &input,
+            vars,
+            resolver,
+            settings,
+            &options.name,
+            options.output.as_deref(),
+        ),
+        Command::Roots => print_roots(
+            &input,
+            vars,
+            resolver,
+            settings,
+            interval,
+            options.roots,
+            options.formats.json,
+        ),
+        Command::Solve => print_solve(
+            &input,
+            vars,
+            resolver,
+            settings,
+            options.unknown.as_deref(),
+            interval,
+            options.roots,
+        ),
+        Command::Plot => print_plot(
+            &input,
+            vars,
+            resolver,
+            settings,
+            interval,
+            options.output.as_deref(),
+            options.derivative.then_some(options.wrt.as_str()),
+        ),
+        Command::Demo | Command::Help | Command::Repl | Command::Disasm | Command::Exec => {
+            unreachable!()
+        }
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            print_error(&e, &input, color);
+            e.exit_code()
+        }
+    }
+}
+
+/// disasm and exec read the bytecode file written by compile. Errors point into
+/// the source stored in the file.
+fn run_bytecode(options: &CliOptions, color: bool) -> i32 {
+    let Some(path) = options.expression.as_ref().or(options.file.as_ref()) else {
+        eprintln!("Missing bytecode file.\n\n{}", USAGE);
+        return USAGE_EXIT_CODE;
+    };
+
+    let bytecode = match Bytecode::load(path) {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            print_error(&e, "", color);
+            return e.exit_code();
+        }
+    };
+
+    let result = match options.command {
+        Command::Disasm => print_disassembly(&bytecode),
+        _ => print_execution(
+            &bytecode,
+            build_vars(options),
+            build_resolver(!options.no_prompt),
+            options.settings,
+            color,
+        ),
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            print_error(&e, bytecode.source(), color);
+            e.exit_code()
+        }
+    }
+}
+
+/// Returns the source text and whether it was read from stdin
+fn read_input(options: &CliOptions) -> Result<(String, bool), String> {
+    if let Some(expression) = &options.expression {
+        return Ok((expression.clone(), false));
+    }
+
+    if let Some(path) = &options.file {
+        return fs::read_to_string(path)
+            .map(|input| (input, false))
+            .map_err(|e| format!("Unable to read '{}': {}", path, e));
+    }
+
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| format!("Unable to read from stdin: {}", e))?;
+    Ok((input, true))
+}
+
+/// Variables are looked up in ARITH_<NAME> environment variables, then prompted for if allowed
+fn build_resolver(prompt: bool) -> Box<dyn VariableResolver> {
+    let mut resolvers: Vec<Box<dyn VariableResolver>> = vec![Box::new(EnvResolver::new("ARITH_"))];
+
+    if prompt {
+        resolvers.push(Box::new(PromptResolver));
+    }
+
+    Box::new(ChainResolver::new(resolvers))
+}
+
+/// Stores the --var values
+fn build_vars(options: &CliOptions) -> VariableManager {
+    let mut vars = VariableManager::new();
+
+    for (name, value) in options.vars.iter() {
+        vars.set(name.clone(), value.clone());
+    }
+
+    vars
+}
+
+/// The original demo: both pipelines on the sample input files
+fn run_demo(color: bool) -> i32 {
+    let input = match fs::read_to_string("src/inputs/input.txt") {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Unable to read from file: {}", e);
+            return USAGE_EXIT_CODE;
+        }
+    };
+    let input2 = match fs::read_to_string("src/inputs/plot.txt") {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Unable to read from file: {}", e);
+            return USAGE_EXIT_CODE;
+        }
+    };
+
+    println!("###################### Part 1: Lexer, Parser(AST) , Postfix , Evaluation ######################\n");
+    ultimate_ast_postfix_eval(&input, color);
+    println!("\n\n ###################### Part 2: Roots and Plot ######################\n");
+    ultimate_root_plot(&input2, color);
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exit_code(args: &[&str]) -> i32 {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        run(&args)
+    }
+
+    #[test]
+    fn exit_code_tells_the_failing_stage() {
+        assert_eq!(exit_code(&["help"]), 0);
+        assert_eq!(
+            exit_code(&["eval", "x + 1", "--var", "x=2", "--no-prompt"]),
+            0
+        );
+        assert_eq!(exit_code(&["frobnicate"]), USAGE_EXIT_CODE);
+        assert_eq!(exit_code(&["eval", "1", "-i", "2"]), USAGE_EXIT_CODE);
+        assert_eq!(
+            exit_code(&["eval", "-f", "no/such/file.txt"]),
+            USAGE_EXIT_CODE
+        );
+        assert_eq!(exit_code(&["eval", "1 $ 2", "--no-prompt"]), 3);
+        assert_eq!(exit_code(&["eval", "(1 + 2", "--no-prompt"]), 4);
+        assert_eq!(exit_code(&["eval", "undefined_name + 1", "--no-prompt"]), 5);
+        assert_eq!(exit_code(&["eval", "2 = 3", "--no-prompt"]), 5);
+        assert_eq!(exit_code(&["solve", "2*x = 3", "--no-prompt"]), 0);
+        assert_eq!(exit_code(&["roots", "x/x", "--no-prompt"]), 6);
+        assert_eq!(exit_code(&["exec", "no/such/file.bc"]), 8);
+        assert_eq!(exit_code(&["asm", "gamma(x)", "--no-prompt"]), 9);
+    }
+}
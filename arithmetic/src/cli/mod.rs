@@ -0,0 +1,15 @@
+pub mod args;
+pub mod commands;
+pub mod repl;
+
+use crate::ast::angle::AngleMode;
+use crate::ast::program::Program;
+use crate::ast::resolver::{
+    num_from_f64, ChainResolver, EnvResolver, PromptResolver, VariableResolver,
+};
+use crate::ast::root::{RootConfig, RootMethod};
+use crate::ast::settings::{DomainMode, EvalSettings};
+use crate::ast::var::VariableManager;
+use crate::codegen::Language;
+use crate::lexer::token::Num;
+use crate::utils::print::Formats;
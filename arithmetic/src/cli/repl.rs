@@ -0,0 +1,521 @@
+use super::{
+    AngleMode, DomainMode, EvalSettings, Num, RootConfig, RootMethod, VariableManager,
+    VariableResolver,
+};
+use crate::ast::{
+    eval::Evaluator,
+    plot::FunctionPlotter,
+    program::{difference, Statement},
+    root::RootFinder,
+    solve, ASTNode,
+};
+use crate::errors::CompilerError;
+use crate::ir::Function;
+use crate::utils::print::{
+    lex_parse_input, lex_parse_program, print_error, print_lexer, print_warnings,
+};
+use crate::vm::Bytecode;
+use std::io::{self, Write};
+
+pub const REPL_HELP: &str = "Enter an expression to evaluate it. It becomes the current expression.
+Assignments such as `r := 2 * pi` or `f(x) := x^2` are kept for the following lines.
+
+Commands:
+  :ast [expr]       print the AST tree of expr or the current expression
+  :postfix [expr]   print the postfix notation of expr or the current expression
+  :infix [expr]     print expr or the current expression with minimal parentheses
+  :latex [expr]     print expr or the current expression as LaTeX
+  :mathml [expr]    print expr or the current expression as MathML
+  :tokens [expr]    print the token stream of expr or the current expression
+  :bytecode [expr]  print the compiled bytecode of expr or the current expression
+  :ir [expr]        print the IR of expr or the current expression and its optimization
+  :derive [var]     replace the current expression by its derivative (x by default)
+  :simplify         replace the current expression by its simplified form
+  :roots [a b]      roots of the current expression in [a, b], all complex
+                    roots of a polynomial without an interval
+  :solve [var] [a b] solve the current equation, or expression = 0, for var. The
+                    root finder searches [a, b] when var can not be isolated
+  :plot a b         plot the current expression in [a, b]
+  :angle [unit]     show or set the angle unit: deg, rad, grad
+  :mode [mode]      show or set the domain mode: strict, lenient
+  :method [name]    show or set the root finding method: hybrid, bisection, newton,
+                    secant, brent, illinois
+  :vars             list the stored variables and functions
+  :clear            remove all stored variables and functions
+  :history          list the previous lines
+  :help             print this message
+  :quit             leave the repl";
+
+/// Interactive session: variables and the current expression live across lines
+pub struct Repl {
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    source: Option<String>,
+    ast: Option<ASTNode>,
+    history: Vec<String>,
+    settings: EvalSettings,
+    roots: RootConfig,
+    color: bool,
+}
+
+impl Repl {
+    pub fn new(vars: VariableManager, resolver: Box<dyn VariableResolver>) -> Self {
+        Self {
+            vars,
+            resolver,
+            source: None,
+            ast: None,
+            history: Vec::new(),
+            settings: EvalSettings::default(),
+            roots: RootConfig::default(),
+            color: false,
+        }
+    }
+
+    /// Sets the initial angle unit and domain mode
+    pub fn with_settings(mut self, settings: EvalSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Diagnostics are printed with colours
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Reads lines from stdin until :quit or end of input
+    pub fn run(&mut self) {
+        println!("Arithmetic REPL. Type :help for the list of commands.");
+
+        loop {
+            print!(">> ");
+            io::stdout().flush().unwrap();
+
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == ":quit" || line == ":q" {
+                break;
+            }
+
+            self.history.push(line.to_string());
+            if let Err(e) = self.handle_line(line) {
+                print_error(&e, self.error_source(line), self.color);
+            }
+        }
+    }
+
+    /// Handles one line: either a command or an expression
+    pub fn handle_line(&mut self, line: &str) -> Result<(), CompilerError> {
+        let Some(command) = line.strip_prefix(':') else {
+            return self.evaluate(line);
+        };
+
+        let (name, rest) = command.split_once(' ').unwrap_or((command, ""));
+        let rest = rest.trim();
+
+        match name {
+            "ast" => match self.expression_or_current(rest)? {
+                Some(ast) => println!("{}", ast.stringify("".to_string(), false)),
+                None => println!("No current expression."),
+            },
+            "postfix" => match self.expression_or_current(rest)? {
+                Some(ast) => println!("{}", ast.postfix()),
+                None => println!("No current expression."),
+            },
+            "infix" => match self.expression_or_current(rest)? {
+                Some(ast) => println!("{}", ast.infix()),
+                None => println!("No current expression."),
+            },
+            "latex" => match self.expression_or_current(rest)? {
+                Some(ast) => println!("{}", ast.to_latex()),
+                None => println!("No current expression."),
+            },
+            "mathml" => match self.expression_or_current(rest)? {
+                Some(ast) => println!("{}", ast.to_mathml()),
+                None => println!("No current expression."),
+            },
+            "bytecode" => match self.expression_or_current(rest)? {
+                Some(ast) => print!("{}", Bytecode::compile(&ast, &self.vars)?.disassemble()),
+                None => println!("No current expression."),
+            },
+            "ir" => match self.expression_or_current(rest)? {
+                Some(ast) => print!(
+                    "{}",
+                    Function::lower(&ast, &self.vars, self.settings.angle_mode)?
+                        .optimization_dump()
+                ),
+                None => println!("No current expression."),
+            },
+            "tokens" => match (rest.is_empty(), self.source.clone()) {
+                (false, _) => print_lexer(rest)?,
+                (true, Some(source)) => print_lexer(&source)?,
+                (true, None) => println!("No current expression."),
+            },
+            "derive" => self.derive(if rest.is_empty() { "x" } else { rest })?,
+            "simplify" => self.simplify(),
+            "roots" => match (rest.is_empty(), Self::parse_interval(rest)) {
+                (true, _) => self.polynomial_roots()?,
+                (false, Some((a, b))) => self.roots(a, b)?,
+                (false, None) => println!("Usage: :roots [a b]"),
+            },
+            "solve" => {
+                // An optional name, then an optional interval
+                let (var, interval) = match rest.split_once(' ') {
+                    Some((var, interval)) if var.parse::<f64>().is_err() => (Some(var), interval),
+                    None if !rest.is_empty() && rest.parse::<f64>().is_err() => (Some(rest), ""),
+                    _ => (None, rest),
+                };
+                match (interval.trim().is_empty(), Self::parse_interval(interval)) {
+                    (true, _) => self.solve(var, (None, None))?,
+                    (false, Some((a, b))) => self.solve(var, (Some(a), Some(b)))?,
+                    (false, None) => println!("Usage: :solve [var] [a b]"),
+                }
+            }
+            "plot" => match Self::parse_interval(rest) {
+                Some((a, b)) => self.plot(a, b)?,
+                None => println!("Usage: :plot a b"),
+            },
+            "angle" => match (rest.is_empty(), AngleMode::parse(rest)) {
+                (true, _) => println!("Angle unit: {}", self.settings.angle_mode),
+                (false, Some(angle_mode)) => {
+                    self.settings.angle_mode = angle_mode;
+                    println!("Angle unit: {}", self.settings.angle_mode);
+                }
+                (false, None) => println!("Usage: :angle deg|rad|grad"),
+            },
+            "mode" => match (rest.is_empty(), DomainMode::parse(rest)) {
+                (true, _) => println!("Domain mode: {}", self.settings.domain_mode),
+                (false, Some(domain_mode)) => {
+                    self.settings.domain_mode = domain_mode;
+                    println!("Domain mode: {}", self.settings.domain_mode);
+                }
+                (false, None) => println!("Usage: :mode strict|lenient"),
+            },
+            "method" => match (rest.is_empty(), RootMethod::parse(rest)) {
+                (true, _) => println!("Root finding method: {}", self.roots.method),
+                (false, Some(method)) => {
+                    self.roots.method = method;
+                    println!("Root finding method: {}", self.roots.method);
+                }
+                (false, None) => {
+                    println!("Usage: :method hybrid|bisection|newton|secant|brent|illinois")
+                }
+            },
+            "vars" => self.print_vars(),
+            "clear" => {
+                self.vars.clear();
+                println!("Variables cleared.");
+            }
+            "history" => {
+                for (i, entry) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", i + 1, entry);
+                }
+            }
+            "help" => println!("{}", REPL_HELP),
+            _ => println!(
+                "Unknown command ':{}'. Type :help for the list of commands.",
+                name
+            ),
+        }
+
+        Ok(())
+    }
+
+    /// Runs the statements of the line. The last expression becomes the current expression.
+    /// A trailing equation is not evaluated, lhs - rhs becomes the current expression.
+    fn evaluate(&mut self, line: &str) -> Result<(), CompilerError> {
+        let mut program = lex_parse_program(line)?;
+        let equation = match program.statements.last() {
+            Some(Statement::Equation(lhs, rhs, span)) => Some(difference(lhs, rhs, span)),
+            _ => None,
+        };
+        if equation.is_some() {
+            program.statements.pop();
+        }
+
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+        let result = evaluator.evaluate_program(&program);
+        print_warnings(&evaluator.warnings, line, self.color);
+        let result = result?;
+
+        if let Some(equation) = equation {
+            self.source = Some(line.to_string());
+            self.ast = Some(equation);
+            println!("Equation stored, :solve solves it.");
+            return Ok(());
+        }
+        if let Some(Statement::Expression(ast)) = program.statements.last() {
+            self.source = Some(line.to_string());
+            self.ast = Some(ast.clone());
+        }
+
+        match result {
+            Some(result) => println!("{}", result),
+            None => println!("Defined."),
+        }
+        Ok(())
+    }
+
+    /// The text the spans of an error refer to: the line, the argument of
+    /// an expression command or the current expression
+    fn error_source<'b>(&'b self, line: &'b str) -> &'b str {
+        let Some(command) = line.strip_prefix(':') else {
+            return line;
+        };
+
+        match command.split_once(' ') {
+            Some((
+                "ast" | "postfix" | "infix" | "latex" | "mathml" | "bytecode" | "ir" | "tokens",
+                rest,
+            )) if !rest.trim().is_empty() => rest.trim(),
+            _ => self.source.as_deref().unwrap_or(""),
+        }
+    }
+
+    /// Parses the given expression, or falls back to the current one
+    fn expression_or_current(&self, expression: &str) -> Result<Option<ASTNode>, CompilerError> {
+        if expression.is_empty() {
+            Ok(self.ast.clone())
+        } else {
+            lex_parse_input(expression).map(Some)
+        }
+    }
+
+    /// User functions are inlined so their bodies are differentiated as well
+    fn derive(&mut self, var: &str) -> Result<(), CompilerError> {
+        let Some(ast) = &self.ast else {
+            println!("No current expression.");
+            return Ok(());
+        };
+
+        let derivative = ast
+            .inline_functions(&self.vars)?
+            .derivative_in(var, self.settings.angle_mode)?
+            .simplify_with(self.settings);
+        println!("{}", derivative.infix());
+        self.ast = Some(derivative);
+        Ok(())
+    }
+
+    fn simplify(&mut self) {
+        let Some(ast) = &self.ast else {
+            println!("No current expression.");
+            return;
+        };
+
+        let simplified = ast.simplify_with(self.settings);
+        println!("{}", simplified.infix());
+        self.ast = Some(simplified);
+    }
+
+    fn roots(&mut self, a: f64, b: f64) -> Result<(), CompilerError> {
+        let Some(ast) = &self.ast else {
+            println!("No current expression.");
+            return Ok(());
+        };
+
+        let var = solve::roots_unknown(&ast.inline_functions(&self.vars)?, &self.vars)?;
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+        let mut root_finder = RootFinder::new(ast, &mut evaluator)
+            .with_config(self.roots)
+            .with_variable(&var);
+        println!("{}", root_finder.find_roots(Some(a), Some(b))?);
+        Ok(())
+    }
+
+    fn polynomial_roots(&mut self) -> Result<(), CompilerError> {
+        let Some(ast) = &self.ast else {
+            println!("No current expression.");
+            return Ok(());
+        };
+
+        let ast = ast.inline_functions(&self.vars)?;
+        let var = solve::roots_unknown(&ast, &self.vars)?;
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+        match ast.polynomial(&var, &mut evaluator)? {
+            Some(polynomial) if polynomial.degree() > 0 => println!("{}", polynomial.roots()),
+            _ => println!("Not a polynomial in {}, use :roots a b", var),
+        }
+        Ok(())
+    }
+
+    fn solve(
+        &mut self,
+        var: Option<&str>,
+        interval: (Option<f64>, Option<f64>),
+    ) -> Result<(), CompilerError> {
+        let Some(ast) = &self.ast else {
+            println!("No current expression.");
+            return Ok(());
+        };
+
+        let ast = ast.inline_functions(&self.vars)?;
+        let var = match var {
+            Some(var) => var.to_lowercase(),
+            None => solve::unknown(&ast, &self.vars)?,
+        };
+        let mut evaluator =
+            Evaluator::new(&mut self.vars, self.resolver.as_mut()).with_settings(self.settings);
+        println!(
+            "{}",
+            solve::solve(&ast, &var, &mut evaluator, interval, self.roots)?
+        );
+        Ok(())
+    }
+
+    fn plot(&mut self, a: f64, b: f64) -> Result<(), CompilerError> {
+        let Some(ast) = &self.ast else {
+            println!("No current expression.");
+            return Ok(());
+        };
+
+        let mut plotter = FunctionPlotter::new(&mut self.vars, self.resolver.as_mut())
+            .with_settings(self.settings);
+        plotter.plot_function(ast, Some(a), Some(b), None)
+    }
+
+    fn print_vars(&self) {
+        let entries = self.vars.entries();
+        let functions = self.vars.function_entries();
+        if entries.is_empty() && functions.is_empty() {
+            println!("No variables stored.");
+        }
+
+        for (name, value) in entries {
+            match value {
+                Num::Integer(i) => println!("{} = {}", name, i),
+                Num::Float(f) => println!("{} = {}", name, f),
+            }
+        }
+
+        for (name, function) in functions {
+            println!(
+                "{} := {}",
+                function.signature(name),
+                function.body.postfix()
+            );
+        }
+    }
+
+    /// "a b" -> (a, b)
+    fn parse_interval(rest: &str) -> Option<(f64, f64)> {
+        let mut parts = rest.split_whitespace().map(|p| p.parse::<f64>());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(a)), Some(Ok(b)), None) => Some((a, b)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::resolver::MapOnlyResolver;
+
+    fn repl() -> Repl {
+        Repl::new(VariableManager::new(), Box::new(MapOnlyResolver))
+    }
+
+    fn current(repl: &Repl) -> Option<String> {
+        repl.ast.as_ref().map(|ast| ast.infix())
+    }
+
+    #[test]
+    fn variables_and_functions_persist_across_lines() {
+        let mut repl = repl();
+        repl.handle_line("r := 2").unwrap();
+        repl.handle_line("f(x) := x^2 + r").unwrap();
+        assert_eq!(repl.vars.get_stored("r"), Some(Num::Integer(2)));
+        assert_eq!(current(&repl), None);
+
+        repl.handle_line("f(3) * r").unwrap();
+        assert_eq!(current(&repl).as_deref(), Some("f(3) * r"));
+        assert_eq!(repl.source.as_deref(), Some("f(3) * r"));
+
+        repl.handle_line(":clear").unwrap();
+        assert_eq!(repl.vars.get_stored("r"), None);
+        assert!(repl.handle_line("r").is_err());
+        // A failed line keeps the current expression
+        assert_eq!(current(&repl).as_deref(), Some("f(3) * r"));
+    }
+
+    #[test]
+    fn equation_becomes_the_current_expression() {
+        let mut repl = repl();
+        repl.handle_line("a := 3; a*x = 6").unwrap();
+        assert_eq!(repl.vars.get_stored("a"), Some(Num::Integer(3)));
+        assert_eq!(current(&repl).as_deref(), Some("a * x - 6"));
+        repl.handle_line(":solve").unwrap();
+        repl.handle_line(":solve x -1 1").unwrap();
+    }
+
+    #[test]
+    fn derive_and_simplify_replace_the_current_expression() {
+        let mut repl = repl();
+        repl.handle_line(":derive").unwrap();
+        assert_eq!(current(&repl), None);
+
+        repl.handle_line("y := 2; y^3 + 0*y").unwrap();
+        repl.handle_line(":simplify").unwrap();
+        assert_eq!(current(&repl).as_deref(), Some("y^3"));
+        repl.handle_line(":derive y").unwrap();
+        assert_eq!(current(&repl).as_deref(), Some("3 * y^2"));
+    }
+
+    #[test]
+    fn settings_commands() {
+        let mut repl = repl();
+        repl.handle_line(":angle rad").unwrap();
+        repl.handle_line(":mode strict").unwrap();
+        repl.handle_line(":method brent").unwrap();
+        assert_eq!(repl.settings.angle_mode, AngleMode::Radians);
+        assert_eq!(repl.settings.domain_mode, DomainMode::Strict);
+        assert_eq!(repl.roots.method, RootMethod::Brent);
+
+        // Invalid values only print the usage
+        repl.handle_line(":angle turns").unwrap();
+        repl.handle_line(":mode loose").unwrap();
+        repl.handle_line(":method guess").unwrap();
+        repl.handle_line(":frobnicate").unwrap();
+        assert_eq!(repl.settings.angle_mode, AngleMode::Radians);
+        assert_eq!(repl.settings.domain_mode, DomainMode::Strict);
+        assert_eq!(repl.roots.method, RootMethod::Brent);
+
+        assert!(repl.handle_line("sqrt(-1)").is_err());
+    }
+
+    #[test]
+    fn expression_commands_take_an_argument_or_the_current_expression() {
+        let mut repl = repl();
+        for command in [":ast", ":postfix", ":infix", ":latex", ":mathml", ":tokens"] {
+            repl.handle_line(command).unwrap();
+            repl.handle_line(&format!("{} 1 + 2*3", command)).unwrap();
+        }
+        assert!(repl.handle_line(":infix 1 +").is_err());
+        assert_eq!(repl.error_source(":infix 1 +"), "1 +");
+
+        repl.handle_line("2 + 3").unwrap();
+        assert_eq!(repl.error_source(":bytecode"), "2 + 3");
+        assert_eq!(repl.error_source("4 *"), "4 *");
+    }
+
+    #[test]
+    fn interval_is_two_numbers() {
+        assert_eq!(Repl::parse_interval("-1 2.5"), Some((-1.0, 2.5)));
+        assert_eq!(Repl::parse_interval("1"), None);
+        assert_eq!(Repl::parse_interval("1 2 3"), None);
+        assert_eq!(Repl::parse_interval("a b"), None);
+    }
+}
@@ -0,0 +1,641 @@
+pub mod regalloc;
+
+use super::Helper;
+use crate::builtins::BUILTINS;
+use crate::errors::{codegen::CodegenError, CompilerError};
+use crate::ir::{BinaryOp, Function, Op, Value};
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+
+use regalloc::{allocate, Allocation, Location};
+
+/// Parameters passed in xmm0 to xmm7 by the System V ABI, the others are on the stack
+const REGISTER_PARAMS: usize = 8;
+
+/// Sign bit and the other bits of a double, for xorpd and andpd
+const SIGN_MASK: u64 = 0x8000_0000_0000_0000;
+const ABS_MASK: u64 = 0x7fff_ffff_ffff_ffff;
+
+/// Lowers the three-address code to x86-64 assembly in AT&T syntax for the GNU
+/// assembler. The function follows the System V ABI: doubles are passed and returned
+/// in xmm registers and transcendental functions are calls to libm, so
+/// `cc -c f.s` gives an object that C code links with `-lm`. Temporaries live in
+/// xmm registers or, when they are spilled, in the stack frame.
+pub struct AssemblyGenerator {
+    name: String,
+    out: String,
+    /// Bit patterns of the constants, each one 16 bytes aligned for the packed
+    /// instructions
+    constants: Vec<u64>,
+    helpers: BTreeSet<Helper>,
+}
+
+impl AssemblyGenerator {
+    /// name is the global symbol of the function
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            out: String::new(),
+            constants: Vec::new(),
+            helpers: BTreeSet::new(),
+        }
+    }
+
+    pub fn generate(mut self, function: &Function) -> Result<String, CompilerError> {
+        let calls = function
+            .code
+            .iter()
+            .map(|instruction| calls(&instruction.op))
+            .collect::<Vec<usize>>();
+
+        // Frame: home slots of the register parameters, a scratch slot, spilled temporaries
+        let homes = function.params.len().min(REGISTER_PARAMS) as u32 * 8;
+        let scratch = Location::Stack(homes + 8);
+        let allocation = allocate(function, &calls, homes + 8);
+        let frame_size = allocation.frame_size.div_ceil(16) * 16;
+
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| format!("double {}", param))
+            .collect();
+        self.comment(&format!(
+            "Generated by arithmetic from: {}",
+            function.source
+        ));
+        self.comment(&format!(
+            "double {}({}), System V AMD64 ABI. Link with -lm.",
+            self.name,
+            match params.is_empty() {
+                true => "void".to_string(),
+                false => params.join(", "),
+            }
+        ));
+        self.comment("Evaluation errors, such as a division by zero, give NaN.");
+        self.directive(".text");
+        self.directive(&format!(".globl {}", self.name));
+        self.directive(&format!(".type {}, @function", self.name));
+        self.out.push_str(&format!("{}:\n", self.name));
+        self.line("push %rbp");
+        self.line("mov %rsp, %rbp");
+        if frame_size > 0 {
+            self.line(&format!("sub ${}, %rsp", frame_size));
+        }
+        for (index, param) in function.params.iter().take(REGISTER_PARAMS).enumerate() {
+            let home = Location::Stack(index as u32 * 8 + 8);
+            self.annotated(&format!("movsd %xmm{}, {}", index, home), param);
+        }
+
+        for (index, instruction) in function.code.iter().enumerate() {
+            self.comment(&format!("    {}", function.describe(index)));
+            let dest = allocation.locations[index];
+            match &instruction.op {
+                Op::Const(value) => {
+                    let constant = self.constant(value.to_bits());
+                    self.copy(&constant, dest);
+                }
+                Op::Param(param) => {
+                    let source = param_location(*param as usize);
+                    self.copy(&source, dest);
+                }
+                Op::Binary(op, left, right) => {
+                    let left = allocation.locations[left.index()];
+                    let right = allocation.locations[right.index()];
+                    self.binary(*op, left, right, dest);
+                }
+                Op::Neg(value) => {
+                    let mask = self.constant(SIGN_MASK);
+                    self.unary("xorpd", &mask, allocation.locations[value.index()], dest);
+                }
+                Op::Call(function, args) => {
+                    let name = BUILTINS[*function as usize].name;
+                    let args = locations(&allocation, args);
+                    if !self.call(name, &args, dest, scratch) {
+                        return Err(CompilerError::Codegen(CodegenError::Unsupported(
+                            name.to_string(),
+                            instruction.span.clone(),
+                        )));
+                    }
+                }
+            }
+        }
+
+        self.comment(&format!("    ret {}", function.result));
+        let result = allocation.locations[function.result.index()];
+        self.load(&result.to_string(), 0);
+        self.line("leave");
+        self.line("ret");
+        self.directive(&format!(".size {}, .-{}", self.name, self.name));
+
+        for helper in self.helpers.iter() {
+            self.out.push('\n');
+            self.out.push_str(helper_source(*helper));
+        }
+
+        if !self.constants.is_empty() {
+            self.out.push('\n');
+            self.directive(".section .rodata");
+            for (index, bits) in self.constants.iter().enumerate() {
+                self.out.push_str(&format!(
+                    "    .align 16\n.LC{}:\n    .quad {:#018x}, 0{}\n",
+                    index,
+                    bits,
+                    match *bits {
+                        SIGN_MASK | ABS_MASK => String::new(),
+                        _ => format!("    # {:?}", f64::from_bits(*bits)),
+                    }
+                ));
+            }
+        }
+        self.directive(".section .note.GNU-stack,\"\",@progbits");
+
+        Ok(self.out)
+    }
+
+    /// dest = left op right
+    fn binary(&mut self, op: BinaryOp, left: Location, right: Location, dest: Location) {
+        let instruction = match op {
+            BinaryOp::Add => "addsd",
+            BinaryOp::Sub => "subsd",
+            BinaryOp::Mul => "mulsd",
+            BinaryOp::Div => "divsd",
+            BinaryOp::Pow => return self.libm("pow", &[left, right], dest),
+            BinaryOp::Mod | BinaryOp::IntDiv => {
+                let helper = match op {
+                    BinaryOp::Mod => Helper::Mod,
+                    _ => Helper::IntDiv,
+                };
+                self.helpers.insert(helper);
+                return self.call_function(helper.name(), false, &[left, right], dest);
+            }
+        };
+
+        // The right operand is read after the result register is written, and again
+        // by the zero check of the division
+        let register = match dest {
+            Location::Register(register)
+                if right != dest || (left == dest && op != BinaryOp::Div) =>
+            {
+                register
+            }
+            _ => 15,
+        };
+        self.load(&left.to_string(), register);
+        self.line(&format!("{} {}, %xmm{}", instruction, right, register));
+        if op == BinaryOp::Div {
+            self.line("xorpd %xmm14, %xmm14");
+            self.annotated(
+                &format!("cmpeqsd {}, %xmm14", right),
+                "all ones for a zero divisor",
+            );
+            self.annotated(&format!("orpd %xmm14, %xmm{}", register), "gives NaN");
+        }
+        self.store(register, dest);
+    }
+
+    /// dest = operand combined with a constant by a packed instruction
+    fn unary(&mut self, instruction: &str, constant: &str, operand: Location, dest: Location) {
+        let register = match dest {
+            Location::Register(register) => register,
+            Location::Stack(_) => 15,
+        };
+        self.load(&operand.to_string(), register);
+        self.line(&format!("{} {}, %xmm{}", instruction, constant, register));
+        self.store(register, dest);
+    }
+
+    /// A built-in function. false when it has no translation.
+    fn call(&mut self, name: &str, args: &[Location], dest: Location, scratch: Location) -> bool {
+        match (name, args) {
+            ("sqrt", [x]) => {
+                let register = dest_register(dest);
+                self.line(&format!("sqrtsd {}, %xmm{}", x, register));
+                self.store(register, dest);
+            }
+            ("sqr", [x]) => self.binary(BinaryOp::Mul, *x, *x, dest),
+            ("abs", [x]) => {
+                let mask = self.constant(ABS_MASK);
+                self.unary("andpd", &mask, *x, dest);
+            }
+            ("sign", [x]) => {
+                let (sign, one, zero) = (
+                    self.constant(SIGN_MASK),
+                    self.constant(1f64.to_bits()),
+                    self.constant(0f64.to_bits()),
+                );
+                self.load(&x.to_string(), 15);
+                self.line(&format!("andpd {}, %xmm15", sign));
+                self.annotated(&format!("orpd {}, %xmm15", one), "-1 or 1");
+                self.load(&x.to_string(), 14);
+                self.annotated(&format!("cmpneqsd {}, %xmm14", zero), "all ones unless 0");
+                self.line("andpd %xmm14, %xmm15");
+                self.load(&x.to_string(), 14);
+                self.annotated("cmpunordsd %xmm14, %xmm14", "all ones for NaN");
+                self.line("orpd %xmm14, %xmm15");
+                self.store(15, dest);
+            }
+            ("cotan", [x]) => {
+                self.libm("tan", &[*x], Location::Register(0));
+                let one = self.constant(1f64.to_bits());
+                self.load(&one, 15);
+                self.line("divsd %xmm0, %xmm15");
+                self.store(15, dest);
+            }
+            ("arccotan", [x]) => {
+                self.libm("atan", &[*x], Location::Register(0));
+                let half_pi = self.constant((PI / 2.0).to_bits());
+                self.load(&half_pi, 15);
+                self.line("subsd %xmm0, %xmm15");
+                self.store(15, dest);
+            }
+            // ln(x) / ln(base), the first logarithm waits in the scratch slot
+            ("log", [x, base]) => {
+                self.libm("log", &[*x], scratch);
+                self.libm("log", &[*base], Location::Register(0));
+                self.load(&scratch.to_string(), 15);
+                self.line("divsd %xmm0, %xmm15");
+                self.store(15, dest);
+            }
+            // Folded from infinity like the evaluator, so NaN arguments are skipped
+            ("min" | "max", _) => {
+                let (function, start) = match name {
+                    "min" => ("fmin", f64::INFINITY),
+                    _ => ("fmax", f64::NEG_INFINITY),
+                };
+                let start = self.constant(start.to_bits());
+                self.load(&start, 0);
+                for arg in args.iter() {
+                    self.load(&arg.to_string(), 1);
+                    self.line(&format!("call {}@PLT", function));
+                }
+                self.store(0, dest);
+            }
+            // libm computes these differently from the Rust standard library
+            ("arcsinh" | "arccosh" | "arctanh" | "cbrt", [x]) => {
+                let helper = match name {
+                    "arcsinh" => Helper::Asinh,
+                    "arccosh" => Helper::Acosh,
+                    "arctanh" => Helper::Atanh,
+                    _ => Helper::Cbrt,
+                };
+                self.helpers.insert(helper);
+                self.call_function(helper.name(), false, &[*x], dest);
+            }
+            _ => {
+                let function = match (name, args.len()) {
+                    ("sin" | "cos" | "tan" | "sinh" | "cosh" | "tanh" | "exp" | "log2", _)
+                    | ("floor" | "ceil" | "trunc" | "hypot" | "atan2" | "pow", _) => name,
+                    ("round", 1) => "round",
+                    ("arcsin", _) => "asin",
+                    ("arccos", _) => "acos",
+                    ("arctan", _) => "atan",
+                    ("ln", _) => "log",
+                    ("log", _) => "log10",
+                    // root, round(x, digits) and the special functions have no libm equivalent
+                    _ => return false,
+                };
+                self.libm(function, args, dest);
+            }
+        }
+        true
+    }
+
+    fn libm(&mut self, function: &str, args: &[Location], dest: Location) {
+        self.call_function(function, true, args, dest);
+    }
+
+    /// Arguments in xmm0 and xmm1, the result comes back in xmm0
+    fn call_function(&mut self, function: &str, plt: bool, args: &[Location], dest: Location) {
+        for (index, arg) in args.iter().enumerate() {
+            self.load(&arg.to_string(), index as u8);
+        }
+        match plt {
+            true => self.line(&format!("call {}@PLT", function)),
+            false => self.line(&format!("call {}", function)),
+        }
+        self.store(0, dest);
+    }
+
+    /// Label of the constant with the bit pattern
+    fn constant(&mut self, bits: u64) -> String {
+        let index = match self.constants.iter().position(|constant| *constant == bits) {
+            Some(index) => index,
+            None => {
+                self.constants.push(bits);
+                self.constants.len() - 1
+            }
+        };
+        format!(".LC{}(%rip)", index)
+    }
+
+    /// Memory to memory moves go through xmm15
+    fn copy(&mut self, source: &str, dest: Location) {
+        let register = dest_register(dest);
+        self.load(source, register);
+        self.store(register, dest);
+    }
+
+    fn load(&mut self, source: &str, register: u8) {
+        let register = format!("%xmm{}", register);
+        if source != register {
+            self.line(&format!("movsd {}, {}", source, register));
+        }
+    }
+
+    fn store(&mut self, register: u8, dest: Location) {
+        if dest != Location::Register(register) {
+            self.line(&format!("movsd %xmm{}, {}", register, dest));
+        }
+    }
+
+    fn line(&mut self, instruction: &str) {
+        self.out.push_str(&format!("    {}\n", instruction));
+    }
+
+    fn annotated(&mut self, instruction: &str, comment: &str) {
+        self.out
+            .push_str(&format!("    {:<32}# {}\n", instruction, comment));
+    }
+
+    fn directive(&mut self, directive: &str) {
+        self.line(directive);
+    }
+
+    fn comment(&mut self, comment: &str) {
+        self.out.push_str(&format!("# {}\n", comment));
+    }
+}
+
+/// Number of calls the instruction makes. The register allocator keeps the
+/// temporaries that live across a call on the stack.
+fn calls(op: &Op) -> usize {
+    match op {
+        Op::Const(_) | Op::Param(_) | Op::Neg(_) => 0,
+        Op::Binary(BinaryOp::Pow | BinaryOp::Mod | BinaryOp::IntDiv, _, _) => 1,
+        Op::Binary(_, _, _) => 0,
+        Op::Call(function, args) => match (BUILTINS[*function as usize].name, args.len()) {
+            ("sqrt" | "sqr" | "abs" | "sign", _) => 0,
+            ("log", 2) => 2,
+            ("min" | "max", count) => count,
+            _ => 1,
+        },
+    }
+}
+
+/// Home slot of a register parameter, or its place in the caller's frame
+fn param_location(index: usize) -> String {
+    match index < REGISTER_PARAMS {
+        true => Location::Stack(index as u32 * 8 + 8).to_string(),
+        false => format!("{}(%rbp)", 16 + (index - REGISTER_PARAMS) * 8),
+    }
+}
+
+fn locations(allocation: &Allocation, values: &[Value]) -> Vec<Location> {
+    values
+        .iter()
+        .map(|value| allocation.locations[value.index()])
+        .collect()
+}
+
+/// Register that receives a result: the destination itself or xmm15
+fn dest_register(dest: Location) -> u8 {
+    match dest {
+        Location::Register(register) => register,
+        Location::Stack(_) => 15,
+    }
+}
+
+/// Routines written next to the function, with the same checks as the evaluator
+fn helper_source(helper: Helper) -> &'static str {
+    match helper {
+        Helper::Mod => {
+            "# `mod`, integer operands only: NaN when a or b has a fraction
+    .type arith_mod, @function
+arith_mod:
+    push %rbp
+    mov %rsp, %rbp
+    sub $32, %rsp
+    movsd %xmm0, -8(%rbp)
+    movsd %xmm1, -16(%rbp)
+    call trunc@PLT
+    movsd -8(%rbp), %xmm1
+    subsd %xmm0, %xmm1
+    movsd %xmm1, -24(%rbp)          # fraction of a
+    movsd -16(%rbp), %xmm0
+    call trunc@PLT
+    movsd -16(%rbp), %xmm1
+    subsd %xmm0, %xmm1              # fraction of b
+    xorpd %xmm2, %xmm2
+    cmpneqsd %xmm2, %xmm1           # all ones when it is not 0
+    movsd -24(%rbp), %xmm3
+    cmpneqsd %xmm2, %xmm3
+    orpd %xmm3, %xmm1
+    movsd %xmm1, -24(%rbp)          # NaN mask
+    movsd -8(%rbp), %xmm0
+    movsd -16(%rbp), %xmm1
+    call fmod@PLT
+    movsd -24(%rbp), %xmm1
+    orpd %xmm1, %xmm0
+    leave
+    ret
+    .size arith_mod, .-arith_mod
+"
+        }
+        Helper::IntDiv => {
+            "# `div`, floor of the quotient of integer operands: NaN when a or b has a
+# fraction or b is 0
+    .type arith_int_div, @function
+arith_int_div:
+    push %rbp
+    mov %rsp, %rbp
+    sub $32, %rsp
+    movsd %xmm0, -8(%rbp)
+    movsd %xmm1, -16(%rbp)
+    call trunc@PLT
+    movsd -8(%rbp), %xmm1
+    subsd %xmm0, %xmm1
+    movsd %xmm1, -24(%rbp)          # fraction of a
+    movsd -16(%rbp), %xmm0
+    call trunc@PLT
+    movsd -16(%rbp), %xmm1
+    subsd %xmm0, %xmm1              # fraction of b
+    xorpd %xmm2, %xmm2
+    cmpneqsd %xmm2, %xmm1           # all ones when it is not 0
+    movsd -24(%rbp), %xmm3
+    cmpneqsd %xmm2, %xmm3
+    orpd %xmm3, %xmm1
+    movsd -16(%rbp), %xmm3
+    cmpeqsd %xmm2, %xmm3            # all ones when b is 0
+    orpd %xmm3, %xmm1
+    movsd %xmm1, -24(%rbp)          # NaN mask
+    movsd -8(%rbp), %xmm0
+    divsd -16(%rbp), %xmm0
+    call floor@PLT
+    movsd -24(%rbp), %xmm1
+    orpd %xmm1, %xmm0
+    leave
+    ret
+    .size arith_int_div, .-arith_int_div
+"
+        }
+        Helper::Cbrt => {
+            "# f64::cbrt is correctly rounded, the long double root rounds the same but for
+# rare halfway cases
+    .type arith_cbrt, @function
+arith_cbrt:
+    push %rbp
+    mov %rsp, %rbp
+    sub $32, %rsp
+    movsd %xmm0, -8(%rbp)
+    fldl -8(%rbp)
+    fstpt (%rsp)                    # long double argument
+    call cbrtl@PLT
+    fstpl -8(%rbp)
+    movsd -8(%rbp), %xmm0
+    leave
+    ret
+    .size arith_cbrt, .-arith_cbrt
+"
+        }
+        Helper::Asinh => {
+            "# copysign(log1p(ax + ax / (hypot(1, 1 / ax) + 1 / ax)), x) with ax = |x|,
+# like f64::asinh
+    .type arith_asinh, @function
+arith_asinh:
+    push %rbp
+    mov %rsp, %rbp
+    sub $32, %rsp
+    movsd %xmm0, -8(%rbp)           # x
+    mov $0x7fffffffffffffff, %rax
+    movq %rax, %xmm1
+    andpd %xmm1, %xmm0
+    movsd %xmm0, -16(%rbp)          # ax
+    mov $0x3ff0000000000000, %rax
+    movq %rax, %xmm0                # 1
+    movapd %xmm0, %xmm1
+    divsd -16(%rbp), %xmm1
+    movsd %xmm1, -24(%rbp)          # ix = 1 / ax
+    call hypot@PLT
+    addsd -24(%rbp), %xmm0
+    movsd -16(%rbp), %xmm1
+    divsd %xmm0, %xmm1
+    addsd -16(%rbp), %xmm1
+    movapd %xmm1, %xmm0
+    call log1p@PLT
+    mov $0x7fffffffffffffff, %rax
+    movq %rax, %xmm1
+    andpd %xmm1, %xmm0
+    mov $0x8000000000000000, %rax
+    movq %rax, %xmm1
+    movsd -8(%rbp), %xmm2
+    andpd %xmm1, %xmm2
+    orpd %xmm2, %xmm0               # sign of x
+    leave
+    ret
+    .size arith_asinh, .-arith_asinh
+"
+        }
+        Helper::Acosh => {
+            "# NaN below 1, otherwise log(x + sqrt(x - 1) * sqrt(x + 1)) like f64::acosh
+    .type arith_acosh, @function
+arith_acosh:
+    push %rbp
+    mov %rsp, %rbp
+    mov $0x3ff0000000000000, %rax
+    movq %rax, %xmm1                # 1
+    ucomisd %xmm1, %xmm0
+    jb .Lacosh_nan                  # also taken for NaN
+    movapd %xmm0, %xmm2
+    subsd %xmm1, %xmm2
+    sqrtsd %xmm2, %xmm2
+    movapd %xmm0, %xmm3
+    addsd %xmm1, %xmm3
+    sqrtsd %xmm3, %xmm3
+    mulsd %xmm3, %xmm2
+    addsd %xmm2, %xmm0
+    call log@PLT
+    leave
+    ret
+.Lacosh_nan:
+    mov $0x7ff8000000000000, %rax
+    movq %rax, %xmm0
+    leave
+    ret
+    .size arith_acosh, .-arith_acosh
+"
+        }
+        Helper::Atanh => {
+            "# 0.5 * log1p(2x / (1 - x)) like f64::atanh
+    .type arith_atanh, @function
+arith_atanh:
+    push %rbp
+    mov %rsp, %rbp
+    mov $0x3ff0000000000000, %rax
+    movq %rax, %xmm1                # 1
+    subsd %xmm0, %xmm1
+    addsd %xmm0, %xmm0
+    divsd %xmm1, %xmm0
+    call log1p@PLT
+    mov $0x3fe0000000000000, %rax
+    movq %rax, %xmm1                # 0.5
+    mulsd %xmm1, %xmm0
+    leave
+    ret
+    .size arith_atanh, .-arith_atanh
+"
+        }
+        // The other functions are calls to libm or a few instructions
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{angle::AngleMode, var::VariableManager};
+    use crate::utils::print::lex_parse_input;
+
+    fn assembly(input: &str) -> Result<String, CompilerError> {
+        let ast = lex_parse_input(input).unwrap();
+        let function = Function::lower(&ast, &VariableManager::new(), AngleMode::Radians)?;
+        AssemblyGenerator::new("f").generate(&function.optimized())
+    }
+
+    #[test]
+    fn spilled_temporaries_are_stored_and_read_from_the_frame() {
+        // The 14 parameters are all live until the sums are computed from the right
+        let asm = assembly(
+            "a + (b + (c + (d + (e1 + (f + (g + (h + (i + (j + (k + (l + (m + n))))))))))))",
+        )
+        .unwrap();
+
+        // 8 homes of register parameters, a scratch slot and the two spilled temporaries
+        assert!(asm.contains("    sub $96, %rsp\n"));
+        for snippet in [
+            "#     t0 = param a\n    movsd -8(%rbp), %xmm15\n    movsd %xmm15, -80(%rbp)\n",
+            "#     t1 = param b\n    movsd -16(%rbp), %xmm15\n    movsd %xmm15, -88(%rbp)\n",
+            "#     t8 = param i\n    movsd 16(%rbp), %xmm10\n",
+            "#     t12 = param m\n    movsd 48(%rbp), %xmm2\n",
+            "#     t13 = param n\n    movsd 56(%rbp), %xmm3\n",
+            "#     t25 = add t1, t24\n    movsd -88(%rbp), %xmm15\n    addsd %xmm3, %xmm15\n",
+            "#     t26 = add t0, t25\n    movsd -80(%rbp), %xmm15\n    addsd %xmm3, %xmm15\n",
+            "#     ret t26\n    movsd %xmm3, %xmm0\n    leave\n    ret\n",
+        ] {
+            assert!(asm.contains(snippet), "{}\nnot in\n{}", snippet, asm);
+        }
+    }
+
+    #[test]
+    fn functions_without_a_libm_counterpart_are_unsupported() {
+        let error = assembly("1 + gamma(x)").unwrap_err();
+        assert_eq!(error.code(), "E0702");
+        match error {
+            CompilerError::Codegen(CodegenError::Unsupported(name, span)) => {
+                assert_eq!((name.as_str(), span.column), ("gamma", 4))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(assembly("digamma(x)").is_err());
+        assert!(assembly("sin(x) + exp(x)").is_ok());
+    }
+}
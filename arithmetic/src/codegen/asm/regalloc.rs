@@ -0,0 +1,223 @@
+use crate::ir::Function;
+use std::fmt;
+
+/// Registers given to temporaries. xmm0 and xmm1 carry the arguments and results of
+/// calls, xmm14 and xmm15 are scratch registers of the instructions.
+const REGISTERS: std::ops::Range<u8> = 2..14;
+
+/// Where a temporary lives from its definition to its last use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// xmm register
+    Register(u8),
+    /// Offset below %rbp
+    Stack(u32),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Register(register) => write!(f, "%xmm{}", register),
+            Location::Stack(offset) => write!(f, "-{}(%rbp)", offset),
+        }
+    }
+}
+
+pub struct Allocation {
+    /// Location of each temporary
+    pub locations: Vec<Location>,
+    /// Offset of the lowest stack slot
+    pub frame_size: u32,
+}
+
+/// Linear scan register allocation. Temporaries are taken in the order of their
+/// definitions; when no register is free the one whose last use is the furthest away
+/// is spilled to the stack. Every xmm register is caller saved, so temporaries that
+/// are live across a call get a stack slot from the start. calls holds the number
+/// of calls each instruction makes, slots are allocated below base.
+pub fn allocate(function: &Function, calls: &[usize], base: u32) -> Allocation {
+    let count = function.code.len();
+
+    // Last use of every temporary, the result is used by the return after the code
+    let mut ends: Vec<usize> = (0..count).collect();
+    for (index, instruction) in function.code.iter().enumerate() {
+        for operand in instruction.op.operands() {
+            ends[operand.index()] = index;
+        }
+    }
+    ends[function.result.index()] = count;
+
+    let mut allocator = Allocator {
+        locations: vec![Location::Stack(0); count],
+        active: Vec::new(),
+        free_registers: REGISTERS.rev().collect(),
+        free_slots: Vec::new(),
+        frame_size: base,
+    };
+
+    for (index, end) in ends.iter().copied().enumerate() {
+        allocator.expire(index);
+
+        // Live across a call, or read by an instruction after its first call
+        let live_across_call =
+            (index + 1..count.min(end + 1)).any(|i| calls[i] > 1 || (calls[i] == 1 && i < end));
+        let location = match live_across_call {
+            true => allocator.slot(),
+            false => allocator.register(end),
+        };
+
+        allocator.locations[index] = location;
+        allocator.active.push((end, index));
+    }
+
+    Allocation {
+        locations: allocator.locations,
+        frame_size: allocator.frame_size,
+    }
+}
+
+struct Allocator {
+    locations: Vec<Location>,
+    /// (last use, temporary) of the temporaries that hold a location
+    active: Vec<(usize, usize)>,
+    /// Popped from the end, so xmm2 is used first
+    free_registers: Vec<u8>,
+    free_slots: Vec<u32>,
+    frame_size: u32,
+}
+
+impl Allocator {
+    /// Frees the locations of the temporaries that are not used after the instruction.
+    /// The operands of an instruction are read before its result is written, so the
+    /// result may take the location of an operand.
+    fn expire(&mut self, index: usize) {
+        let locations = &self.locations;
+        let (expired, active): (Vec<_>, Vec<_>) =
+            self.active.iter().partition(|(end, _)| *end <= index);
+        self.active = active;
+
+        for (_, temporary) in expired {
+            match locations[temporary] {
+                Location::Register(register) => self.free_registers.push(register),
+                Location::Stack(offset) => self.free_slots.push(offset),
+            }
+        }
+    }
+
+    /// A free register, or the register of the active temporary used last
+    fn register(&mut self, end: usize) -> Location {
+        if let Some(register) = self.free_registers.pop() {
+            return Location::Register(register);
+        }
+
+        let spilled = self
+            .active
+            .iter()
+            .filter(|(_, temporary)| matches!(self.locations[*temporary], Location::Register(_)))
+            .max_by_key(|(end, _)| *end)
+            .copied();
+
+        match spilled {
+            Some((spilled_end, temporary)) if spilled_end > end => {
+                let register = self.locations[temporary];
+                self.locations[temporary] = self.slot();
+                register
+            }
+            _ => self.slot(),
+        }
+    }
+
+    fn slot(&mut self) -> Location {
+        if let Some(offset) = self.free_slots.pop() {
+            return Location::Stack(offset);
+        }
+        self.frame_size += 8;
+        Location::Stack(self.frame_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{BinaryOp, Instruction, Op, Value};
+    use crate::lexer::span::TextSpan;
+
+    fn function(code: Vec<Op>) -> Function {
+        let span = TextSpan::new(0, 1, "x".to_string(), 1, 0);
+        let result = Value(code.len() as u32 - 1);
+        Function {
+            source: String::new(),
+            params: Vec::new(),
+            code: code
+                .into_iter()
+                .map(|op| Instruction {
+                    op,
+                    span: span.clone(),
+                })
+                .collect(),
+            result,
+        }
+    }
+
+    fn locations(allocation: &Allocation) -> Vec<String> {
+        allocation
+            .locations
+            .iter()
+            .map(|location| location.to_string())
+            .collect()
+    }
+
+    /// t0..t13 are parameters that are all live until the sum
+    /// t0 + (t1 + (... + (t12 + t13))) is computed from the right
+    #[test]
+    fn spills_the_temporaries_used_last() {
+        let mut code: Vec<Op> = (0..14).map(Op::Param).collect();
+        code.push(Op::Binary(BinaryOp::Add, Value(12), Value(13)));
+        for i in (0..12).rev() {
+            let sum = Value(code.len() as u32 - 1);
+            code.push(Op::Binary(BinaryOp::Add, Value(i), sum));
+        }
+        let calls = vec![0; code.len()];
+        let allocation = allocate(&function(code), &calls, 8);
+
+        // 12 registers for 14 parameters: t0 and t1, read by the last two additions,
+        // go to the stack and t12, t13 take their registers. A sum reuses the
+        // register of its operand.
+        let registers = |range: std::ops::Range<u8>| range.map(|r| format!("%xmm{}", r));
+        let expected: Vec<String> = ["-16(%rbp)", "-24(%rbp)"]
+            .map(String::from)
+            .into_iter()
+            .chain(registers(4..14))
+            .chain(registers(2..4))
+            .chain(registers(3..4).cycle().take(13))
+            .collect();
+        assert_eq!(locations(&allocation), expected);
+        assert_eq!(allocation.frame_size, 24);
+    }
+
+    #[test]
+    fn temporaries_live_across_a_call_are_on_the_stack() {
+        let code = vec![
+            Op::Param(0),
+            Op::Call(0, vec![Value(0)]),
+            Op::Binary(BinaryOp::Add, Value(0), Value(1)),
+        ];
+        let allocation = allocate(&function(code), &[0, 1, 0], 0);
+        assert_eq!(locations(&allocation), ["-8(%rbp)", "%xmm2", "%xmm2"]);
+        assert_eq!(allocation.frame_size, 8);
+    }
+
+    #[test]
+    fn free_locations_are_reused() {
+        // t0 and t1 are dead after t2, which takes the register freed last. t3 reuses it too.
+        let code = vec![
+            Op::Param(0),
+            Op::Const(2.0),
+            Op::Binary(BinaryOp::Mul, Value(0), Value(1)),
+            Op::Neg(Value(2)),
+        ];
+        let allocation = allocate(&function(code), &[0; 4], 0);
+        assert_eq!(locations(&allocation), ["%xmm2", "%xmm3", "%xmm3", "%xmm3"]);
+        assert_eq!(allocation.frame_size, 0);
+    }
+}
@@ -0,0 +1,257 @@
+use super::{Function, Helper};
+
+/// Names that can not be used as parameters: the keywords and the math.h functions,
+/// which a parameter of the same name would hide
+pub const KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "acos", "asin", "atan", "atan2", "cbrt",
+    "cbrtl", "ceil", "copysign", "cos", "cosh", "exp", "fabs", "floor", "fmax", "fmin", "fmod",
+    "hypot", "isnan", "log", "log10", "log1p", "log2", "pow", "round", "sin", "sinh", "sqrt",
+    "tan", "tanh", "trunc",
+];
+
+/// C source with math.h, the helpers and the function
+pub fn source(function: &Function) -> String {
+    let mut out = String::from("/*\n");
+    for line in function.header.lines() {
+        out.push_str(&format!(" * {}\n", line.replace("*/", "* /")));
+    }
+    out.push_str(" */\n#include <math.h>\n\n");
+
+    for helper in function.helpers.iter() {
+        out.push_str(helper_source(*helper));
+        out.push('\n');
+    }
+
+    let params = match function.params.is_empty() {
+        true => "void".to_string(),
+        false => function
+            .params
+            .iter()
+            .map(|param| format!("double {}", param))
+            .collect::<Vec<String>>()
+            .join(", "),
+    };
+    out.push_str(&format!("double {}({}) {{\n", function.name, params));
+    for (name, value) in function.constants.iter() {
+        out.push_str(&format!("    const double {} = {};\n", name, value));
+    }
+    out.push_str(&format!("    return {};\n}}\n", function.body));
+
+    out
+}
+
+/// Call of a math.h function, named after the f64 method
+pub fn math(method: &str, args: &[String]) -> String {
+    let name = match method {
+        "ln" => "log",
+        "powf" => "pow",
+        "abs" => "fabs",
+        "min" => "fmin",
+        "max" => "fmax",
+        _ => method,
+    };
+    format!("{}({})", name, args.join(", "))
+}
+
+/// Literal that reads back as the same double
+pub fn number(value: f64) -> String {
+    match value {
+        f64::INFINITY => "INFINITY".to_string(),
+        f64::NEG_INFINITY => "-INFINITY".to_string(),
+        _ if value.is_nan() => "NAN".to_string(),
+        _ => format!("{:?}", value),
+    }
+}
+
+/// The same computations as builtins.rs and the Rust standard library
+fn helper_source(helper: Helper) -> &'static str {
+    match helper {
+        Helper::Div => {
+            "/* `/`, the evaluator fails on a zero divisor */
+static double arith_div(double a, double b) {
+    return b == 0.0 ? NAN : a / b;
+}
+"
+        }
+        Helper::Mod => {
+            "/* `mod`, integer operands only */
+static double arith_mod(double a, double b) {
+    if (a - trunc(a) != 0.0 || b - trunc(b) != 0.0) {
+        return NAN;
+    }
+    return fmod(a, b);
+}
+"
+        }
+        Helper::IntDiv => {
+            "/* `div`, floor of the quotient of integer operands */
+static double arith_int_div(double a, double b) {
+    if (a - trunc(a) != 0.0 || b - trunc(b) != 0.0 || b == 0.0) {
+        return NAN;
+    }
+    return floor(a / b);
+}
+"
+        }
+        Helper::Sqr => {
+            "static double arith_sqr(double x) {
+    return x * x;
+}
+"
+        }
+        Helper::Cbrt => {
+            "/* f64::cbrt is correctly rounded, the long double root rounds the same but for
+ * rare halfway cases */
+static double arith_cbrt(double x) {
+    return (double)cbrtl(x);
+}
+"
+        }
+        Helper::Root => {
+            "/* n-th root, odd roots of negative numbers are real */
+static double arith_root(double n, double x) {
+    if (x < 0.0 && n - trunc(n) == 0.0 && fmod(n, 2.0) != 0.0) {
+        return -pow(-x, 1.0 / n);
+    }
+    return pow(x, 1.0 / n);
+}
+"
+        }
+        Helper::Powi => {
+            "/* x^n by repeated squaring, like f64::powi */
+static double arith_powi(double x, int n) {
+    int reciprocal = n < 0;
+    double result = 1.0;
+    while (1) {
+        if (n & 1) {
+            result *= x;
+        }
+        n /= 2;
+        if (n == 0) {
+            break;
+        }
+        x *= x;
+    }
+    return reciprocal ? 1.0 / result : result;
+}
+"
+        }
+        Helper::RoundDigits => {
+            "/* round(x, digits), digits saturate like `as i32` */
+static double arith_round_digits(double x, double digits) {
+    int n = isnan(digits)                ? 0
+            : digits >= 2147483647.0     ? 2147483647
+            : digits <= -2147483648.0    ? -2147483647 - 1
+                                         : (int)digits;
+    double factor = arith_powi(10.0, n);
+    return round(x * factor) / factor;
+}
+"
+        }
+        Helper::Sign => {
+            "/* -1, 0 or 1 */
+static double arith_sign(double x) {
+    if (x == 0.0) {
+        return 0.0;
+    }
+    return isnan(x) ? x : copysign(1.0, x);
+}
+"
+        }
+        Helper::Asinh => {
+            "static double arith_asinh(double x) {
+    double ax = fabs(x);
+    double ix = 1.0 / ax;
+    return copysign(log1p(ax + ax / (hypot(1.0, ix) + ix)), x);
+}
+"
+        }
+        Helper::Acosh => {
+            "static double arith_acosh(double x) {
+    return x < 1.0 ? NAN : log(x + sqrt(x - 1.0) * sqrt(x + 1.0));
+}
+"
+        }
+        Helper::Atanh => {
+            "static double arith_atanh(double x) {
+    return 0.5 * log1p(2.0 * x / (1.0 - x));
+}
+"
+        }
+        Helper::Gamma => {
+            "/* Gamma function: Lanczos approximation with the reflection formula for x < 0.5 */
+static double arith_gamma(double x) {
+    static const double coefficients[9] = {
+        0.9999999999998099, 676.5203681218851, -1259.1392167224028,
+        771.3234287776531, -176.6150291621406, 12.507343278686905,
+        -0.13857109526572012, 9.984369578019572e-6, 1.5056327351493116e-7,
+    };
+    const double pi = 3.141592653589793;
+
+    /* Poles at 0, -1, -2, ... */
+    if (x <= 0.0 && x - trunc(x) == 0.0) {
+        return NAN;
+    }
+    if (x < 0.5) {
+        return pi / (sin(pi * x) * arith_gamma(1.0 - x));
+    }
+
+    x = x - 1.0;
+    double t = x + 7.0 + 0.5;
+    double series = coefficients[0];
+    for (int i = 1; i < 9; i++) {
+        series = series + coefficients[i] / (x + i);
+    }
+    return sqrt(2.0 * pi) * pow(t, x + 0.5) * exp(-t) * series;
+}
+"
+        }
+        Helper::Factorial => {
+            "/* n! for integers, gamma(n + 1) otherwise */
+static double arith_factorial(double n) {
+    if (n - trunc(n) != 0.0) {
+        return arith_gamma(n + 1.0);
+    }
+    if (n < 0.0) {
+        return NAN;
+    }
+
+    double limit = fmin(n, 171.0);
+    double result = 1.0;
+    for (double i = 2.0; i <= limit; i += 1.0) {
+        result *= i;
+    }
+    return result;
+}
+"
+        }
+        Helper::Digamma => {
+            "/* Digamma function: recurrence up to x >= 6, then the asymptotic series */
+static double arith_digamma(double x) {
+    const double pi = 3.141592653589793;
+
+    /* Poles at 0, -1, -2, ... */
+    if (x <= 0.0 && x - trunc(x) == 0.0) {
+        return NAN;
+    }
+    if (x < 0.0) {
+        return arith_digamma(1.0 - x) - pi / tan(pi * x);
+    }
+
+    double result = 0.0;
+    while (x < 6.0) {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+
+    double inv2 = 1.0 / (x * x);
+    return result + log(x) - 0.5 / x
+           - inv2 * (1.0 / 12.0 - inv2 * (1.0 / 120.0 - inv2 * (1.0 / 252.0 - inv2 / 240.0)));
+}
+"
+        }
+    }
+}
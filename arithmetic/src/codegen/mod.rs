@@ -0,0 +1,631 @@
+pub mod asm;
+pub mod c;
+pub mod rust;
+
+use crate::ast::infix::{ATOM, PRODUCT, SUM, UNARY};
+use crate::ast::{angle::AngleMode, var::VariableManager, ASTNode};
+use crate::errors::{eval::EvaluationError, CompilerError};
+use crate::lexer::token::{Num, TokenKind};
+use crate::vm::compiler::resolve_function;
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Target of the code generator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    C,
+    Rust,
+}
+
+impl Language {
+    /// "c" or "rust"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "c" => Some(Language::C),
+            "rust" | "rs" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::C => write!(f, "C"),
+            Language::Rust => write!(f, "Rust"),
+        }
+    }
+}
+
+/// Functions written next to the generated one when the expression needs them.
+/// The order is the order of the definitions, helpers come before their callers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Helper {
+    Div,
+    Mod,
+    IntDiv,
+    Sqr,
+    Cbrt,
+    Root,
+    Powi,
+    RoundDigits,
+    Sign,
+    Asinh,
+    Acosh,
+    Atanh,
+    Gamma,
+    Factorial,
+    Digamma,
+}
+
+impl Helper {
+    /// Helpers called by this one
+    fn requires(self, language: Language) -> &'static [Helper] {
+        match (self, language) {
+            (Helper::Factorial, _) => &[Helper::Gamma],
+            (Helper::RoundDigits, Language::C) => &[Helper::Powi],
+            _ => &[],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Helper::Div => "arith_div",
+            Helper::Mod => "arith_mod",
+            Helper::IntDiv => "arith_int_div",
+            Helper::Sqr => "arith_sqr",
+            Helper::Cbrt => "arith_cbrt",
+            Helper::Root => "arith_root",
+            Helper::Powi => "arith_powi",
+            Helper::RoundDigits => "arith_round_digits",
+            Helper::Sign => "arith_sign",
+            Helper::Asinh => "arith_asinh",
+            Helper::Acosh => "arith_acosh",
+            Helper::Atanh => "arith_atanh",
+            Helper::Gamma => "arith_gamma",
+            Helper::Factorial => "arith_factorial",
+            Helper::Digamma => "arith_digamma",
+        }
+    }
+}
+
+/// Turns an expression into a standalone C or Rust function with the semantics of
+/// the Evaluator: the angle unit of the trigonometric functions, `div` as floor, the
+/// integer checks of `mod` and `div` and the same special functions. The evaluator
+/// errors, such as a division by zero, return NaN. User functions are inlined,
+/// stored variables become constants and the others are the parameters, sorted by name.
+pub struct CodeGenerator<'a> {
+    vars: &'a VariableManager,
+    language: Language,
+    angle_mode: AngleMode,
+    name: String,
+    helpers: BTreeSet<Helper>,
+}
+
+impl<'a> CodeGenerator<'a> {
+    pub fn new(vars: &'a VariableManager, language: Language) -> Self {
+        Self {
+            vars,
+            language,
+            angle_mode: AngleMode::default(),
+            name: "f".to_string(),
+            helpers: BTreeSet::new(),
+        }
+    }
+
+    /// Unit of the trigonometric functions. Degrees by default.
+    pub fn with_angle_mode(mut self, angle_mode: AngleMode) -> Self {
+        self.angle_mode = angle_mode;
+        self
+    }
+
+    /// Name of the generated function, f by default
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn generate(mut self, node: &ASTNode) -> Result<String, CompilerError> {
+        let inlined = node.inline_functions(self.vars)?;
+        let body = self.expression(&inlined, SUM)?;
+
+        // Stored variables are constants of the function, the others its parameters
+        let mut params = Vec::new();
+        let mut constants = BTreeMap::new();
+        for name in inlined.variables() {
+            let id = identifier(&name, self.language);
+            match self.vars.get_stored(&name) {
+                Some(Num::Integer(i)) => constants.insert(id, self.number(i as f64)),
+                Some(Num::Float(f)) => constants.insert(id, self.number(f)),
+                None => {
+                    params.push(id);
+                    None
+                }
+            };
+        }
+
+        let mut helpers = BTreeSet::new();
+        for helper in self.helpers.iter() {
+            helpers.insert(*helper);
+            helpers.extend(helper.requires(self.language));
+        }
+
+        let header = format!(
+            "Generated by arithmetic from: {}\nAngles are in {}. Evaluation errors, such as a division by zero, give NaN.",
+            node.infix(),
+            self.angle_mode
+        );
+        let function = Function {
+            header: &header,
+            name: &self.name,
+            params: &params,
+            constants: &constants,
+            helpers: &helpers,
+            body: &body,
+        };
+
+        Ok(match self.language {
+            Language::C => c::source(&function),
+            Language::Rust => rust::source(&function),
+        })
+    }
+
+    /// Source of the node, in parentheses when it binds weaker than min_precedence.
+    /// Only + - * and negation stay operators, everything else becomes a call.
+    fn expression(&mut self, node: &ASTNode, min_precedence: u8) -> Result<String, CompilerError> {
+        let (code, precedence) = match node {
+            ASTNode::Number(Num::Integer(i), _) => (self.number(*i as f64), ATOM),
+            ASTNode::Number(Num::Float(f), _) => (self.number(*f), ATOM),
+            ASTNode::Mantissa(value, span) => {
+                let value = value.parse::<f64>().map_err(|_| {
+                    CompilerError::Eval(EvaluationError::InvalidMantissa(span.clone()))
+                })?;
+                (self.number(value), ATOM)
+            }
+            ASTNode::Constant(TokenKind::Pi, _) => (self.constant("PI", PI), ATOM),
+            ASTNode::Constant(TokenKind::Euler, _) => {
+                (self.constant("E", std::f64::consts::E), ATOM)
+            }
+            ASTNode::Constant(_, span) => {
+                return Err(CompilerError::Eval(EvaluationError::InvalidConstant(
+                    span.clone(),
+                )))
+            }
+            ASTNode::Identifier(id, _) => (identifier(id, self.language), ATOM),
+            ASTNode::BinaryOp(left, op, right, span) => match op {
+                TokenKind::Plus | TokenKind::Minus | TokenKind::Multiply => {
+                    let precedence = match op {
+                        TokenKind::Multiply => PRODUCT,
+                        _ => SUM,
+                    };
+                    let code = format!(
+                        "{} {} {}",
+                        self.expression(left, precedence)?,
+                        op,
+                        self.expression(right, precedence + 1)?
+                    );
+                    (code, precedence)
+                }
+                _ => {
+                    let args = [self.expression(left, SUM)?, self.expression(right, SUM)?];
+                    let code = match op {
+                        TokenKind::Divide => self.helper_call(Helper::Div, &args),
+                        TokenKind::Mod => self.helper_call(Helper::Mod, &args),
+                        TokenKind::Div => self.helper_call(Helper::IntDiv, &args),
+                        TokenKind::Power => self.math("powf", &args),
+                        _ => {
+                            return Err(CompilerError::Eval(
+                                EvaluationError::UnsupportedBinaryOperator(
+                                    op.to_string(),
+                                    span.clone(),
+                                ),
+                            ))
+                        }
+                    };
+                    (code, ATOM)
+                }
+            },
+            ASTNode::UnaryOp(op, expr, span) => match op {
+                // The operand of a negation is never an other negation: --x is a decrement in C
+                TokenKind::Minus => (format!("-{}", self.expression(expr, ATOM)?), UNARY),
+                TokenKind::Plus => return self.expression(expr, min_precedence),
+                _ => {
+                    return Err(CompilerError::Eval(
+                        EvaluationError::UnsupportedUnaryOperator(op.to_string(), span.clone()),
+                    ))
+                }
+            },
+            ASTNode::FunctionCall(func, args, span) => {
+                resolve_function(self.vars, func, args.len(), span)?;
+                self.call(func, args)?
+            }
+            // Partial trees from error recovery can not be compiled
+            ASTNode::Error(span) => return Err(CompilerError::GenericError(span.clone())),
+        };
+
+        Ok(wrap(code, precedence, min_precedence))
+    }
+
+    /// A built-in function, written like its implementation in builtins.rs.
+    /// Returns the code and its precedence.
+    fn call(&mut self, func: &str, args: &[ASTNode]) -> Result<(String, u8), CompilerError> {
+        let code = match (func, args) {
+            ("sin" | "cos" | "tan", [x]) => {
+                let radians = self.radians(x)?;
+                self.math(func, &[radians])
+            }
+            ("cotan", [x]) => {
+                let radians = self.radians(x)?;
+                return Ok((format!("1.0 / {}", self.math("tan", &[radians])), PRODUCT));
+            }
+            ("arcsin" | "arccos" | "arctan" | "atan2", _) => {
+                let method = match func {
+                    "arcsin" => "asin",
+                    "arccos" => "acos",
+                    "arctan" => "atan",
+                    _ => "atan2",
+                };
+                let args = self.arguments(args)?;
+                return Ok(self.angle(self.math(method, &args), ATOM));
+            }
+            ("arccotan", [x]) => {
+                let x = self.expression(x, SUM)?;
+                let radians = format!("{} - {}", self.number(PI / 2.0), self.math("atan", &[x]));
+                return Ok(self.angle(radians, SUM));
+            }
+            ("arcsinh", _) => self.c_helper(Helper::Asinh, "asinh", args)?,
+            ("arccosh", _) => self.c_helper(Helper::Acosh, "acosh", args)?,
+            ("arctanh", _) => self.c_helper(Helper::Atanh, "atanh", args)?,
+            ("sqr", _) => self.c_helper(Helper::Sqr, "powi_2", args)?,
+            ("cbrt", _) => self.c_helper(Helper::Cbrt, "cbrt", args)?,
+            ("log", [x]) => {
+                let x = self.expression(x, SUM)?;
+                self.math("log10", &[x])
+            }
+            ("log", [x, base]) => {
+                let x = self.expression(x, SUM)?;
+                let base = self.expression(base, SUM)?;
+                let code = format!("{} / {}", self.math("ln", &[x]), self.math("ln", &[base]));
+                return Ok((code, PRODUCT));
+            }
+            ("pow", _) => {
+                let args = self.arguments(args)?;
+                self.math("powf", &args)
+            }
+            ("root", _) => self.helper(Helper::Root, args)?,
+            ("round", [_, _]) => self.helper(Helper::RoundDigits, args)?,
+            ("sign", _) => self.helper(Helper::Sign, args)?,
+            ("factorial", _) => self.helper(Helper::Factorial, args)?,
+            ("gamma", _) => self.helper(Helper::Gamma, args)?,
+            ("digamma", _) => self.helper(Helper::Digamma, args)?,
+            // min and max fold from infinity like the evaluator, so NaN arguments are skipped
+            ("min" | "max", _) => {
+                let start = self.number(match func {
+                    "min" => f64::INFINITY,
+                    _ => f64::NEG_INFINITY,
+                });
+                self.arguments(args)?
+                    .into_iter()
+                    .fold(start, |acc, arg| self.math(func, &[acc, arg]))
+            }
+            _ => {
+                let args = self.arguments(args)?;
+                self.math(func, &args)
+            }
+        };
+
+        Ok((code, ATOM))
+    }
+
+    fn arguments(&mut self, args: &[ASTNode]) -> Result<Vec<String>, CompilerError> {
+        args.iter().map(|arg| self.expression(arg, SUM)).collect()
+    }
+
+    /// Functions that the Rust standard library computes itself are helpers in C
+    fn c_helper(
+        &mut self,
+        helper: Helper,
+        method: &str,
+        args: &[ASTNode],
+    ) -> Result<String, CompilerError> {
+        match self.language {
+            Language::C => self.helper(helper, args),
+            Language::Rust => {
+                let args = self.arguments(args)?;
+                Ok(self.math(method, &args))
+            }
+        }
+    }
+
+    /// Argument of sin, cos, tan in radians
+    fn radians(&mut self, angle: &ASTNode) -> Result<String, CompilerError> {
+        Ok(match (self.angle_mode, self.language) {
+            (AngleMode::Radians, _) => self.expression(angle, SUM)?,
+            (AngleMode::Degrees, Language::Rust) => {
+                format!("f64::to_radians({})", self.expression(angle, SUM)?)
+            }
+            (AngleMode::Degrees, Language::C) => format!(
+                "{} * {}",
+                self.expression(angle, PRODUCT)?,
+                self.number(PI / 180.0)
+            ),
+            (AngleMode::Gradians, _) => format!(
+                "{} * {} / 200.0",
+                self.expression(angle, PRODUCT)?,
+                self.constant("PI", PI)
+            ),
+        })
+    }
+
+    /// Result of arcsin, arccos, ... in the angle unit
+    fn angle(&self, radians: String, precedence: u8) -> (String, u8) {
+        match (self.angle_mode, self.language) {
+            (AngleMode::Radians, _) => (radians, precedence),
+            (AngleMode::Degrees, Language::Rust) => (format!("f64::to_degrees({})", radians), ATOM),
+            (AngleMode::Degrees, Language::C) => {
+                let radians = wrap(radians, precedence, PRODUCT);
+                (
+                    format!("{} * {}", radians, self.number(180.0 / PI)),
+                    PRODUCT,
+                )
+            }
+            (AngleMode::Gradians, _) => {
+                let radians = wrap(radians, precedence, PRODUCT);
+                let code = format!("{} * 200.0 / {}", radians, self.constant("PI", PI));
+                (code, PRODUCT)
+            }
+        }
+    }
+
+    fn helper(&mut self, helper: Helper, args: &[ASTNode]) -> Result<String, CompilerError> {
+        let args = self.arguments(args)?;
+        Ok(self.helper_call(helper, &args))
+    }
+
+    fn helper_call(&mut self, helper: Helper, args: &[String]) -> String {
+        self.helpers.insert(helper);
+        format!("{}({})", helper.name(), args.join(", "))
+    }
+
+    /// Call of a function of math.h or of f64, named as in Rust
+    fn math(&self, method: &str, args: &[String]) -> String {
+        match self.language {
+            Language::C => c::math(method, args),
+            Language::Rust => rust::math(method, args),
+        }
+    }
+
+    fn number(&self, value: f64) -> String {
+        match self.language {
+            Language::C => c::number(value),
+            Language::Rust => rust::number(value),
+        }
+    }
+
+    fn constant(&self, name: &str, value: f64) -> String {
+        match self.language {
+            Language::C => c::number(value),
+            Language::Rust => format!("std::f64::consts::{}", name),
+        }
+    }
+}
+
+/// Parts of the generated source
+pub struct Function<'a> {
+    /// Comment above the function
+    pub header: &'a str,
+    pub name: &'a str,
+    pub params: &'a [String],
+    /// Stored variables and their values
+    pub constants: &'a BTreeMap<String, String>,
+    pub helpers: &'a BTreeSet<Helper>,
+    pub body: &'a str,
+}
+
+/// The code in parentheses when it binds weaker than min_precedence
+fn wrap(code: String, precedence: u8, min_precedence: u8) -> String {
+    if precedence < min_precedence {
+        format!("({})", code)
+    } else {
+        code
+    }
+}
+
+/// Variable names are case insensitive. Keywords of the target get a trailing underscore.
+pub fn identifier(id: &str, language: Language) -> String {
+    let name = id.to_lowercase();
+    match is_reserved(&name, language) {
+        true => format!("{}_", name),
+        false => name,
+    }
+}
+
+/// Whether the name can be given to the generated function
+pub fn is_valid_name(name: &str, language: Language) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !is_reserved(name, language)
+}
+
+/// Keywords of the target and the names of the helpers
+fn is_reserved(name: &str, language: Language) -> bool {
+    let keywords = match language {
+        Language::C => c::KEYWORDS,
+        Language::Rust => rust::KEYWORDS,
+    };
+    keywords.contains(&name) || name.starts_with("arith_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{eval::Evaluator, resolver::MapOnlyResolver};
+    use crate::utils::print::lex_parse_input;
+    use std::fs;
+    use std::process::Command;
+
+    /// Expression and the values of its variables, sorted by name like the parameters
+    const CASES: &[(&str, &[&[f64]])] = &[
+        // Degrees
+        (
+            "sin(x) + cos(2 * x) * tan(x / 3) + arcsin(x / 200)",
+            &[&[30.0], &[45.0], &[-120.0]],
+        ),
+        // Floor of the quotient for negative operands
+        (
+            "x div y",
+            &[
+                &[7.0, 2.0],
+                &[-7.0, 2.0],
+                &[7.0, -2.0],
+                &[-7.0, -2.0],
+                &[6.0, -3.0],
+            ],
+        ),
+        // Integer operands only, the sign follows the dividend
+        (
+            "x mod y",
+            &[
+                &[7.5, 2.0],
+                &[7.0, 2.5],
+                &[-7.0, 3.0],
+                &[7.0, -3.0],
+                &[5.0, 0.0],
+            ],
+        ),
+    ];
+
+    /// Result of the Evaluator, NaN where it fails
+    fn evaluate(input: &str, args: &[f64]) -> f64 {
+        let ast = lex_parse_input(input).unwrap();
+        let mut vars = VariableManager::new();
+        for (name, &value) in ast.variables().iter().zip(args) {
+            vars.set(name.clone(), Num::Float(value));
+        }
+        let mut resolver = MapOnlyResolver;
+        Evaluator::new(&mut vars, &mut resolver)
+            .evaluate(&ast)
+            .unwrap_or(f64::NAN)
+    }
+
+    /// Compiles the generated function with a main that calls it on every set of
+    /// arguments and returns the printed results
+    fn run_generated(language: Language, input: &str, args: &[&[f64]]) -> Vec<f64> {
+        let vars = VariableManager::new();
+        let ast = lex_parse_input(input).unwrap();
+        let mut program = CodeGenerator::new(&vars, language).generate(&ast).unwrap();
+        let calls: Vec<String> = args
+            .iter()
+            .map(|args| {
+                let args: Vec<String> = args.iter().map(|arg| format!("{:?}", arg)).collect();
+                format!("f({})", args.join(", "))
+            })
+            .collect();
+
+        let dir = std::env::temp_dir().join(format!(
+            "arithmetic-codegen-{}-{}-{}",
+            language,
+            std::process::id(),
+            input.len()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("main.out");
+
+        let status = match language {
+            Language::Rust => {
+                let prints: String = calls
+                    .iter()
+                    .map(|call| format!("    println!(\"{{:?}}\", {});\n", call))
+                    .collect();
+                program.push_str(&format!("\nfn main() {{\n{}}}\n", prints));
+                let source = dir.join("main.rs");
+                fs::write(&source, &program).unwrap();
+                Command::new("rustc")
+                    .args(["-A", "warnings"])
+                    .arg(&source)
+                    .arg("-o")
+                    .arg(&binary)
+                    .status()
+            }
+            Language::C => {
+                let prints: String = calls
+                    .iter()
+                    .map(|call| format!("    printf(\"%.17g\\n\", {});\n", call))
+                    .collect();
+                program = format!("#include <stdio.h>\n{}", program);
+                program.push_str(&format!(
+                    "\nint main(void) {{\n{}    return 0;\n}}\n",
+                    prints
+                ));
+                let source = dir.join("main.c");
+                fs::write(&source, &program).unwrap();
+                Command::new("cc")
+                    .arg(&source)
+                    .arg("-o")
+                    .arg(&binary)
+                    .arg("-lm")
+                    .status()
+            }
+        };
+        assert!(
+            status.unwrap().success(),
+            "{} of {} does not compile",
+            language,
+            input
+        );
+
+        let output = Command::new(&binary).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| match line.to_lowercase().contains("nan") {
+                true => f64::NAN,
+                false => line.parse().unwrap(),
+            })
+            .collect()
+    }
+
+    fn assert_same_as_evaluator(language: Language) {
+        for (input, args) in CASES.iter() {
+            let results = run_generated(language, input, args);
+            assert_eq!(results.len(), args.len());
+
+            for (result, args) in results.iter().zip(args.iter()) {
+                let value = evaluate(input, args);
+                match value.is_nan() {
+                    true => assert!(
+                        result.is_nan(),
+                        "{} at {:?}: {} instead of NaN",
+                        input,
+                        args,
+                        result
+                    ),
+                    false => assert!(
+                        (result - value).abs() <= 1e-12 * value.abs().max(1.0),
+                        "{} at {:?}: {} instead of {}",
+                        input,
+                        args,
+                        result,
+                        value
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_rust_matches_the_evaluator() {
+        assert_same_as_evaluator(Language::Rust);
+    }
+
+    #[test]
+    fn generated_c_matches_the_evaluator() {
+        assert_same_as_evaluator(Language::C);
+    }
+}
@@ -0,0 +1,207 @@
+use super::{Function, Helper};
+
+/// Names that can not be used as parameters
+pub const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Rust source with the function and the helpers after it
+pub fn source(function: &Function) -> String {
+    let mut out = String::new();
+    for line in function.header.lines() {
+        out.push_str(&format!("// {}\n", line));
+    }
+
+    let params = function
+        .params
+        .iter()
+        .map(|param| format!("{}: f64", param))
+        .collect::<Vec<String>>()
+        .join(", ");
+    out.push_str(&format!("pub fn {}({}) -> f64 {{\n", function.name, params));
+    for (name, value) in function.constants.iter() {
+        out.push_str(&format!("    let {}: f64 = {};\n", name, value));
+    }
+    out.push_str(&format!("    {}\n}}\n", function.body));
+
+    for helper in function.helpers.iter() {
+        out.push('\n');
+        out.push_str(helper_source(*helper));
+    }
+
+    out
+}
+
+/// Call of an f64 method. powi_2 is the square.
+pub fn math(method: &str, args: &[String]) -> String {
+    match method {
+        "powi_2" => format!("f64::powi({}, 2)", args.join(", ")),
+        _ => format!("f64::{}({})", method, args.join(", ")),
+    }
+}
+
+/// Literal that reads back as the same f64
+pub fn number(value: f64) -> String {
+    match value {
+        f64::INFINITY => "f64::INFINITY".to_string(),
+        f64::NEG_INFINITY => "f64::NEG_INFINITY".to_string(),
+        _ if value.is_nan() => "f64::NAN".to_string(),
+        _ => format!("{:?}", value),
+    }
+}
+
+/// The same computations as builtins.rs
+fn helper_source(helper: Helper) -> &'static str {
+    match helper {
+        Helper::Div => {
+            "/// `/`, the evaluator fails on a zero divisor
+fn arith_div(a: f64, b: f64) -> f64 {
+    if b == 0.0 {
+        f64::NAN
+    } else {
+        a / b
+    }
+}
+"
+        }
+        Helper::Mod => {
+            "/// `mod`, integer operands only
+fn arith_mod(a: f64, b: f64) -> f64 {
+    if a.fract() != 0.0 || b.fract() != 0.0 {
+        f64::NAN
+    } else {
+        a % b
+    }
+}
+"
+        }
+        Helper::IntDiv => {
+            "/// `div`, floor of the quotient of integer operands
+fn arith_int_div(a: f64, b: f64) -> f64 {
+    if a.fract() != 0.0 || b.fract() != 0.0 || b == 0.0 {
+        f64::NAN
+    } else {
+        (a / b).floor()
+    }
+}
+"
+        }
+        Helper::Root => {
+            "/// n-th root, odd roots of negative numbers are real
+fn arith_root(n: f64, x: f64) -> f64 {
+    if x < 0.0 && n.fract() == 0.0 && n % 2.0 != 0.0 {
+        -(-x).powf(1.0 / n)
+    } else {
+        x.powf(1.0 / n)
+    }
+}
+"
+        }
+        Helper::RoundDigits => {
+            "fn arith_round_digits(x: f64, digits: f64) -> f64 {
+    let factor = 10f64.powi(digits as i32);
+    (x * factor).round() / factor
+}
+"
+        }
+        Helper::Sign => {
+            "/// -1, 0 or 1
+fn arith_sign(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x.signum()
+    }
+}
+"
+        }
+        Helper::Gamma => {
+            "/// Gamma function: Lanczos approximation with the reflection formula for x < 0.5
+fn arith_gamma(x: f64) -> f64 {
+    use std::f64::consts::PI;
+    const COEFFICIENTS: [f64; 9] = [
+        0.9999999999998099,
+        676.5203681218851,
+        -1259.1392167224028,
+        771.3234287776531,
+        -176.6150291621406,
+        12.507343278686905,
+        -0.13857109526572012,
+        9.984369578019572e-6,
+        1.5056327351493116e-7,
+    ];
+
+    // Poles at 0, -1, -2, ...
+    if x <= 0.0 && x.fract() == 0.0 {
+        return f64::NAN;
+    }
+    if x < 0.5 {
+        return PI / ((PI * x).sin() * arith_gamma(1.0 - x));
+    }
+
+    let x = x - 1.0;
+    let t = x + 7.0 + 0.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64));
+
+    (2.0 * PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * series
+}
+"
+        }
+        Helper::Factorial => {
+            "/// n! for integers, gamma(n + 1) otherwise
+fn arith_factorial(n: f64) -> f64 {
+    if n.fract() != 0.0 {
+        return arith_gamma(n + 1.0);
+    }
+    if n < 0.0 {
+        return f64::NAN;
+    }
+
+    (2..=(n.min(171.0) as u64)).fold(1.0, |acc, i| acc * i as f64)
+}
+"
+        }
+        Helper::Digamma => {
+            "/// Digamma function: recurrence up to x >= 6, then the asymptotic series
+fn arith_digamma(x: f64) -> f64 {
+    use std::f64::consts::PI;
+
+    // Poles at 0, -1, -2, ...
+    if x <= 0.0 && x.fract() == 0.0 {
+        return f64::NAN;
+    }
+    if x < 0.0 {
+        return arith_digamma(1.0 - x) - PI / (PI * x).tan();
+    }
+
+    let mut x = x;
+    let mut result = 0.0;
+    while x < 6.0 {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+
+    let inv2 = 1.0 / (x * x);
+    result + x.ln()
+        - 0.5 / x
+        - inv2 * (1.0 / 12.0 - inv2 * (1.0 / 120.0 - inv2 * (1.0 / 252.0 - inv2 / 240.0)))
+}
+"
+        }
+        // Computed by f64 itself in Rust
+        Helper::Sqr
+        | Helper::Cbrt
+        | Helper::Powi
+        | Helper::Asinh
+        | Helper::Acosh
+        | Helper::Atanh => "",
+    }
+}
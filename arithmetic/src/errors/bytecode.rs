@@ -0,0 +1,65 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum BytecodeError {
+    /// The file could not be read or written
+    Io(String),
+    /// The file is not bytecode written by `compile`
+    InvalidFormat(String),
+    UnsupportedVersion(u32),
+    /// Index of an instruction with an operand out of range
+    InvalidInstruction(usize),
+    /// The code does not leave exactly one value on the stack
+    StackMismatch,
+}
+
+impl BytecodeError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            BytecodeError::Io(_) => "E0601",
+            BytecodeError::InvalidFormat(_) => "E0602",
+            BytecodeError::UnsupportedVersion(_) => "E0603",
+            BytecodeError::InvalidInstruction(_) => "E0604",
+            BytecodeError::StackMismatch => "E0605",
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            BytecodeError::Io(_) => Some("check the path of the bytecode file"),
+            BytecodeError::InvalidFormat(_) | BytecodeError::UnsupportedVersion(_) => {
+                Some("compile the expression again with `compile --output <path>`")
+            }
+            BytecodeError::InvalidInstruction(_) | BytecodeError::StackMismatch => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BytecodeError::Io(message) => write!(f, "Bytecode file error: {}.", message),
+            BytecodeError::InvalidFormat(message) => {
+                write!(f, "Invalid bytecode file: {}.", message)
+            }
+            BytecodeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported bytecode version {}.", version)
+            }
+            BytecodeError::InvalidInstruction(index) => {
+                write!(
+                    f,
+                    "Invalid bytecode: instruction {:04} has an operand out of range.",
+                    index
+                )
+            }
+            BytecodeError::StackMismatch => {
+                write!(
+                    f,
+                    "Invalid bytecode: the code does not leave exactly one value on the stack."
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BytecodeError {}
@@ -0,0 +1,52 @@
+use crate::lexer::span::TextSpan;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodegenError {
+    /// The generated source could not be written
+    Io(String),
+    /// The function has no translation for the target
+    Unsupported(String, TextSpan),
+}
+
+impl CodegenError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            CodegenError::Io(_) => "E0701",
+            CodegenError::Unsupported(_, _) => "E0702",
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            CodegenError::Io(_) => Some("check that the output directory is writable"),
+            CodegenError::Unsupported(_, _) => {
+                Some("`codegen` translates every function to C and Rust")
+            }
+        }
+    }
+
+    /// Location in the source, if the error has one
+    pub fn span(&self) -> Option<&TextSpan> {
+        match self {
+            CodegenError::Io(_) => None,
+            CodegenError::Unsupported(_, span) => Some(span),
+        }
+    }
+}
+
+impl std::fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CodegenError::Io(message) => write!(f, "Code generation error: {}.", message),
+            CodegenError::Unsupported(function, _) => write!(
+                f,
+                "The assembly backend does not support the function '{}'.",
+                function
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
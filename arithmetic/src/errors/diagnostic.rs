@@ -0,0 +1,210 @@
+use super::{eval::EvaluationError, CompilerError, TextSpan};
+use std::env;
+use std::io::{self, IsTerminal};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or a warning together with what is needed to point at the source:
+///
+/// ```text
+/// error[E0303]: Division by zero
+///  --> 1:3
+///   |
+/// 1 | 1 / (2 - 2)
+///   |   ^
+///   = help: the right operand evaluates to 0
+/// ```
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<TextSpan>,
+    pub help: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn error(error: &CompilerError) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.message(),
+            span: error.span().cloned(),
+            help: error.help(),
+        }
+    }
+
+    /// Domain problems reported in lenient mode
+    pub fn warning(warning: &EvaluationError) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code: warning.code(),
+            message: warning.message(),
+            span: Some(warning.span().clone()),
+            help: warning.help(),
+        }
+    }
+
+    /// Renders the diagnostic with the source line and a caret under the span
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}{}", style, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let (label, style) = match self.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+
+        let mut out = format!(
+            "{}{}\n",
+            paint(style, &format!("{}[{}]", label, self.code)),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+
+        let mut gutter = String::new();
+        if let Some(span) = &self.span {
+            let line_number = span.line.to_string();
+            let line_text = source
+                .lines()
+                .nth(span.line.saturating_sub(1))
+                .unwrap_or("");
+            gutter = " ".repeat(line_number.len());
+
+            // Tabs are kept so the caret lines up with the source
+            let indent: String = line_text
+                .chars()
+                .take(span.column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let available = line_text.chars().count().saturating_sub(span.column);
+            let width = span.literal.chars().count().min(available).max(1);
+
+            out.push_str(&format!(
+                "{}{} {}:{}\n",
+                gutter,
+                paint(BLUE, "-->"),
+                span.line,
+                span.column + 1
+            ));
+            out.push_str(&format!("{} {}\n", gutter, paint(BLUE, "|")));
+            out.push_str(&format!(
+                "{} {} {}\n",
+                paint(BLUE, &line_number),
+                paint(BLUE, "|"),
+                line_text
+            ));
+            out.push_str(&format!(
+                "{} {} {}{}\n",
+                gutter,
+                paint(BLUE, "|"),
+                indent,
+                paint(style, &"^".repeat(width))
+            ));
+        }
+
+        if let Some(help) = self.help {
+            out.push_str(&format!("{} {} help: {}\n", gutter, paint(BLUE, "="), help));
+        }
+
+        out
+    }
+}
+
+/// Colours are used when stderr is a terminal and NO_COLOR is not set
+pub fn color_enabled() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{eval::Evaluator, resolver::MapOnlyResolver, var::VariableManager};
+    use crate::utils::print::{lex_parse_input, lex_parse_program};
+
+    fn evaluation_error(input: &str) -> CompilerError {
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let ast = lex_parse_input(input).unwrap();
+        Evaluator::new(&mut vars, &mut resolver)
+            .evaluate(&ast)
+            .unwrap_err()
+    }
+
+    #[test]
+    fn the_documented_layout() {
+        let source = "1 / (2 - 2)";
+        let rendered = Diagnostic::error(&evaluation_error(source)).render(source, false);
+        assert_eq!(
+            rendered,
+            "error[E0303]: Division by zero\n \
+             --> 1:3\n  \
+             |\n\
+             1 | 1 / (2 - 2)\n  \
+             |   ^\n  \
+             = help: the right operand evaluates to 0\n"
+        );
+    }
+
+    #[test]
+    fn the_caret_is_on_the_line_of_the_error() {
+        let source = "a := 1\nb := (2\nc := 3";
+        let error = lex_parse_program(source).unwrap_err();
+        let diagnostic = Diagnostic::error(error.errors()[0]);
+        assert!(diagnostic.code.starts_with("E02"));
+        let rendered = diagnostic.render(source, false);
+        assert!(rendered.contains("\n2 | b := (2\n"), "{}", rendered);
+        assert!(!rendered.contains("a := 1"), "{}", rendered);
+    }
+
+    #[test]
+    fn warnings_and_colours() {
+        let span = TextSpan::new(4, 8, "sqrt".to_string(), 1, 4);
+        let warning = EvaluationError::DomainError("sqrt".to_string(), -1.0, span);
+        let diagnostic = Diagnostic::warning(&warning);
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.code, "E0311");
+
+        let plain = diagnostic.render("1 + sqrt(-1)", false);
+        assert!(plain.starts_with("warning[E0311]: "), "{}", plain);
+        assert!(
+            plain.contains(" --> 1:5\n  |\n1 | 1 + sqrt(-1)\n  |     ^^^^\n"),
+            "{}",
+            plain
+        );
+        assert!(!plain.contains('\x1b'));
+
+        let coloured = diagnostic.render("1 + sqrt(-1)", true);
+        assert!(coloured.starts_with(&format!("{}warning[E0311]{}", YELLOW, RESET)));
+        assert!(coloured.contains(&format!("{}^^^^{}", YELLOW, RESET)));
+    }
+
+    #[test]
+    fn errors_without_a_span() {
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            code: "E0001",
+            message: "Something went wrong".to_string(),
+            span: None,
+            help: None,
+        };
+        assert_eq!(
+            diagnostic.render("1 + 1", false),
+            "error[E0001]: Something went wrong\n"
+        );
+    }
+}
@@ -1,71 +1,151 @@
+use crate::lexer::span::TextSpan;
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EvaluationError {
-    UnsupportedBinaryOperator(String, usize, usize),
-    UnsupportedUnaryOperator(String, usize, usize),
-    DivisionByZero(usize, usize),
-    IntegerOperatorWithFloatOperands(usize, usize),
-    UnsupportedFunction(String, usize),
-    InvalidConstant(usize, usize),
-    InvalidMantissa(usize, usize),
+    UnsupportedBinaryOperator(String, TextSpan),
+    UnsupportedUnaryOperator(String, TextSpan),
+    DivisionByZero(TextSpan),
+    IntegerOperatorWithFloatOperands(TextSpan),
+    InvalidConstant(TextSpan),
+    InvalidMantissa(TextSpan),
+    UndefinedVariable(String, TextSpan),
+    UndefinedFunction(String, TextSpan),
+    /// Function, expected number of arguments ("2", "1 to 2", "at least 2"), found
+    ArityMismatch(String, String, usize, TextSpan),
+    RecursionLimit(String, TextSpan),
+    DomainError(String, f64, TextSpan),
+    Overflow(String, f64, TextSpan),
+    /// Built-in function without a symbolic derivative
+    NoDerivative(String, TextSpan),
+    /// lhs = rhs where a value is expected, the span is the one of `=`
+    EquationHasNoValue(TextSpan),
 }
 
-// Implement Display for EvaluationError
-impl fmt::Display for EvaluationError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl EvaluationError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvaluationError::UnsupportedBinaryOperator(_, _) => "E0301",
+            EvaluationError::UnsupportedUnaryOperator(_, _) => "E0302",
+            EvaluationError::DivisionByZero(_) => "E0303",
+            EvaluationError::IntegerOperatorWithFloatOperands(_) => "E0304",
+            EvaluationError::InvalidConstant(_) => "E0305",
+            EvaluationError::InvalidMantissa(_) => "E0306",
+            EvaluationError::UndefinedVariable(_, _) => "E0307",
+            EvaluationError::UndefinedFunction(_, _) => "E0308",
+            EvaluationError::ArityMismatch(_, _, _, _) => "E0309",
+            EvaluationError::RecursionLimit(_, _) => "E0310",
+            EvaluationError::DomainError(_, _, _) => "E0311",
+            EvaluationError::Overflow(_, _, _) => "E0312",
+            EvaluationError::NoDerivative(_, _) => "E0313",
+            EvaluationError::EquationHasNoValue(_) => "E0314",
+        }
+    }
+
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            EvaluationError::UnsupportedBinaryOperator(_, span)
+            | EvaluationError::UnsupportedUnaryOperator(_, span)
+            | EvaluationError::DivisionByZero(span)
+            | EvaluationError::IntegerOperatorWithFloatOperands(span)
+            | EvaluationError::InvalidConstant(span)
+            | EvaluationError::InvalidMantissa(span)
+            | EvaluationError::UndefinedVariable(_, span)
+            | EvaluationError::UndefinedFunction(_, span)
+            | EvaluationError::ArityMismatch(_, _, _, span)
+            | EvaluationError::RecursionLimit(_, span)
+            | EvaluationError::DomainError(_, _, span)
+            | EvaluationError::Overflow(_, _, span)
+            | EvaluationError::NoDerivative(_, span)
+            | EvaluationError::EquationHasNoValue(span) => span,
+        }
+    }
+
+    /// Description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            EvaluationError::UnsupportedBinaryOperator(op, _) => {
+                format!("Unsupported binary operator '{}'", op)
+            }
+            EvaluationError::UnsupportedUnaryOperator(op, _) => {
+                format!("Unsupported unary operator '{}'", op)
+            }
+            EvaluationError::DivisionByZero(_) => "Division by zero".to_string(),
+            EvaluationError::IntegerOperatorWithFloatOperands(_) => {
+                "Integer operator used with float operands".to_string()
+            }
+            EvaluationError::InvalidConstant(_) => "Invalid constant".to_string(),
+            EvaluationError::InvalidMantissa(_) => "Invalid mantissa".to_string(),
+            EvaluationError::UndefinedVariable(name, _) => {
+                format!("Undefined variable '{}'", name)
+            }
+            EvaluationError::UndefinedFunction(name, _) => {
+                format!("Undefined function '{}'", name)
+            }
+            EvaluationError::ArityMismatch(name, expected, found, _) => format!(
+                "Function '{}' expects {} argument(s) but got {}",
+                name, expected, found
+            ),
+            EvaluationError::RecursionLimit(name, _) => {
+                format!("Recursion limit reached in function '{}'", name)
+            }
+            EvaluationError::DomainError(name, arg, _) => {
+                format!("'{}' is not defined for {}", name, arg)
+            }
+            EvaluationError::Overflow(name, arg, _) => format!("'{}' overflows for {}", name, arg),
+            EvaluationError::NoDerivative(name, _) => {
+                format!("The derivative of '{}' is not supported", name)
+            }
+            EvaluationError::EquationHasNoValue(_) => "An equation has no value".to_string(),
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
         match self {
-            EvaluationError::UnsupportedBinaryOperator(op, line, pos) => {
-                write!(
-                    f,
-                    "Runtime Error: Unsupported binary operator '{}' at line {}, position {}.",
-                    op, line, pos
-                )
-            }
-            EvaluationError::UnsupportedUnaryOperator(op, line, pos) => {
-                write!(
-                    f,
-                    "Runtime Error: Unsupported unary operator '{}' at line {}, position {}.",
-                    op, line, pos
-                )
-            }
-            EvaluationError::DivisionByZero(line, pos) => {
-                write!(
-                    f,
-                    "Runtime Error: Division by zero at line {}, position {}.",
-                    line, pos
-                )
-            }
-            EvaluationError::IntegerOperatorWithFloatOperands(line, pos) => {
-                write!(
-                    f,
-                    "Runtime Error: Integer operator used with float operands at line {}, position {}.",
-                    line, pos
-                )
-            }
-            EvaluationError::UnsupportedFunction(func, line) => {
-                write!(
-                    f,
-                    "Runtime Error: Unsupported function '{}' at line {}.",
-                    func, line
-                )
-            }
-            EvaluationError::InvalidConstant(line, pos) => {
-                write!(
-                    f,
-                    "Runtime Error: Invalid constant at line {}, position {}.",
-                    line, pos
-                )
-            }
-            EvaluationError::InvalidMantissa(line, pos) => {
-                write!(
-                    f,
-                    "Runtime Error: Invalid mantissa at line {}, position {}.",
-                    line, pos
-                )
+            EvaluationError::DivisionByZero(_) => Some("the right operand evaluates to 0"),
+            EvaluationError::IntegerOperatorWithFloatOperands(_) => {
+                Some("mod and div only accept whole numbers")
+            }
+            EvaluationError::UndefinedVariable(_, _) => {
+                Some("assign it first (x := 1), pass --var x=1 or set ARITH_X")
+            }
+            EvaluationError::UndefinedFunction(_, _) => {
+                Some("define it first, for example f(x) := x^2")
+            }
+            EvaluationError::ArityMismatch(_, _, _, _) => {
+                Some("check the parameters of the definition")
+            }
+            EvaluationError::RecursionLimit(_, _) => {
+                Some("a function can not call itself, directly or through other functions")
             }
+            EvaluationError::DomainError(_, _, _) => {
+                Some("the argument is outside of the domain of the function")
+            }
+            EvaluationError::Overflow(_, _, _) => {
+                Some("the result is too large for a 64-bit float")
+            }
+            EvaluationError::EquationHasNoValue(_) => {
+                Some("use solve to find where both sides are equal, := assigns a variable")
+            }
+            _ => None,
         }
     }
 }
 
+// Implement Display for EvaluationError
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "Runtime Error: {} at line {}, position {}.",
+            self.message(),
+            span.line,
+            span.column + 1
+        )
+    }
+}
+
 impl std::error::Error for EvaluationError {}
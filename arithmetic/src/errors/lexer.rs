@@ -1,39 +1,69 @@
+use super::TextSpan;
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LexerError {
-    InvalidNumber(String, usize, usize),
-    InvalidCharacter(char, usize, usize),
-    InvalidIdentifier(usize, usize),
+    InvalidNumber(String, TextSpan),
+    InvalidCharacter(char, TextSpan),
+    InvalidIdentifier(TextSpan),
 }
 
-// Implement Display for LexerError
-impl fmt::Display for LexerError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl LexerError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerError::InvalidNumber(_, _) => "E0101",
+            LexerError::InvalidCharacter(_, _) => "E0102",
+            LexerError::InvalidIdentifier(_) => "E0103",
+        }
+    }
+
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            LexerError::InvalidNumber(_, span)
+            | LexerError::InvalidCharacter(_, span)
+            | LexerError::InvalidIdentifier(span) => span,
+        }
+    }
+
+    /// Description of the error without its location
+    pub fn message(&self) -> String {
         match self {
-            LexerError::InvalidNumber(num, line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Invalid number '{}' at line {}, position {}.",
-                    num, line, pos
-                )
+            LexerError::InvalidNumber(num, _) => format!("Invalid number '{}'", num),
+            LexerError::InvalidCharacter(ch, _) => format!("Invalid character '{}'", ch),
+            LexerError::InvalidIdentifier(_) => "Invalid identifier".to_string(),
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            LexerError::InvalidNumber(_, _) => {
+                Some("scientific notation needs exponent digits, for example 1.5e3")
             }
-            LexerError::InvalidCharacter(ch, line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Invalid character '{}' at line {}, position {}.",
-                    ch, line, pos
-                )
+            LexerError::InvalidCharacter(_, _) => {
+                Some("only numbers, names, operators, parentheses, ',' and ';' are allowed")
             }
-            LexerError::InvalidIdentifier(line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Invalid identifier at line {}, position {}.",
-                    line, pos
-                )
+            LexerError::InvalidIdentifier(_) => {
+                Some("write the multiplication explicitly, for example 2 * x")
             }
         }
     }
 }
 
+// Implement Display for LexerError
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "Syntax Error: {} at line {}, position {}.",
+            self.message(),
+            span.line,
+            span.column + 1
+        )
+    }
+}
+
 impl std::error::Error for LexerError {}
@@ -1,10 +1,15 @@
+pub mod bytecode;
+pub mod codegen;
+pub mod diagnostic;
 pub mod eval;
 pub mod lexer;
 pub mod parser;
 pub mod plot;
 pub mod root;
 
-use crate::lexer::token::TokenKind;
+use crate::lexer::{span::TextSpan, token::TokenKind};
+use bytecode::BytecodeError;
+use codegen::CodegenError;
 use eval::EvaluationError;
 use lexer::LexerError;
 use parser::ParserError;
@@ -12,14 +17,112 @@ use plot::PlottingError;
 use root::RootFinderError;
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CompilerError {
     Lex(LexerError),
     Parse(ParserError),
     Eval(EvaluationError),
     Root(RootFinderError),
     Plot(PlottingError),
-    GenericError(usize, usize),
+    Bytecode(BytecodeError),
+    Codegen(CodegenError),
+    GenericError(TextSpan),
+    /// Every syntax error of the input, in source order. Never empty.
+    Multiple(Vec<CompilerError>),
+}
+
+impl CompilerError {
+    /// Process exit code for each error category, used by the command line interface
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CompilerError::GenericError(_) => 1,
+            CompilerError::Lex(_) => 3,
+            CompilerError::Parse(_) => 4,
+            CompilerError::Eval(_) => 5,
+            CompilerError::Root(_) => 6,
+            CompilerError::Plot(_) => 7,
+            CompilerError::Bytecode(_) => 8,
+            CompilerError::Codegen(_) => 9,
+            CompilerError::Multiple(errors) => errors[0].exit_code(),
+        }
+    }
+
+    /// One error for a single entry, Multiple otherwise. None when there are no errors.
+    pub fn from_errors(mut errors: Vec<CompilerError>) -> Option<Self> {
+        match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(CompilerError::Multiple(errors)),
+        }
+    }
+
+    /// The errors to report: the list of Multiple or the error itself
+    pub fn errors(&self) -> Vec<&CompilerError> {
+        match self {
+            CompilerError::Multiple(errors) => errors.iter().collect(),
+            _ => vec![self],
+        }
+    }
+
+    /// Stable error code: E00xx generic, E01xx lexer, E02xx parser, E03xx evaluation,
+    /// E04xx roots, E05xx plots, E06xx bytecode, E07xx code generation
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompilerError::Lex(err) => err.code(),
+            CompilerError::Parse(err) => err.code(),
+            CompilerError::Eval(err) => err.code(),
+            CompilerError::Root(err) => err.code(),
+            CompilerError::Plot(err) => err.code(),
+            CompilerError::Bytecode(err) => err.code(),
+            CompilerError::Codegen(err) => err.code(),
+            CompilerError::GenericError(_) => "E0001",
+            CompilerError::Multiple(errors) => errors[0].code(),
+        }
+    }
+
+    /// Location in the source. Root finding, plotting, bytecode and most code
+    /// generation errors have none.
+    pub fn span(&self) -> Option<&TextSpan> {
+        match self {
+            CompilerError::Lex(err) => Some(err.span()),
+            CompilerError::Parse(err) => Some(err.span()),
+            CompilerError::Eval(err) => Some(err.span()),
+            CompilerError::Codegen(err) => err.span(),
+            CompilerError::Root(_) | CompilerError::Plot(_) | CompilerError::Bytecode(_) => None,
+            CompilerError::GenericError(span) => Some(span),
+            CompilerError::Multiple(errors) => errors[0].span(),
+        }
+    }
+
+    /// Description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            CompilerError::Lex(err) => err.message(),
+            CompilerError::Parse(err) => err.message(),
+            CompilerError::Eval(err) => err.message(),
+            CompilerError::Root(err) => err.to_string(),
+            CompilerError::Plot(err) => err.to_string(),
+            CompilerError::Bytecode(err) => err.to_string(),
+            CompilerError::Codegen(err) => err.to_string(),
+            CompilerError::GenericError(_) => "Unexpected error occurred".to_string(),
+            CompilerError::Multiple(errors) => errors[0].message(),
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            CompilerError::Lex(err) => err.help(),
+            CompilerError::Parse(err) => err.help(),
+            CompilerError::Eval(err) => err.help(),
+            CompilerError::Root(err) => err.help(),
+            CompilerError::Plot(err) => err.help(),
+            CompilerError::Bytecode(err) => err.help(),
+            CompilerError::Codegen(err) => err.help(),
+            CompilerError::GenericError(_) => None,
+            CompilerError::Multiple(errors) => errors[0].help(),
+        }
+    }
 }
 
 // Implement Display for CompilerError
@@ -31,14 +134,21 @@ impl fmt::Display for CompilerError {
             CompilerError::Eval(err) => write!(f, "{}", err),
             CompilerError::Root(err) => write!(f, "{}", err),
             CompilerError::Plot(err) => write!(f, "{}", err),
+            CompilerError::Bytecode(err) => write!(f, "{}", err),
+            CompilerError::Codegen(err) => write!(f, "{}", err),
 
-            CompilerError::GenericError(line, pos) => {
+            CompilerError::GenericError(span) => {
                 write!(
                     f,
                     "Unexpected error occurred at line {}, position {}.",
-                    line, pos
+                    span.line,
+                    span.column + 1
                 )
             }
+            CompilerError::Multiple(errors) => {
+                let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
         }
     }
 }
@@ -1,48 +1,88 @@
-use super::TokenKind;
+use super::{TextSpan, TokenKind};
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParserError {
-    UnexpectedToken(TokenKind, usize, usize),
-    MissingLParen(usize, usize),
-    MissingRParen(usize, usize),
-    MissingOperator(usize, usize),
+    UnexpectedToken(TokenKind, TextSpan),
+    MissingLParen(TextSpan),
+    MissingRParen(TextSpan),
+    MissingOperator(TextSpan),
+    InvalidParameter(String, TextSpan),
+    InvalidArgumentCount(String, String, usize, TextSpan),
 }
 
-// Implement Display for ParserError
-impl fmt::Display for ParserError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl ParserError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::UnexpectedToken(_, _) => "E0201",
+            ParserError::MissingLParen(_) => "E0202",
+            ParserError::MissingRParen(_) => "E0203",
+            ParserError::MissingOperator(_) => "E0204",
+            ParserError::InvalidParameter(_, _) => "E0205",
+            ParserError::InvalidArgumentCount(_, _, _, _) => "E0206",
+        }
+    }
+
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            ParserError::UnexpectedToken(_, span)
+            | ParserError::MissingLParen(span)
+            | ParserError::MissingRParen(span)
+            | ParserError::MissingOperator(span)
+            | ParserError::InvalidParameter(_, span)
+            | ParserError::InvalidArgumentCount(_, _, _, span) => span,
+        }
+    }
+
+    /// Description of the error without its location
+    pub fn message(&self) -> String {
         match self {
-            ParserError::UnexpectedToken(kind, line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Unexpected token '{}' at line {}, position {}.",
-                    kind, line, pos
-                )
+            ParserError::UnexpectedToken(kind, _) => format!("Unexpected token '{}'", kind),
+            ParserError::MissingLParen(_) => "Missing '('".to_string(),
+            ParserError::MissingRParen(_) => "Missing ')'".to_string(),
+            ParserError::MissingOperator(_) => "Missing operator".to_string(),
+            ParserError::InvalidParameter(name, _) => format!("Invalid parameter '{}'", name),
+            ParserError::InvalidArgumentCount(name, expected, found, _) => format!(
+                "Function '{}' expects {} argument(s) but got {}",
+                name, expected, found
+            ),
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            ParserError::UnexpectedToken(_, _) => {
+                Some("expected a number, a name, a function call or '('")
             }
-            ParserError::MissingLParen(line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Missing '(' at line {}, position {}.",
-                    line, pos
-                )
+            ParserError::MissingLParen(_) => {
+                Some("every ')' needs a matching '(', function arguments are written as sin(x)")
             }
-            ParserError::MissingRParen(line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Missing ')' at line {}, position {}.",
-                    line, pos
-                )
+            ParserError::MissingRParen(_) => Some("close every '(' with a matching ')'"),
+            ParserError::MissingOperator(_) => Some("put an operator between the two values"),
+            ParserError::InvalidParameter(_, _) => {
+                Some("parameters are names and every parameter needs a different one")
             }
-            ParserError::MissingOperator(line, pos) => {
-                write!(
-                    f,
-                    "Syntax Error: Missing operator at line {}, position {}.",
-                    line, pos
-                )
+            ParserError::InvalidArgumentCount(_, _, _, _) => {
+                Some("the function list is in the README")
             }
         }
     }
 }
 
+// Implement Display for ParserError
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "Syntax Error: {} at line {}, position {}.",
+            self.message(),
+            span.line,
+            span.column + 1
+        )
+    }
+}
+
 impl std::error::Error for ParserError {}
@@ -1,7 +1,29 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PlottingError {
     FileCreationError,
     GenericError,
+    /// No interval was given and it can not be asked for
+    MissingInterval,
+}
+
+impl PlottingError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlottingError::FileCreationError => "E0501",
+            PlottingError::GenericError => "E0502",
+            PlottingError::MissingInterval => "E0503",
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            PlottingError::FileCreationError => Some("check that the output directory is writable"),
+            PlottingError::GenericError => None,
+            PlottingError::MissingInterval => Some("pass the interval with --interval a,b"),
+        }
+    }
 }
 
 impl std::fmt::Display for PlottingError {
@@ -16,6 +38,12 @@ impl std::fmt::Display for PlottingError {
             PlottingError::GenericError => {
                 write!(f, "Generic plotting error: An unspecified error occurred during the plotting process.")
             }
+            PlottingError::MissingInterval => {
+                write!(
+                    f,
+                    "Missing interval: The interval to plot is not given and can not be asked for."
+                )
+            }
         }
     }
 }
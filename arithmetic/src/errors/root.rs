@@ -1,8 +1,60 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RootFinderError {
     InvalidInterval,
     NoRootInInterval,
     MaxIterationsReached,
+    /// An open method (Newton, secant) converged outside of the bracket of the root
+    Diverged,
+    /// Tolerance, step size or iteration limit that is not positive
+    InvalidConfig,
+    /// The variable to solve for does not occur in the equation
+    NoUnknown,
+    /// Several variables without a value, the unknown must be chosen
+    SeveralUnknowns(Vec<String>),
+    /// No interval was given and it can not be asked for
+    MissingInterval,
+    /// solve without an interval could neither isolate the unknown nor solve a polynomial
+    NoSymbolicSolution,
+}
+
+impl RootFinderError {
+    /// Stable error code shown in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            RootFinderError::InvalidInterval => "E0401",
+            RootFinderError::NoRootInInterval => "E0402",
+            RootFinderError::MaxIterationsReached => "E0403",
+            RootFinderError::Diverged => "E0404",
+            RootFinderError::InvalidConfig => "E0405",
+            RootFinderError::NoUnknown => "E0406",
+            RootFinderError::SeveralUnknowns(_) => "E0407",
+            RootFinderError::MissingInterval => "E0408",
+            RootFinderError::NoSymbolicSolution => "E0409",
+        }
+    }
+
+    /// Short hint on how to fix the input
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            RootFinderError::InvalidInterval => Some("the interval a,b needs a < b"),
+            RootFinderError::NoRootInInterval => Some("try a larger interval"),
+            RootFinderError::MaxIterationsReached => None,
+            RootFinderError::Diverged => {
+                Some("bisection, brent, illinois and hybrid never leave the bracket")
+            }
+            RootFinderError::InvalidConfig => {
+                Some("tolerance, step size and max iterations must be positive")
+            }
+            RootFinderError::NoUnknown => None,
+            RootFinderError::SeveralUnknowns(_) => {
+                Some("choose one with --unknown <name>, or :solve <name> in the repl")
+            }
+            RootFinderError::MissingInterval => Some("pass the interval with --interval a,b"),
+            RootFinderError::NoSymbolicSolution => {
+                Some("search for roots with --interval a,b, or :solve [var] a b in the repl")
+            }
+        }
+    }
 }
 
 // Implement Display for RootFinderError
@@ -18,6 +70,40 @@ impl std::fmt::Display for RootFinderError {
             RootFinderError::MaxIterationsReached => {
                 write!(f, "Maximum iterations reached: The root-finding algorithm did not converge within the maximum allowed iterations.")
             }
+            RootFinderError::Diverged => {
+                write!(
+                    f,
+                    "Diverged: The root-finding method converged outside of the interval of the root."
+                )
+            }
+            RootFinderError::InvalidConfig => {
+                write!(
+                    f,
+                    "Invalid configuration: The root finder settings are not valid."
+                )
+            }
+            RootFinderError::NoUnknown => {
+                write!(
+                    f,
+                    "No unknown: The variable to solve for does not occur in the equation."
+                )
+            }
+            RootFinderError::SeveralUnknowns(names) => {
+                write!(
+                    f,
+                    "Several unknowns: {} have no value, it is not clear which one to solve for.",
+                    names.join(", ")
+                )
+            }
+            RootFinderError::MissingInterval => {
+                write!(f, "Missing interval: The interval to search is not given.")
+            }
+            RootFinderError::NoSymbolicSolution => {
+                write!(
+                    f,
+                    "No symbolic solution: Isolating the unknown and solving a polynomial found no solution."
+                )
+            }
         }
     }
 }
@@ -0,0 +1,170 @@
+use super::{BinaryOp, Function, Instruction, Op, Value};
+use crate::ast::{angle::AngleMode, var::VariableManager, ASTNode};
+use crate::errors::{eval::EvaluationError, CompilerError};
+use crate::lexer::{
+    span::TextSpan,
+    token::{Num, TokenKind},
+};
+use crate::vm::compiler::resolve_function;
+use std::f64::consts::{E, PI};
+
+/// Translates the AST into three-address code, operands before the operation like
+/// the postfix notation. Every node gets its own temporary, nothing is shared yet.
+pub struct Lowering<'a> {
+    vars: &'a VariableManager,
+    angle_mode: AngleMode,
+    params: Vec<String>,
+    code: Vec<Instruction>,
+}
+
+impl<'a> Lowering<'a> {
+    pub fn new(vars: &'a VariableManager, angle_mode: AngleMode) -> Self {
+        Self {
+            vars,
+            angle_mode,
+            params: Vec::new(),
+            code: Vec::new(),
+        }
+    }
+
+    pub fn lower(mut self, node: &ASTNode) -> Result<Function, CompilerError> {
+        let inlined = node.inline_functions(self.vars)?;
+        self.params = inlined
+            .variables()
+            .into_iter()
+            .filter(|name| self.vars.get_stored(name).is_none())
+            .collect();
+
+        let result = self.lower_node(&inlined)?;
+        Ok(Function {
+            source: node.infix(),
+            params: self.params,
+            code: self.code,
+            result,
+        })
+    }
+
+    fn lower_node(&mut self, node: &ASTNode) -> Result<Value, CompilerError> {
+        match node {
+            ASTNode::Number(Num::Integer(i), span) => Ok(self.emit(Op::Const(*i as f64), span)),
+            ASTNode::Number(Num::Float(f), span) => Ok(self.emit(Op::Const(*f), span)),
+            ASTNode::Mantissa(value, span) => {
+                let value = value.parse::<f64>().map_err(|_| {
+                    CompilerError::Eval(EvaluationError::InvalidMantissa(span.clone()))
+                })?;
+                Ok(self.emit(Op::Const(value), span))
+            }
+            ASTNode::Constant(TokenKind::Pi, span) => Ok(self.emit(Op::Const(PI), span)),
+            ASTNode::Constant(TokenKind::Euler, span) => Ok(self.emit(Op::Const(E), span)),
+            ASTNode::Constant(_, span) => Err(CompilerError::Eval(
+                EvaluationError::InvalidConstant(span.clone()),
+            )),
+            ASTNode::Identifier(id, span) => {
+                let name = id.to_lowercase();
+                let op = match self.vars.get_stored(&name) {
+                    Some(Num::Integer(i)) => Op::Const(i as f64),
+                    Some(Num::Float(f)) => Op::Const(f),
+                    None => {
+                        let index = self.params.iter().position(|param| *param == name);
+                        Op::Param(index.expect("every variable is a parameter") as u32)
+                    }
+                };
+                Ok(self.emit(op, span))
+            }
+            ASTNode::BinaryOp(left, op, right, span) => {
+                let left = self.lower_node(left)?;
+                let right = self.lower_node(right)?;
+
+                let op = match op {
+                    TokenKind::Plus => BinaryOp::Add,
+                    TokenKind::Minus => BinaryOp::Sub,
+                    TokenKind::Multiply => BinaryOp::Mul,
+                    TokenKind::Divide => BinaryOp::Div,
+                    TokenKind::Mod => BinaryOp::Mod,
+                    TokenKind::Div => BinaryOp::IntDiv,
+                    TokenKind::Power => BinaryOp::Pow,
+                    _ => {
+                        return Err(CompilerError::Eval(
+                            EvaluationError::UnsupportedBinaryOperator(
+                                op.to_string(),
+                                span.clone(),
+                            ),
+                        ))
+                    }
+                };
+                Ok(self.emit(Op::Binary(op, left, right), span))
+            }
+            ASTNode::UnaryOp(op, expr, span) => {
+                let value = self.lower_node(expr)?;
+
+                match op {
+                    TokenKind::Minus => Ok(self.emit(Op::Neg(value), span)),
+                    TokenKind::Plus => Ok(value),
+                    _ => Err(CompilerError::Eval(
+                        EvaluationError::UnsupportedUnaryOperator(op.to_string(), span.clone()),
+                    )),
+                }
+            }
+            ASTNode::FunctionCall(func, args, span) => {
+                let function = resolve_function(self.vars, func, args.len(), span)?;
+                let mut values = args
+                    .iter()
+                    .map(|arg| self.lower_node(arg))
+                    .collect::<Result<Vec<Value>, CompilerError>>()?;
+
+                match func.as_str() {
+                    "sin" | "cos" | "tan" | "cotan" => {
+                        values[0] = self.radians(values[0], span);
+                        Ok(self.emit(Op::Call(function as u32, values), span))
+                    }
+                    "arcsin" | "arccos" | "arctan" | "arccotan" | "atan2" => {
+                        let radians = self.emit(Op::Call(function as u32, values), span);
+                        Ok(self.angle(radians, span))
+                    }
+                    _ => Ok(self.emit(Op::Call(function as u32, values), span)),
+                }
+            }
+            // Partial trees from error recovery can not be compiled
+            ASTNode::Error(span) => Err(CompilerError::GenericError(span.clone())),
+        }
+    }
+
+    /// The angle in radians, computed like AngleMode::angle_to_radians
+    fn radians(&mut self, angle: Value, span: &TextSpan) -> Value {
+        match self.angle_mode {
+            AngleMode::Degrees => self.scale(angle, PI / 180.0, None, span),
+            AngleMode::Radians => angle,
+            AngleMode::Gradians => self.scale(angle, PI, Some(200.0), span),
+        }
+    }
+
+    /// Radians in the angle unit, computed like AngleMode::radians_to_angle
+    fn angle(&mut self, radians: Value, span: &TextSpan) -> Value {
+        match self.angle_mode {
+            AngleMode::Degrees => self.scale(radians, 180.0 / PI, None, span),
+            AngleMode::Radians => radians,
+            AngleMode::Gradians => self.scale(radians, 200.0, Some(PI), span),
+        }
+    }
+
+    /// value * factor, then / divisor
+    fn scale(&mut self, value: Value, factor: f64, divisor: Option<f64>, span: &TextSpan) -> Value {
+        let factor = self.emit(Op::Const(factor), span);
+        let product = self.emit(Op::Binary(BinaryOp::Mul, value, factor), span);
+        match divisor {
+            Some(divisor) => {
+                let divisor = self.emit(Op::Const(divisor), span);
+                self.emit(Op::Binary(BinaryOp::Div, product, divisor), span)
+            }
+            None => product,
+        }
+    }
+
+    fn emit(&mut self, op: Op, span: &TextSpan) -> Value {
+        self.code.push(Instruction {
+            op,
+            span: span.clone(),
+        });
+        Value(self.code.len() as u32 - 1)
+    }
+}
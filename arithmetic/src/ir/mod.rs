@@ -0,0 +1,400 @@
+pub mod lower;
+pub mod opt;
+
+use crate::ast::{angle::AngleMode, var::VariableManager, ASTNode};
+use crate::builtins::BUILTINS;
+use crate::errors::CompilerError;
+use crate::lexer::span::TextSpan;
+use std::fmt;
+
+use lower::Lowering;
+
+/// A temporary: the result of the instruction with the same index. Every temporary
+/// is assigned once, so the code is in SSA form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Value(pub u32);
+
+impl Value {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "t{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    /// `/`, NaN for a zero divisor
+    Div,
+    /// `mod`, NaN unless both operands are integers
+    Mod,
+    /// `div`, floor of the quotient of integer operands, NaN otherwise
+    IntDiv,
+    Pow,
+}
+
+impl BinaryOp {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::Mod => "mod",
+            BinaryOp::IntDiv => "idiv",
+            BinaryOp::Pow => "pow",
+        }
+    }
+}
+
+/// Right hand side of an instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Const(f64),
+    /// Parameter with the given index
+    Param(u32),
+    Binary(BinaryOp, Value, Value),
+    Neg(Value),
+    /// Built-in function with the given index in BUILTINS. Angles are in radians,
+    /// the conversions of the angle unit are instructions of their own.
+    Call(u32, Vec<Value>),
+}
+
+impl Op {
+    /// Temporaries read by the operation
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Op::Const(_) | Op::Param(_) => Vec::new(),
+            Op::Binary(_, left, right) => vec![*left, *right],
+            Op::Neg(value) => vec![*value],
+            Op::Call(_, args) => args.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub op: Op,
+    /// Node of the source the instruction comes from
+    pub span: TextSpan,
+}
+
+/// Three-address code of an expression: a straight line of instructions, each
+/// defining the temporary of its index, and the temporary that is returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The expression, as source text
+    pub source: String,
+    /// Variables without a stored value, sorted by name
+    pub params: Vec<String>,
+    pub code: Vec<Instruction>,
+    pub result: Value,
+}
+
+impl Function {
+    /// Lowers the expression. User functions are inlined, stored variables become
+    /// constants and the trigonometric functions get the conversions of the angle unit.
+    pub fn lower(
+        node: &ASTNode,
+        vars: &VariableManager,
+        angle_mode: AngleMode,
+    ) -> Result<Self, CompilerError> {
+        Lowering::new(vars, angle_mode).lower(node)
+    }
+
+    /// The instruction with the given index as text: `t3 = call sin(t2)`
+    pub fn describe(&self, index: usize) -> String {
+        let rhs = match &self.code[index].op {
+            Op::Const(value) => format!("const {}", value),
+            Op::Param(param) => format!("param {}", self.params[*param as usize]),
+            Op::Binary(op, left, right) => format!("{} {}, {}", op.mnemonic(), left, right),
+            Op::Neg(value) => format!("neg {}", value),
+            Op::Call(function, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                format!(
+                    "call {}({})",
+                    BUILTINS[*function as usize].name,
+                    args.join(", ")
+                )
+            }
+        };
+        format!("{} = {}", Value(index as u32), rhs)
+    }
+
+    /// The function after the optimization passes
+    pub fn optimized(mut self) -> Self {
+        opt::optimize(&mut self, |_, _| {});
+        self
+    }
+
+    /// Dump of the lowered function followed by the code after every pass that
+    /// changed it, the last one is the optimized function
+    pub fn optimization_dump(&self) -> String {
+        let mut out = self.to_string();
+        let mut function = self.clone();
+        opt::optimize(&mut function, |pass, function| {
+            out.push_str(&format!(
+                "\n; after {}: {} instructions\n{}",
+                pass,
+                function.code.len(),
+                function.listing()
+            ));
+        });
+        out
+    }
+
+    /// The instructions and the return, one per line with the location in the source
+    fn listing(&self) -> String {
+        let mut out = String::new();
+        for (index, instruction) in self.code.iter().enumerate() {
+            out.push_str(&format!(
+                "    {:<30} {}:{}\n",
+                self.describe(index),
+                instruction.span.line,
+                instruction.span.column + 1
+            ));
+        }
+        out.push_str(&format!("    ret {}\n", self.result));
+        out
+    }
+}
+
+/// Textual dump, one instruction per line with the location in the source:
+///
+/// ```text
+/// ; source: sin(x)^2 + y
+/// params: x, y
+///     t0 = param x                   1:5
+///     t1 = const 0.017453292519943295 1:5
+///     t2 = mul t0, t1                1:5
+///     t3 = call sin(t2)              1:1
+///     ...
+///     ret t7
+/// ```
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "; source: {}", self.source)?;
+        writeln!(f, "params: {}", self.params.join(", "))?;
+        write!(f, "{}", self.listing())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::opt::Pass;
+    use super::*;
+    use crate::ast::{eval::Evaluator, resolver::MapOnlyResolver};
+    use crate::utils::print::lex_parse_program;
+
+    /// Lowers the last statement of the program after running the others
+    fn lower(input: &str, angle_mode: AngleMode) -> Function {
+        let (statements, result) = lex_parse_program(input).unwrap().split_result();
+        let mut vars = VariableManager::new();
+        let mut resolver = MapOnlyResolver;
+        let mut evaluator = Evaluator::new(&mut vars, &mut resolver);
+        for statement in statements.iter() {
+            evaluator.execute_statement(statement).unwrap();
+        }
+        Function::lower(&result, &vars, angle_mode).unwrap()
+    }
+
+    /// The instructions and the return, without the locations
+    fn code(function: &Function) -> Vec<String> {
+        (0..function.code.len())
+            .map(|index| function.describe(index))
+            .chain([format!("ret {}", function.result)])
+            .collect()
+    }
+
+    fn run(pass: Pass, input: &str) -> (bool, Vec<String>) {
+        let mut function = lower(input, AngleMode::Radians);
+        let changed = pass.run(&mut function);
+        (changed, code(&function))
+    }
+
+    #[test]
+    fn lowering_gives_ssa_form() {
+        let function = lower("sin(x)^2 + y", AngleMode::Degrees);
+        assert_eq!(function.params, ["x", "y"]);
+        assert_eq!(
+            code(&function),
+            [
+                "t0 = param x",
+                "t1 = const 0.017453292519943295",
+                "t2 = mul t0, t1",
+                "t3 = call sin(t2)",
+                "t4 = const 2",
+                "t5 = pow t3, t4",
+                "t6 = param y",
+                "t7 = add t5, t6",
+                "ret t7",
+            ]
+        );
+
+        // Every temporary is defined once, before it is read
+        let function = lower(
+            "f(t) := t * a\nf(x + 1) / max(x, a, 2) - -x",
+            AngleMode::Radians,
+        );
+        for (index, instruction) in function.code.iter().enumerate() {
+            assert!(instruction
+                .op
+                .operands()
+                .iter()
+                .all(|operand| operand.index() < index));
+        }
+        assert_eq!(function.params, ["a", "x"]);
+    }
+
+    #[test]
+    fn stored_variables_and_angle_units() {
+        assert_eq!(
+            code(&lower("c := 2.5\nc * x", AngleMode::Radians)),
+            [
+                "t0 = const 2.5",
+                "t1 = param x",
+                "t2 = mul t0, t1",
+                "ret t2"
+            ]
+        );
+        assert_eq!(
+            code(&lower("arcsin(x)", AngleMode::Gradians)),
+            [
+                "t0 = param x",
+                "t1 = call arcsin(t0)",
+                "t2 = const 200",
+                "t3 = mul t1, t2",
+                "t4 = const 3.141592653589793",
+                "t5 = div t3, t4",
+                "ret t5",
+            ]
+        );
+    }
+
+    #[test]
+    fn constant_folding() {
+        let (changed, code) = run(Pass::ConstantFolding, "2 * 3 + sqrt(16) * x");
+        assert!(changed);
+        assert_eq!(
+            code,
+            [
+                "t0 = const 2",
+                "t1 = const 3",
+                "t2 = const 6",
+                "t3 = const 16",
+                "t4 = const 4",
+                "t5 = param x",
+                "t6 = mul t4, t5",
+                "t7 = add t2, t6",
+                "ret t7",
+            ]
+        );
+
+        // Errors of the evaluator are NaN, as in the compiled code
+        let (_, code) = run(Pass::ConstantFolding, "1 / 0 + x");
+        assert_eq!(code[2], "t2 = const NaN");
+        assert!(!run(Pass::ConstantFolding, "x + y").0);
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        let (changed, code) = run(Pass::CommonSubexpressions, "sin(x) * sin(x)");
+        assert!(changed);
+        assert_eq!(
+            code,
+            [
+                "t0 = param x",
+                "t1 = call sin(t0)",
+                "t2 = mul t1, t1",
+                "ret t2"
+            ]
+        );
+
+        // Operands of + and * in either order, not those of - and /
+        let (_, code) = run(Pass::CommonSubexpressions, "x*y + y*x - (x/y - y/x)");
+        assert_eq!(
+            code,
+            [
+                "t0 = param x",
+                "t1 = param y",
+                "t2 = mul t0, t1",
+                "t3 = add t2, t2",
+                "t4 = div t0, t1",
+                "t5 = div t1, t0",
+                "t6 = sub t4, t5",
+                "t7 = sub t3, t6",
+                "ret t7",
+            ]
+        );
+    }
+
+    #[test]
+    fn strength_reduction() {
+        let (changed, code) = run(Pass::StrengthReduction, "x^2 + sqr(y) + pow(x, 2)");
+        assert!(changed);
+        assert_eq!(code[2], "t2 = mul t0, t0");
+        assert_eq!(code[4], "t4 = mul t3, t3");
+        assert_eq!(code[8], "t8 = mul t6, t6");
+
+        // Only powers of two have an exact reciprocal
+        let (_, code) = run(Pass::StrengthReduction, "x / 4 + x / 3 + x / 0");
+        assert_eq!(
+            code,
+            [
+                "t0 = param x",
+                "t1 = const 4",
+                "t2 = const 0.25",
+                "t3 = mul t0, t2",
+                "t4 = param x",
+                "t5 = const 3",
+                "t6 = div t4, t5",
+                "t7 = add t3, t6",
+                "t8 = param x",
+                "t9 = const 0",
+                "t10 = div t8, t9",
+                "t11 = add t7, t10",
+                "ret t11",
+            ]
+        );
+        assert!(!run(Pass::StrengthReduction, "x^3 / 6").0);
+    }
+
+    #[test]
+    fn dead_code_elimination() {
+        let mut function = lower("2 * 3 + x", AngleMode::Radians);
+        Pass::ConstantFolding.run(&mut function);
+        assert!(Pass::DeadCode.run(&mut function));
+        assert_eq!(
+            code(&function),
+            ["t0 = const 6", "t1 = param x", "t2 = add t0, t1", "ret t2"]
+        );
+        assert!(!Pass::DeadCode.run(&mut function));
+    }
+
+    #[test]
+    fn passes_run_until_nothing_changes() {
+        let function = lower("x^2 + sqr(y) + pow(x, 2)", AngleMode::Radians).optimized();
+        assert_eq!(
+            code(&function),
+            [
+                "t0 = param x",
+                "t1 = mul t0, t0",
+                "t2 = param y",
+                "t3 = mul t2, t2",
+                "t4 = add t1, t3",
+                "t5 = add t4, t1",
+                "ret t5",
+            ]
+        );
+        assert!(Pass::ALL
+            .iter()
+            .all(|pass| !pass.run(&mut function.clone())));
+    }
+}
@@ -0,0 +1,275 @@
+use super::{BinaryOp, Function, Instruction, Op, Value};
+use crate::ast::angle::AngleMode;
+use crate::builtins::BUILTINS;
+use crate::lexer::span::TextSpan;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Optimization pass over the IR. Every pass keeps the result of the function
+/// bit for bit, apart from the squares that strength reduction computes with a
+/// multiplication: `x*x` is correctly rounded, `pow(x, 2)` can be one unit in the
+/// last place off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Computes the operations whose operands are all constants
+    ConstantFolding,
+    /// `sqr(x)`, `x^2` and `pow(x, 2)` become `x*x`, divisions by a power of two multiplications
+    StrengthReduction,
+    /// Reuses the temporary of an operation computed before on the same operands
+    CommonSubexpressions,
+    /// Removes the instructions the result does not depend on
+    DeadCode,
+}
+
+impl Pass {
+    /// The passes in the order they run
+    pub const ALL: [Pass; 4] = [
+        Pass::ConstantFolding,
+        Pass::StrengthReduction,
+        Pass::CommonSubexpressions,
+        Pass::DeadCode,
+    ];
+
+    /// Runs the pass, returns whether it changed the code
+    pub fn run(self, function: &mut Function) -> bool {
+        match self {
+            Pass::ConstantFolding => rewrite(function, fold_constant),
+            Pass::StrengthReduction => rewrite(function, reduce_strength),
+            Pass::CommonSubexpressions => {
+                let mut seen = HashMap::new();
+                rewrite(function, |builder, op, span| {
+                    let key = Key::new(&op);
+                    match seen.get(&key) {
+                        Some(value) => Rewrite::Replaced(*value),
+                        None => {
+                            let value = builder.emit(op, span);
+                            seen.insert(key, value);
+                            Rewrite::Kept(value)
+                        }
+                    }
+                })
+            }
+            Pass::DeadCode => eliminate_dead_code(function),
+        }
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Pass::ConstantFolding => "constant folding",
+            Pass::StrengthReduction => "strength reduction",
+            Pass::CommonSubexpressions => "common subexpression elimination",
+            Pass::DeadCode => "dead code elimination",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Runs the passes in order until none of them changes the code. The function is
+/// given to observe after every pass that changed it, the dump of `ir` prints it.
+pub fn optimize(function: &mut Function, mut observe: impl FnMut(Pass, &Function)) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for pass in Pass::ALL {
+            if pass.run(function) {
+                observe(pass, function);
+                changed = true;
+            }
+        }
+    }
+}
+
+/// What a pass made of an instruction
+enum Rewrite {
+    /// The instruction was emitted as it was
+    Kept(Value),
+    /// The instruction was replaced by the given temporary
+    Replaced(Value),
+}
+
+/// Code of the rewritten function
+struct Builder {
+    code: Vec<Instruction>,
+}
+
+impl Builder {
+    fn emit(&mut self, op: Op, span: &TextSpan) -> Value {
+        self.code.push(Instruction {
+            op,
+            span: span.clone(),
+        });
+        Value(self.code.len() as u32 - 1)
+    }
+
+    fn constant(&self, value: Value) -> Option<f64> {
+        match self.code[value.index()].op {
+            Op::Const(constant) => Some(constant),
+            _ => None,
+        }
+    }
+}
+
+/// Rebuilds the code instruction by instruction. The operands given to the pass
+/// already refer to the rewritten code, the pass emits what replaces the instruction.
+fn rewrite(
+    function: &mut Function,
+    mut pass: impl FnMut(&mut Builder, Op, &TextSpan) -> Rewrite,
+) -> bool {
+    let mut builder = Builder { code: Vec::new() };
+    let mut map: Vec<Value> = Vec::with_capacity(function.code.len());
+    let mut changed = false;
+
+    for instruction in function.code.iter() {
+        let op = renumber(&instruction.op, &map);
+        let value = match pass(&mut builder, op, &instruction.span) {
+            Rewrite::Kept(value) => value,
+            Rewrite::Replaced(value) => {
+                changed = true;
+                value
+            }
+        };
+        map.push(value);
+    }
+
+    function.result = map[function.result.index()];
+    function.code = builder.code;
+    changed
+}
+
+/// The operation with its operands taken from the map
+fn renumber(op: &Op, map: &[Value]) -> Op {
+    match op {
+        Op::Const(_) | Op::Param(_) => op.clone(),
+        Op::Binary(op, left, right) => Op::Binary(*op, map[left.index()], map[right.index()]),
+        Op::Neg(value) => Op::Neg(map[value.index()]),
+        Op::Call(function, args) => {
+            Op::Call(*function, args.iter().map(|arg| map[arg.index()]).collect())
+        }
+    }
+}
+
+fn fold_constant(builder: &mut Builder, op: Op, span: &TextSpan) -> Rewrite {
+    let folded = match &op {
+        Op::Binary(op, left, right) => builder
+            .constant(*left)
+            .zip(builder.constant(*right))
+            .map(|(left, right)| binary(*op, left, right)),
+        Op::Neg(value) => builder.constant(*value).map(|value| -value),
+        Op::Call(function, args) => args
+            .iter()
+            .map(|arg| builder.constant(*arg))
+            .collect::<Option<Vec<f64>>>()
+            .map(|args| (BUILTINS[*function as usize].apply)(&args, AngleMode::Radians)),
+        Op::Const(_) | Op::Param(_) => None,
+    };
+
+    match folded {
+        Some(value) => Rewrite::Replaced(builder.emit(Op::Const(value), span)),
+        None => Rewrite::Kept(builder.emit(op, span)),
+    }
+}
+
+/// The binary operation, NaN where the evaluator fails
+fn binary(op: BinaryOp, left: f64, right: f64) -> f64 {
+    let integers = left.fract() == 0.0 && right.fract() == 0.0;
+    match op {
+        BinaryOp::Add => left + right,
+        BinaryOp::Sub => left - right,
+        BinaryOp::Mul => left * right,
+        BinaryOp::Div if right == 0.0 => f64::NAN,
+        BinaryOp::Div => left / right,
+        BinaryOp::Mod if integers => left % right,
+        BinaryOp::IntDiv if integers && right != 0.0 => (left / right).floor(),
+        BinaryOp::Mod | BinaryOp::IntDiv => f64::NAN,
+        BinaryOp::Pow => left.powf(right),
+    }
+}
+
+fn reduce_strength(builder: &mut Builder, op: Op, span: &TextSpan) -> Rewrite {
+    match op {
+        Op::Call(function, ref args) if BUILTINS[function as usize].name == "sqr" => {
+            Rewrite::Replaced(builder.emit(Op::Binary(BinaryOp::Mul, args[0], args[0]), span))
+        }
+        Op::Call(function, ref args)
+            if BUILTINS[function as usize].name == "pow"
+                && builder.constant(args[1]) == Some(2.0) =>
+        {
+            Rewrite::Replaced(builder.emit(Op::Binary(BinaryOp::Mul, args[0], args[0]), span))
+        }
+        Op::Binary(BinaryOp::Pow, base, exponent) if builder.constant(exponent) == Some(2.0) => {
+            Rewrite::Replaced(builder.emit(Op::Binary(BinaryOp::Mul, base, base), span))
+        }
+        // 1/c is exact, so x * (1/c) rounds like x / c
+        Op::Binary(BinaryOp::Div, left, right) => match builder.constant(right) {
+            Some(divisor) if is_power_of_two(divisor) => {
+                let reciprocal = builder.emit(Op::Const(1.0 / divisor), span);
+                Rewrite::Replaced(builder.emit(Op::Binary(BinaryOp::Mul, left, reciprocal), span))
+            }
+            _ => Rewrite::Kept(builder.emit(op, span)),
+        },
+        _ => Rewrite::Kept(builder.emit(op, span)),
+    }
+}
+
+/// A power of two whose reciprocal is a normal number
+fn is_power_of_two(value: f64) -> bool {
+    let value = value.abs();
+    value.is_normal() && value.to_bits() & ((1 << 52) - 1) == 0 && (1.0 / value).is_normal()
+}
+
+/// Operation compared by value: constants by their bits, the operands of the
+/// commutative operations in order
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Const(u64),
+    Param(u32),
+    Binary(BinaryOp, Value, Value),
+    Neg(Value),
+    Call(u32, Vec<Value>),
+}
+
+impl Key {
+    fn new(op: &Op) -> Self {
+        match op {
+            Op::Const(value) => Key::Const(value.to_bits()),
+            Op::Param(param) => Key::Param(*param),
+            Op::Binary(op @ (BinaryOp::Add | BinaryOp::Mul), left, right) => {
+                Key::Binary(*op, *left.min(right), *left.max(right))
+            }
+            Op::Binary(op, left, right) => Key::Binary(*op, *left, *right),
+            Op::Neg(value) => Key::Neg(*value),
+            Op::Call(function, args) => Key::Call(*function, args.clone()),
+        }
+    }
+}
+
+/// Keeps the instructions the result depends on. Operands come before the
+/// instruction that reads them, so one pass from the end finds them all.
+fn eliminate_dead_code(function: &mut Function) -> bool {
+    let mut live = vec![false; function.code.len()];
+    live[function.result.index()] = true;
+    for (index, instruction) in function.code.iter().enumerate().rev() {
+        if live[index] {
+            for operand in instruction.op.operands() {
+                live[operand.index()] = true;
+            }
+        }
+    }
+
+    if live.iter().all(|live| *live) {
+        return false;
+    }
+
+    let mut index = 0;
+    rewrite(function, |builder, op, span| {
+        index += 1;
+        match live[index - 1] {
+            true => Rewrite::Kept(builder.emit(op, span)),
+            // Never read, any temporary will do
+            false => Rewrite::Replaced(Value(0)),
+        }
+    });
+    true
+}
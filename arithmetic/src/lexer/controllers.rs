@@ -1,15 +1,14 @@
-use super::Lexer;
+use super::{Lexer, TextSpan};
 
 impl<'a> Lexer<'a> {
     /// Advances to the next position also returns the current char before moving the position
     pub fn advance(&mut self) {
-        if self.pos >= self.input.len() {
+        let Some(c) = self.current_char() else {
             return;
-        }
-        let c = self.current_char();
+        };
         self.pos += 1;
 
-        if c == Some('\n') {
+        if c == '\n' {
             self.line += 1;
             self.column = 0
         } else {
@@ -27,6 +26,17 @@ impl<'a> Lexer<'a> {
         self.input.chars().nth(self.pos + 1)
     }
 
+    /// Span of the input between start and the current position
+    pub fn span_from(&self, start: usize, line: usize, column: usize) -> TextSpan {
+        let literal = self
+            .input
+            .chars()
+            .skip(start)
+            .take(self.pos - start)
+            .collect();
+        TextSpan::new(start, self.pos, literal, line, column)
+    }
+
     /// resets the lexer position so the input can be lexed again without the need to re-initialize
     pub fn reset(&mut self) {
         self.pos = 0;
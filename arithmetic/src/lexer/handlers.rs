@@ -21,10 +21,10 @@ impl<'a> Lexer<'a> {
         c.is_ascii()
     }
 
-    /// Skip White spaces: spaces, newline, \t
+    /// Skip White spaces: spaces, \t, \r. Newlines separate statements and are tokens
     pub fn skip_whitespace(&mut self) {
         while let Some(c) = self.current_char() {
-            if c.is_whitespace() {
+            if c.is_whitespace() && c != '\n' {
                 self.advance();
             } else {
                 break;
@@ -35,6 +35,7 @@ impl<'a> Lexer<'a> {
     /// Handle the punctuations operators and separators
     /// The ones that are not alphabetic but are ascii
     pub fn handle_punctuation(&mut self) -> Result<TokenKind, CompilerError> {
+        let (start, line, column) = (self.pos, self.line, self.column);
         let c = self.current_char().unwrap();
         self.advance();
         match c {
@@ -45,10 +46,17 @@ impl<'a> Lexer<'a> {
             '(' => Ok(TokenKind::LeftParen),
             ')' => Ok(TokenKind::RightParen),
             '^' => Ok(TokenKind::Power),
+            ',' => Ok(TokenKind::Comma),
+            ';' => Ok(TokenKind::Semicolon),
+            '\n' => Ok(TokenKind::Newline),
+            ':' if self.current_char() == Some('=') => {
+                self.advance();
+                Ok(TokenKind::Assign)
+            }
+            '=' => Ok(TokenKind::Equals),
             _ => Err(CompilerError::Lex(LexerError::InvalidCharacter(
                 c,
-                self.line,
-                self.pos - 1,
+                self.span_from(start, line, column),
             ))),
         }
     }
@@ -59,9 +67,11 @@ impl<'a> Lexer<'a> {
 
         // Ensure the first character is valid for the start of an identifier
         if let Some(c) = self.current_char() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
+                let (start, line, column) = (self.pos, self.line, self.column);
+                self.advance();
                 return Err(CompilerError::Lex(LexerError::InvalidIdentifier(
-                    self.line, self.pos,
+                    self.span_from(start, line, column),
                 )));
             } else if Self::is_identifier_start(&c) {
                 identifier.push(c);
@@ -83,7 +93,7 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn handle_number(&mut self) -> Result<TokenKind, CompilerError> {
-        let start_pos = self.pos;
+        let (start_pos, line, column) = (self.pos, self.line, self.column);
         let mut number_str = String::new();
         let mut has_dot = false;
 
@@ -132,7 +142,8 @@ impl<'a> Lexer<'a> {
             if !has_exponent_digits {
                 // TODO: InvalidMantissa in the compiler error
                 return Err(CompilerError::Lex(LexerError::InvalidNumber(
-                    number_str, self.line, start_pos,
+                    number_str,
+                    self.span_from(start_pos, line, column),
                 )));
             }
         }
@@ -146,7 +157,8 @@ impl<'a> Lexer<'a> {
             match number_str.parse::<f64>() {
                 Ok(float_num) => Ok(TokenKind::Number(Num::Float(float_num))),
                 Err(_) => Err(CompilerError::Lex(LexerError::InvalidNumber(
-                    number_str, self.line, start_pos,
+                    number_str,
+                    self.span_from(start_pos, line, column),
                 ))),
             }
         } else {
@@ -154,7 +166,8 @@ impl<'a> Lexer<'a> {
             match number_str.parse::<i64>() {
                 Ok(int_num) => Ok(TokenKind::Number(Num::Integer(int_num))),
                 Err(_) => Err(CompilerError::Lex(LexerError::InvalidNumber(
-                    number_str, self.line, start_pos,
+                    number_str,
+                    self.span_from(start_pos, line, column),
                 ))),
             }
         }
@@ -4,11 +4,13 @@ pub mod span;
 pub mod token;
 pub mod utils;
 
+use crate::builtins::Builtin;
 use crate::errors::{lexer::LexerError, CompilerError};
 use span::TextSpan;
 use token::{Num, Token, TokenKind};
 
 /// Lexer: lexes the input and returns token stream
+#[derive(Clone)]
 pub struct Lexer<'a> {
     input: &'a str,
     pub pos: usize,
@@ -26,12 +28,28 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Returns the token kind of a reserved word: functions, operators and constants
+    pub fn keyword(identifier_lower: &str) -> Option<TokenKind> {
+        if let Some(builtin) = Builtin::lookup(identifier_lower) {
+            return Some(TokenKind::Function(builtin.name));
+        }
+
+        let kind = match identifier_lower {
+            "div" => TokenKind::Div,
+            "mod" => TokenKind::Mod,
+            "e" => TokenKind::Euler,
+            "pi" => TokenKind::Pi,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
     /// The core lexer logic
     pub fn get_next_token(&mut self) -> Result<Token, CompilerError> {
         self.skip_whitespace();
 
-        // Ensure that the lexer is in bound.
-        if self.pos >= self.input.len() {
+        // Ensure that the lexer is in bound. pos counts chars, not bytes.
+        if self.current_char().is_none() {
             let eof_char: char = '\0';
             return Ok(Token::new(
                 TokenKind::Eof,
@@ -50,7 +68,8 @@ impl<'a> Lexer<'a> {
             Some(ch) => ch,
             None => {
                 return Err(CompilerError::Lex(LexerError::InvalidCharacter(
-                    '\0', self.line, self.pos,
+                    '\0',
+                    self.span_from(self.pos, self.line, self.column),
                 )))
             }
         };
@@ -74,15 +93,15 @@ impl<'a> Lexer<'a> {
 
         // Matching
         let kind = if Self::is_number_start(&c) {
-            let number_kind = self
-                .handle_number()
-                .map_err(|_| CompilerError::GenericError(self.line, self.pos))?;
+            let number_kind = self.handle_number()?;
 
             // If the next character is part of an identifier -> Raise Error
             if let Some(next_char) = self.current_char() {
                 if Self::is_identifier_start(&next_char) {
+                    // Underline the number and the name: 2x
+                    self.handle_identifier()?;
                     return Err(CompilerError::Lex(LexerError::InvalidIdentifier(
-                        self.line, self.pos,
+                        self.span_from(start, line, column),
                     )));
                 }
             }
@@ -93,33 +112,16 @@ impl<'a> Lexer<'a> {
             let identifier = self.handle_identifier()?;
             let identifier_lower = identifier.to_lowercase();
 
-            match identifier_lower.as_str() {
-                "sin" => TokenKind::Sin,
-                "cos" => TokenKind::Cos,
-                "tan" => TokenKind::Tan,
-                "cotan" => TokenKind::Cotan,
-                "arcsin" => TokenKind::Arcsin,
-                "arccos" => TokenKind::Arccos,
-                "arctan" => TokenKind::Arctan,
-                "arccotan" => TokenKind::Arccotan,
-                "ln" => TokenKind::Ln,
-                "log" => TokenKind::Log,
-                "exp" => TokenKind::Exp,
-                "sqrt" => TokenKind::Sqrt,
-                "sqr" => TokenKind::Sqr,
-                "div" => TokenKind::Div,
-                "mod" => TokenKind::Mod,
-                "e" => TokenKind::Euler,
-                "pi" => TokenKind::Pi,
-
-                _ => {
+            match Self::keyword(&identifier_lower) {
+                Some(kind) => kind,
+                None => {
                     if identifier_lower
                         .chars()
                         .next()
-                        .map_or(false, |c| c.is_ascii_digit())
+                        .is_some_and(|c| c.is_ascii_digit())
                     {
                         return Err(CompilerError::Lex(LexerError::InvalidIdentifier(
-                            self.line, self.pos,
+                            self.span_from(start, line, column),
                         )));
                     } else {
                         TokenKind::Identifier(identifier)
@@ -129,13 +131,61 @@ impl<'a> Lexer<'a> {
         } else if Self::is_ascii_start(&c) {
             self.handle_punctuation()?
         } else {
-            return Err(CompilerError::GenericError(self.line, self.pos));
+            self.advance();
+            return Err(CompilerError::Lex(LexerError::InvalidCharacter(
+                c,
+                self.span_from(start, line, column),
+            )));
         };
 
-        let end = self.pos;
-        let literal = self.input[start..end].to_string();
-        let span = TextSpan::new(start, end, literal, line, column);
+        Ok(Token::new(kind, self.span_from(start, line, column)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Kinds of the tokens up to Eof, errors are kept in place
+    fn kinds(input: &str) -> Vec<Result<TokenKind, CompilerError>> {
+        let mut lexer = Lexer::new(input);
+        let mut kinds = Vec::new();
+        loop {
+            match lexer.get_next_token() {
+                Ok(token) if token.kind == TokenKind::Eof => return kinds,
+                token => kinds.push(token.map(|token| token.kind)),
+            }
+        }
+    }
+
+    #[test]
+    fn non_ascii_input_is_an_error_not_a_panic() {
+        let kinds = kinds("x€ + 1");
+        assert_eq!(kinds[0], Ok(TokenKind::Identifier("x".to_string())));
+        match &kinds[1] {
+            Err(CompilerError::Lex(LexerError::InvalidCharacter('€', span))) => {
+                assert_eq!((span.line, span.column, span.literal.as_str()), (1, 1, "€"))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(kinds[2], Ok(TokenKind::Plus));
+        assert_eq!(kinds[3], Ok(TokenKind::Number(Num::Integer(1))));
+        assert_eq!(kinds.len(), 4);
+    }
 
-        Ok(Token::new(kind, span))
+    #[test]
+    fn literals_after_multi_byte_chars() {
+        let mut lexer = Lexer::new("€€ sin(x)");
+        let mut literals = Vec::new();
+        loop {
+            let span = match lexer.get_next_token() {
+                Ok(token) if token.kind == TokenKind::Eof => break,
+                Ok(token) => token.span,
+                Err(CompilerError::Lex(LexerError::InvalidCharacter(_, span))) => span,
+                Err(e) => panic!("unexpected {:?}", e),
+            };
+            literals.push(span.literal);
+        }
+        assert_eq!(literals, ["€", "€", "sin", "(", "x", ")"]);
     }
 }
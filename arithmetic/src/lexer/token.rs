@@ -1,14 +1,16 @@
 use super::TextSpan;
+use crate::builtins::Builtin;
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Num {
     Integer(i64),
     Float(f64),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TokenKind {
     // Literals
     Number(Num),
@@ -29,21 +31,17 @@ pub enum TokenKind {
     // Separators a
     LeftParen,
     RightParen,
+    Comma,
+    Semicolon,
+    Newline,
 
-    // Mathematical functions
-    Sin,
-    Cos,
-    Tan,
-    Cotan,
-    Arcsin,
-    Arccos,
-    Arctan,
-    Arccotan,
-    Ln,
-    Log,
-    Exp,
-    Sqrt,
-    Sqr,
+    // Statements
+    Assign,
+    Equals,
+
+    // Mathematical functions: sin, cos, ... see builtins.rs
+    #[serde(deserialize_with = "builtin_name")]
+    Function(FunctionName),
 
     // Constants
     Euler,
@@ -53,7 +51,7 @@ pub enum TokenKind {
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: TextSpan,
@@ -65,6 +63,21 @@ impl Token {
     }
 }
 
+/// Name of a built-in function. Behind an alias serde does not try to borrow
+/// it from the input, it is looked up in the built-in table instead.
+pub type FunctionName = &'static str;
+
+/// Function names in JSON are looked up in the built-in table
+fn builtin_name<'de, D>(deserializer: D) -> Result<&'static str, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    Builtin::lookup(&name.to_lowercase())
+        .map(|builtin| builtin.name)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown function '{}'", name)))
+}
+
 // Handling the Display for Token, TokenKind and Num
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -75,8 +88,8 @@ impl fmt::Display for Token {
 impl fmt::Display for Num {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Num::Integer(i) => write!(f, "Integer({})", i),
-            Num::Float(fl) => write!(f, "Float({:.5})", fl),
+            Num::Integer(i) => write!(f, "{}", i),
+            Num::Float(fl) => write!(f, "{}", fl),
         }
     }
 }
@@ -84,8 +97,8 @@ impl fmt::Display for Num {
 impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TokenKind::Number(num) => write!(f, "Number({})", num),
-            TokenKind::Identifier(string) => write!(f, "Identifier({})", string),
+            TokenKind::Number(num) => write!(f, "{}", num),
+            TokenKind::Identifier(string) => write!(f, "{}", string),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::Minus => write!(f, "-"),
             TokenKind::Multiply => write!(f, "*"),
@@ -95,19 +108,12 @@ impl fmt::Display for TokenKind {
             TokenKind::Power => write!(f, "^"),
             TokenKind::LeftParen => write!(f, "("),
             TokenKind::RightParen => write!(f, ")"),
-            TokenKind::Sin => write!(f, "Sin"),
-            TokenKind::Cos => write!(f, "Cos"),
-            TokenKind::Tan => write!(f, "Tan"),
-            TokenKind::Cotan => write!(f, "Cotan"),
-            TokenKind::Arcsin => write!(f, "ArcSin"),
-            TokenKind::Arccos => write!(f, "ArcCos"),
-            TokenKind::Arctan => write!(f, "ArcTan"),
-            TokenKind::Arccotan => write!(f, "ArcCotan"),
-            TokenKind::Ln => write!(f, "Ln"),
-            TokenKind::Log => write!(f, "Log"),
-            TokenKind::Exp => write!(f, "Exp"),
-            TokenKind::Sqrt => write!(f, "Sqrt"),
-            TokenKind::Sqr => write!(f, "Sqr"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::Newline => write!(f, "New Line"),
+            TokenKind::Assign => write!(f, ":="),
+            TokenKind::Equals => write!(f, "="),
+            TokenKind::Function(name) => write!(f, "{}", name),
             TokenKind::Mantissa(num_str) => write!(f, "{}", num_str),
             TokenKind::Euler => write!(f, "e"),
             TokenKind::Pi => write!(f, "π"),
@@ -30,4 +30,10 @@ impl<'a> Lexer<'a> {
                 .collect::<String>()
         })
     }
+
+    /// Lexes all tokens and returns them as a JSON array
+    pub fn jsonify(&mut self) -> Result<String, CompilerError> {
+        self.lex_all()
+            .map(|tokens| serde_json::to_string_pretty(&tokens).expect("tokens always serialize"))
+    }
 }
@@ -1,18 +1,18 @@
 mod ast;
+mod builtins;
+mod cli;
+mod codegen;
 mod errors;
+mod ir;
 mod lexer;
 mod parser;
 mod utils;
-use std::fs;
+mod vm;
+use std::{env, process};
 
-use utils::ultimate::{ultimate_ast_postfix_eval, ultimate_root_plot};
+use cli::commands::run;
 
 fn main() {
-    let input = fs::read_to_string("src/inputs/input.txt").expect("Unable to read from file");
-    let input2 = fs::read_to_string("src/inputs/plot.txt").expect("Unable to read from file");
-
-    println!("###################### Part 1: Lexer, Parser(AST) , Postfix , Evaluation ######################\n");
-    ultimate_ast_postfix_eval(&input);
-    println!("\n\n ###################### Part 2: Roots and Plot ######################\n");
-    ultimate_root_plot(&input2);
+    let args: Vec<String> = env::args().skip(1).collect();
+    process::exit(run(&args));
 }
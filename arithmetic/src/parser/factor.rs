@@ -1,4 +1,4 @@
-use super::{ASTNode, CompilerError, Parser, ParserError, TextSpan, TokenKind};
+use super::{ASTNode, Builtin, CompilerError, Parser, ParserError, TextSpan, TokenKind};
 
 impl<'a> Parser<'a> {
     pub fn parse_factor(&mut self) -> Result<ASTNode, CompilerError> {
@@ -9,30 +9,25 @@ impl<'a> Parser<'a> {
             TokenKind::Number(_) => self.parse_number(span),
             TokenKind::Mantissa(_) => self.parse_mantissa(span),
             TokenKind::Euler | TokenKind::Pi => self.parse_constant(span),
-            TokenKind::Sin
-            | TokenKind::Cos
-            | TokenKind::Tan
-            | TokenKind::Cotan
-            | TokenKind::Arcsin
-            | TokenKind::Arccos
-            | TokenKind::Arctan
-            | TokenKind::Arccotan
-            | TokenKind::Ln
-            | TokenKind::Log
-            | TokenKind::Exp
-            | TokenKind::Sqrt
-            | TokenKind::Sqr => self.parse_function(span),
+            TokenKind::Function(_) => self.parse_function(span),
             TokenKind::Identifier(_) => self.parse_identifier(span),
             TokenKind::LeftParen => self.parse_parentheses(),
-            TokenKind::RightParen => Err(CompilerError::Parse(ParserError::MissingLParen(
-                self.lexer.line,
-                self.lexer.pos,
-            ))),
-            _ => Err(CompilerError::Parse(ParserError::UnexpectedToken(
-                self.current_token.kind.clone(),
-                self.lexer.pos,
-                self.lexer.line,
-            ))),
+            // ')' without '(': skipped, or it would end the statement as well
+            TokenKind::RightParen if self.paren_depth == 0 => {
+                self.report(CompilerError::Parse(ParserError::MissingLParen(
+                    span.clone(),
+                )));
+                self.advance()?;
+                Ok(ASTNode::Error(span))
+            }
+            _ => {
+                self.report(CompilerError::Parse(ParserError::UnexpectedToken(
+                    self.current_token.kind.clone(),
+                    span.clone(),
+                )));
+                self.synchronize()?;
+                Ok(ASTNode::Error(span))
+            }
         }
     }
 
@@ -55,10 +50,10 @@ impl<'a> Parser<'a> {
 
             // Ensure no consecutive numbers without an operator
             if let TokenKind::Number(_) = self.current_token.kind {
-                return Err(CompilerError::Parse(ParserError::MissingOperator(
-                    self.lexer.line,
-                    self.lexer.pos,
+                self.report(CompilerError::Parse(ParserError::MissingOperator(
+                    self.current_token.span.clone(),
                 )));
+                self.synchronize()?;
             }
 
             Ok(ASTNode::Number(value, span))
@@ -84,74 +79,90 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_function(&mut self, span: TextSpan) -> Result<ASTNode, CompilerError> {
-        let func_name = self.current_token.kind.clone();
+        let func_name = match self.current_token.kind {
+            TokenKind::Function(name) => name,
+            _ => unreachable!(),
+        };
         self.advance()?;
 
         if self.current_token.kind != TokenKind::LeftParen {
-            return Err(CompilerError::Parse(ParserError::MissingLParen(
-                self.lexer.pos,
-                self.lexer.line,
+            self.report(CompilerError::Parse(ParserError::MissingLParen(
+                self.current_token.span.clone(),
             )));
+            self.synchronize()?;
+            return Ok(ASTNode::Error(span));
         }
 
-        self.advance()?; // Skip '('
-        let argument = self.parse_expression()?; // Parse function argument
+        let arguments = self.parse_arguments()?; // Parse function arguments
 
-        if self.current_token.kind != TokenKind::RightParen {
-            return Err(CompilerError::Parse(ParserError::MissingRParen(
-                self.lexer.line,
-                self.lexer.pos,
+        // Validate the number of arguments
+        let builtin = Builtin::lookup(func_name).expect("the lexer only emits known functions");
+        if !builtin.accepts(arguments.len()) {
+            self.report(CompilerError::Parse(ParserError::InvalidArgumentCount(
+                func_name.to_string(),
+                builtin.expected_args(),
+                arguments.len(),
+                span.clone(),
             )));
         }
 
-        self.advance()?; // Skip ')'
-
-        let func_name_str = match func_name {
-            TokenKind::Sin => "sin",
-            TokenKind::Cos => "cos",
-            TokenKind::Tan => "tan",
-            TokenKind::Cotan => "cotan",
-            TokenKind::Arcsin => "arcsin",
-            TokenKind::Arccos => "arccos",
-            TokenKind::Arctan => "arctan",
-            TokenKind::Arccotan => "arccotan",
-            TokenKind::Ln => "ln",
-            TokenKind::Log => "log",
-            TokenKind::Exp => "exp",
-            TokenKind::Sqrt => "sqrt",
-            TokenKind::Sqr => "sqr",
-            _ => unreachable!(),
-        };
-
         Ok(ASTNode::FunctionCall(
-            func_name_str.to_string(),
-            Box::new(argument),
+            func_name.to_string(),
+            arguments,
             span,
         ))
     }
 
+    /// Variables: x, Calls of user defined functions: f(x, 2)
     fn parse_identifier(&mut self, span: TextSpan) -> Result<ASTNode, CompilerError> {
         if let TokenKind::Identifier(name) = &self.current_token.kind {
             let identifier = name.clone();
             self.advance()?;
+
+            if self.current_token.kind == TokenKind::LeftParen {
+                let arguments = self.parse_arguments()?;
+                return Ok(ASTNode::FunctionCall(identifier, arguments, span));
+            }
+
             Ok(ASTNode::Identifier(identifier, span))
         } else {
             unreachable!()
         }
     }
 
+    /// ( expression, expression, ... )
+    fn parse_arguments(&mut self) -> Result<Vec<ASTNode>, CompilerError> {
+        self.open_paren()?; // Skip '('
+        let mut arguments = Vec::new();
+
+        if self.current_token.kind != TokenKind::RightParen {
+            arguments.push(self.parse_expression()?);
+
+            while self.current_token.kind == TokenKind::Comma {
+                self.advance()?; // Skip ','
+                arguments.push(self.parse_expression()?);
+            }
+        }
+
+        if self.current_token.kind != TokenKind::RightParen {
+            self.missing_paren();
+            return Ok(arguments);
+        }
+
+        self.close_paren()?; // Skip ')'
+        Ok(arguments)
+    }
+
     fn parse_parentheses(&mut self) -> Result<ASTNode, CompilerError> {
-        self.advance()?;
+        self.open_paren()?; // Skip '('
         let node = self.parse_expression()?;
 
         if self.current_token.kind != TokenKind::RightParen {
-            return Err(CompilerError::Parse(ParserError::MissingRParen(
-                self.lexer.line,
-                self.lexer.pos,
-            )));
+            self.missing_paren();
+            return Ok(node);
         }
 
-        self.advance()?; // Skip ')'
+        self.close_paren()?; // Skip ')'
         Ok(node)
     }
 }
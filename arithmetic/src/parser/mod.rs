@@ -1,6 +1,11 @@
 pub mod factor;
+pub mod statement;
 
-use crate::ast::ASTNode;
+use crate::ast::{
+    program::{Program, Statement},
+    ASTNode,
+};
+use crate::builtins::Builtin;
 use crate::errors::{parser::ParserError, CompilerError};
 use crate::lexer::Lexer;
 use crate::lexer::{
@@ -8,32 +13,175 @@ use crate::lexer::{
     token::{Token, TokenKind},
 };
 
+/// Recursive descent parser. Syntax errors are collected and parsing goes on,
+/// so the whole input is checked in one pass.
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Token,
+    paren_depth: usize,
+    /// Newline skipped inside parentheses right before the current token
+    skipped_newline: Option<Token>,
+    /// Token put back when the parser returns to a skipped newline
+    pending: Option<Token>,
+    errors: Vec<CompilerError>,
+    /// Start of the token of the last error, follow-up errors there are dropped
+    last_error_start: Option<usize>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(mut lexer: Lexer<'a>) -> Result<Self, CompilerError> {
-        let current_token = lexer.get_next_token()?;
-        Ok(Parser {
+    pub fn new(lexer: Lexer<'a>) -> Result<Self, CompilerError> {
+        let mut parser = Parser {
             lexer,
-            current_token,
-        })
+            current_token: Token::new(TokenKind::Eof, TextSpan::new(0, 0, String::new(), 1, 0)),
+            paren_depth: 0,
+            skipped_newline: None,
+            pending: None,
+            errors: Vec::new(),
+            last_error_start: None,
+        };
+        parser.current_token = parser.next_token();
+        Ok(parser)
     }
 
     #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.lexer.reset();
-        self.current_token = self.lexer.get_next_token().unwrap();
+        self.paren_depth = 0;
+        self.skipped_newline = None;
+        self.pending = None;
+        self.errors.clear();
+        self.last_error_start = None;
+        self.current_token = self.next_token();
     }
 
     // Parser Advance method: Calls the lexer to get the next token
+    // Newlines inside parentheses do not end a statement and are skipped.
     pub fn advance(&mut self) -> Result<(), CompilerError> {
-        self.current_token = self.lexer.get_next_token()?;
+        self.skipped_newline = None;
+        self.current_token = self.next_token();
+        while self.paren_depth > 0 && self.current_token.kind == TokenKind::Newline {
+            let token = self.next_token();
+            let newline = std::mem::replace(&mut self.current_token, token);
+            self.skipped_newline.get_or_insert(newline);
+        }
+        Ok(())
+    }
+
+    /// Next valid token. Lexer errors are recorded and the invalid input is skipped.
+    fn next_token(&mut self) -> Token {
+        if let Some(token) = self.pending.take() {
+            return token;
+        }
+
+        let mut skipped = false;
+        loop {
+            match self.lexer.get_next_token() {
+                Ok(token) => {
+                    // The parser error caused by the missing token is not worth reporting
+                    if skipped {
+                        self.last_error_start = Some(token.span.start);
+                    }
+                    return token;
+                }
+                Err(error) => {
+                    self.errors.push(error);
+                    skipped = true;
+                }
+            }
+        }
+    }
+
+    /// Returns the token after the current one without consuming it
+    pub fn peek(&self) -> Result<Token, CompilerError> {
+        if let Some(token) = &self.pending {
+            return Ok(token.clone());
+        }
+
+        let mut lexer = self.lexer.clone();
+        loop {
+            if let Ok(token) = lexer.get_next_token() {
+                return Ok(token);
+            }
+        }
+    }
+
+    /// Records a syntax error at the current token and keeps parsing.
+    /// A line that can not continue the open parentheses starts a new statement:
+    /// the parser goes back to the newline and reports the missing ')' there instead.
+    fn report(&mut self, error: CompilerError) {
+        if let Some(newline) = self.skipped_newline.take() {
+            let token = std::mem::replace(&mut self.current_token, newline);
+            self.pending = Some(token);
+            self.paren_depth = 0;
+            return self.report(CompilerError::Parse(ParserError::MissingRParen(
+                self.current_token.span.clone(),
+            )));
+        }
+
+        let start = self.current_token.span.start;
+        if self.last_error_start != Some(start) {
+            self.errors.push(error);
+            self.last_error_start = Some(start);
+        }
+    }
+
+    /// Panic mode: skips tokens until one that an expression can continue from.
+    /// A newline ends the statement even inside parentheses opened before the error.
+    fn synchronize(&mut self) -> Result<(), CompilerError> {
+        while !matches!(
+            self.current_token.kind,
+            TokenKind::RightParen
+                | TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Multiply
+                | TokenKind::Divide
+                | TokenKind::Mod
+                | TokenKind::Div
+                | TokenKind::Power
+                | TokenKind::Comma
+                | TokenKind::Equals
+                | TokenKind::Newline
+                | TokenKind::Semicolon
+                | TokenKind::Eof
+        ) {
+            self.skipped_newline = None;
+            self.current_token = self.next_token();
+        }
+
+        if self.current_token.kind == TokenKind::Newline {
+            self.paren_depth = 0;
+        }
         Ok(())
     }
 
+    /// The parsed value if there were no errors, otherwise all of the errors
+    fn finish<T>(&mut self, value: T) -> Result<T, CompilerError> {
+        match CompilerError::from_errors(std::mem::take(&mut self.errors)) {
+            Some(error) => Err(error),
+            None => Ok(value),
+        }
+    }
+
+    /// Skips '(' and starts ignoring newlines
+    fn open_paren(&mut self) -> Result<(), CompilerError> {
+        self.paren_depth += 1;
+        self.advance()
+    }
+
+    /// Skips ')'
+    fn close_paren(&mut self) -> Result<(), CompilerError> {
+        self.paren_depth = self.paren_depth.saturating_sub(1);
+        self.advance()
+    }
+
+    /// Closes the group when ')' is missing: reports it without consuming anything
+    fn missing_paren(&mut self) {
+        self.paren_depth = self.paren_depth.saturating_sub(1);
+        self.report(CompilerError::Parse(ParserError::MissingRParen(
+            self.current_token.span.clone(),
+        )));
+    }
+
     /// RDP starting point:
     pub fn parse_expression(&mut self) -> Result<ASTNode, CompilerError> {
         let mut node = self.parse_term()?; // Start with parsing a term
@@ -89,3 +237,88 @@ impl<'a> Parser<'a> {
         Ok(node)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::token::Num;
+
+    /// Code and line:column, as shown in the diagnostics, of every error of the input
+    fn errors(input: &str) -> Vec<(&'static str, usize, usize)> {
+        let mut parser = Parser::new(Lexer::new(input)).unwrap();
+        let (_, errors) = parser.parse_program_partial();
+        errors
+            .iter()
+            .map(|error| {
+                let span = error.span().expect("syntax errors have a span");
+                (error.code(), span.line, span.column + 1)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unclosed_call_ends_at_the_newline() {
+        assert_eq!(errors("a := sin(3\nb := 4"), [("E0203", 1, 11)]);
+    }
+
+    #[test]
+    fn every_line_reports_its_own_error() {
+        let input = "1 + * 2\nsin(3\n4 $ 5\nx := ;\n(1+2))";
+        assert_eq!(
+            errors(input),
+            [
+                ("E0201", 1, 5),
+                ("E0203", 2, 6),
+                ("E0102", 3, 3),
+                ("E0201", 4, 6),
+                ("E0201", 5, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn newline_after_an_error_inside_parentheses() {
+        assert_eq!(
+            errors("a := sin(3 4 5\nb := 4"),
+            [("E0204", 1, 12), ("E0203", 1, 15)]
+        );
+        assert_eq!(
+            errors("a := (1 + (2\n\nb := 4\n)"),
+            [("E0203", 1, 13), ("E0202", 4, 1)]
+        );
+    }
+
+    #[test]
+    fn expressions_may_continue_on_the_next_line_inside_parentheses() {
+        assert_eq!(errors("max(3\n+ 4,\n 2)\n(1 +\n2) * 2"), []);
+    }
+
+    #[test]
+    fn unexpected_tokens_are_shown_as_written() {
+        let messages = |input: &str| -> Vec<String> {
+            let mut parser = Parser::new(Lexer::new(input)).unwrap();
+            let (_, errors) = parser.parse_program_partial();
+            errors.iter().map(|error| error.message()).collect()
+        };
+        assert_eq!(messages("1 + * 2"), ["Unexpected token '*'"]);
+        assert_eq!(messages("x = 1 = 2"), ["Unexpected token '='"]);
+        assert_eq!(messages("f(x) := x, 2"), ["Unexpected token ','"]);
+
+        let unexpected = |kind: TokenKind, literal: &str| {
+            let span = TextSpan::new(0, literal.len(), literal.to_string(), 1, 0);
+            ParserError::UnexpectedToken(kind, span).message()
+        };
+        assert_eq!(
+            unexpected(TokenKind::Number(Num::Integer(2)), "2"),
+            "Unexpected token '2'"
+        );
+        assert_eq!(
+            unexpected(TokenKind::Number(Num::Float(2.5)), "2.5"),
+            "Unexpected token '2.5'"
+        );
+        assert_eq!(
+            unexpected(TokenKind::Identifier("y".to_string()), "y"),
+            "Unexpected token 'y'"
+        );
+    }
+}
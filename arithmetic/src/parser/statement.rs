@@ -0,0 +1,172 @@
+use super::{ASTNode, CompilerError, Parser, ParserError, Program, Statement, TokenKind};
+
+impl<'a> Parser<'a> {
+    /// Parses statements separated by newlines or ';' until the end of the input.
+    /// Fails with every syntax error of the input.
+    pub fn parse_program(&mut self) -> Result<Program, CompilerError> {
+        let (program, errors) = self.parse_program_partial();
+        self.errors = errors;
+        self.finish(program)
+    }
+
+    /// Parses the whole input even when it has errors. Invalid parts of the tree
+    /// are `ASTNode::Error` placeholders and the errors are returned in source order.
+    pub fn parse_program_partial(&mut self) -> (Program, Vec<CompilerError>) {
+        let mut statements = Vec::new();
+
+        loop {
+            self.paren_depth = 0;
+            let _ = self.skip_separators();
+            if self.current_token.kind == TokenKind::Eof {
+                break;
+            }
+
+            if let Ok(statement) = self.parse_statement() {
+                statements.push(statement);
+            }
+            let _ = self.expect_end_of_statement();
+        }
+
+        // An empty program is reported the same way as an empty expression
+        if statements.is_empty() {
+            let error = self.unexpected_token();
+            self.report(error);
+        }
+
+        (Program::new(statements), std::mem::take(&mut self.errors))
+    }
+
+    /// Parses exactly one expression. Anything left after it is an error.
+    pub fn parse_single_expression(&mut self) -> Result<ASTNode, CompilerError> {
+        self.skip_separators()?;
+        let node = self.parse_expression()?;
+
+        self.skip_separators()?;
+        if self.current_token.kind != TokenKind::Eof {
+            let error = self.unexpected_token();
+            self.report(error);
+        }
+
+        self.finish(node)
+    }
+
+    /// name := expression | name(params) := expression | expression = expression | expression
+    fn parse_statement(&mut self) -> Result<Statement, CompilerError> {
+        let starts_with_identifier = match &self.current_token.kind {
+            TokenKind::Identifier(name) => {
+                if self.peek()?.kind == TokenKind::Assign {
+                    let name = name.clone();
+                    self.advance()?; // Skip the identifier
+
+                    let span = self.current_token.span.clone();
+                    self.advance()?; // Skip ':='
+
+                    let expr = self.parse_expression()?;
+                    return Ok(Statement::Assign(name, expr, span));
+                }
+                true
+            }
+            _ => false,
+        };
+
+        let expr = self.parse_expression()?;
+
+        if self.current_token.kind == TokenKind::Equals {
+            let span = self.current_token.span.clone();
+            self.advance()?; // Skip '='
+
+            let rhs = self.parse_expression()?;
+            return Ok(Statement::Equation(expr, rhs, span));
+        }
+
+        if self.current_token.kind != TokenKind::Assign {
+            return Ok(Statement::Expression(expr));
+        }
+
+        // The left side was parsed as a call: f(x, y) := body
+        match expr {
+            ASTNode::FunctionCall(name, args, _) if starts_with_identifier => {
+                let params = self.parse_parameters(args)?;
+
+                let span = self.current_token.span.clone();
+                self.advance()?; // Skip ':='
+
+                let body = self.parse_expression()?;
+                Ok(Statement::FunctionDef(name, params, body, span))
+            }
+            // Only a name or a call can be assigned to, the rest of the line is skipped
+            _ => Ok(Statement::Expression(expr)),
+        }
+    }
+
+    /// Parameters of a function definition must be distinct variable names
+    fn parse_parameters(&mut self, args: Vec<ASTNode>) -> Result<Vec<String>, CompilerError> {
+        let mut params: Vec<String> = Vec::new();
+
+        for arg in args {
+            match arg {
+                ASTNode::Identifier(name, span) => {
+                    let name = name.to_lowercase();
+                    if params.contains(&name) {
+                        // Reported at the parameter, the token key would hide the next error
+                        self.errors
+                            .push(CompilerError::Parse(ParserError::InvalidParameter(
+                                name, span,
+                            )));
+                        continue;
+                    }
+                    params.push(name);
+                }
+                other => {
+                    let span = other.span().clone();
+                    self.errors
+                        .push(CompilerError::Parse(ParserError::InvalidParameter(
+                            span.literal.clone(),
+                            span,
+                        )));
+                }
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// A statement must be followed by a newline, ';' or the end of the input.
+    /// Anything else is reported and skipped up to the next statement.
+    fn expect_end_of_statement(&mut self) -> Result<(), CompilerError> {
+        if !self.at_end_of_statement() {
+            let error = self.unexpected_token();
+            self.report(error);
+
+            while !self.at_end_of_statement() {
+                self.advance()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn at_end_of_statement(&self) -> bool {
+        matches!(
+            self.current_token.kind,
+            TokenKind::Newline | TokenKind::Semicolon | TokenKind::Eof
+        )
+    }
+
+    /// Skips empty lines and ';'
+    fn skip_separators(&mut self) -> Result<(), CompilerError> {
+        while matches!(
+            self.current_token.kind,
+            TokenKind::Newline | TokenKind::Semicolon
+        ) {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    fn unexpected_token(&self) -> CompilerError {
+        CompilerError::Parse(ParserError::UnexpectedToken(
+            self.current_token.kind.clone(),
+            self.current_token.span.clone(),
+        ))
+    }
+}
@@ -3,8 +3,15 @@ pub mod prompt;
 pub mod rand;
 pub mod ultimate;
 
+use crate::ast::resolver::{PromptResolver, VariableResolver};
+use crate::ast::settings::EvalSettings;
+use crate::ast::var::VariableManager;
 use crate::ast::wrapper::ASTWrapper;
-use crate::ast::ASTNode;
+use crate::ast::{
+    program::{Program, Statement},
+    ASTNode,
+};
+use crate::errors::{diagnostic::Diagnostic, eval::EvaluationError, CompilerError};
 use crate::lexer::Lexer;
 use crate::parser::Parser;
 use print::lex_parse_input;
@@ -1,91 +1,396 @@
 #![allow(dead_code)]
-use super::{ASTNode, ASTWrapper, Lexer, Parser};
+use super::{
+    ASTNode, ASTWrapper, CompilerError, Diagnostic, EvalSettings, EvaluationError, Lexer, Parser,
+    Program, Statement, VariableManager, VariableResolver,
+};
+use crate::ast::root::RootConfig;
+use crate::codegen::{asm::AssemblyGenerator, CodeGenerator, Language};
+use crate::errors::codegen::CodegenError;
+use crate::errors::root::RootFinderError;
+use crate::ir::Function;
+use crate::vm::{machine::VirtualMachine, Bytecode};
+use std::fs;
 
-/// Lex, Parse input and return AST
-pub fn lex_parse_input(input: &str) -> Result<ASTNode, String> {
+/// Renderings selected on the command line. LaTeX and MathML are printed next to
+/// the usual output, JSON and DOT are machine readable and replace it.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Formats {
+    pub latex: bool,
+    pub mathml: bool,
+    pub json: bool,
+    pub dot: bool,
+}
+
+impl Formats {
+    /// Print every statement of the program in the selected formats
+    pub fn print_program(&self, program: &Program) {
+        if self.latex {
+            println!("LaTeX: {}", program.to_latex());
+        }
+        if self.mathml {
+            println!("MathML: {}", program.to_mathml());
+        }
+    }
+
+    /// Print the expression in the selected formats
+    pub fn print_node(&self, node: &ASTNode) {
+        if self.latex {
+            println!("LaTeX: {}", node.to_latex());
+        }
+        if self.mathml {
+            println!("MathML: {}", node.to_mathml());
+        }
+    }
+}
+
+/// Lex, Parse input and return AST. The input must be a single expression.
+pub fn lex_parse_input(input: &str) -> Result<ASTNode, CompilerError> {
+    let lexer = Lexer::new(input);
+
+    let mut parser = Parser::new(lexer)?;
+    parser.parse_single_expression()
+}
+
+/// Lex, Parse input that may contain assignments and several statements
+pub fn lex_parse_program(input: &str) -> Result<Program, CompilerError> {
+    let lexer = Lexer::new(input);
+
+    let mut parser = Parser::new(lexer)?;
+    parser.parse_program()
+}
+
+/// Lex, Parse the whole input even when it has errors. Returns the partial program
+/// with `<error>` placeholders and every error found.
+pub fn lex_parse_program_partial(input: &str) -> (Program, Vec<CompilerError>) {
     let lexer = Lexer::new(input);
 
-    let mut parser = Parser::new(lexer).unwrap();
-    match parser.parse_expression() {
-        Ok(ast) => Ok(ast),
-        Err(error) => Err(format!("{}", error)),
+    match Parser::new(lexer) {
+        Ok(mut parser) => parser.parse_program_partial(),
+        Err(e) => (Program::new(Vec::new()), vec![e]),
     }
 }
 
 /// print lexer output
-pub fn print_lexer(input: &str) {
+pub fn print_lexer(input: &str) -> Result<(), CompilerError> {
     let mut lexer = Lexer::new(input);
 
-    match lexer.stringify() {
-        Ok(tokens_string) => {
-            println!("Lexer Output. Token Stream:\n{}", tokens_string);
-        }
-        Err(e) => {
-            eprintln!("{}", e)
-        }
-    }
+    let tokens_string = lexer.stringify()?;
+    println!("Lexer Output. Token Stream:\n{}", tokens_string);
+    Ok(())
 }
 
-/// Prints AST from the input
-pub fn print_ast(input: &str) {
-    match lex_parse_input(input) {
-        Ok(ast) => println!("Ast Tree:\n{}", ast.stringify("".to_string(), false)),
-        Err(error) => eprintln!(" {}", error),
+/// print the token stream as JSON
+pub fn print_lexer_json(input: &str) -> Result<(), CompilerError> {
+    let mut lexer = Lexer::new(input);
+
+    println!("{}", lexer.jsonify()?);
+    Ok(())
+}
+
+/// Prints AST from the input, as JSON or DOT if selected.
+/// With syntax errors the partial tree is printed as well.
+pub fn print_ast(input: &str, formats: Formats) -> Result<(), CompilerError> {
+    let (program, errors) = lex_parse_program_partial(input);
+    if formats.json {
+        println!("{}", program.to_json());
+    } else if formats.dot {
+        print!("{}", program.to_dot());
+    } else if !program.statements.is_empty() {
+        println!("Ast Tree:\n{}", program.stringify());
+        formats.print_program(&program);
+    }
+
+    match CompilerError::from_errors(errors) {
+        Some(error) => Err(error),
+        None => Ok(()),
     }
 }
 
 /// Prints the Postfix notation of the AST Tree
-pub fn print_postfix(input: &str) {
-    match lex_parse_input(input) {
-        Ok(ast) => println!("Postfix Notation: {}", ast.postfix()),
-        Err(error) => eprintln!("{}", error),
+pub fn print_postfix(input: &str, formats: Formats) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    println!("Postfix Notation: {}", program.postfix());
+    formats.print_program(&program);
+    Ok(())
+}
+
+/// Prints the input with minimal parentheses
+pub fn print_infix(input: &str, formats: Formats) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    println!("Infix Notation: {}", program.infix());
+    formats.print_program(&program);
+    Ok(())
+}
+
+/// Print the result of the evaluation. Unknown variables are handled by the resolver.
+pub fn print_evaluation(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    color: bool,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    // The wrapper turns a trailing equation into lhs - rhs for roots and solve
+    if let Some(Statement::Equation(_, _, span)) = program.statements.last() {
+        let span = span.clone();
+        return Err(CompilerError::Eval(EvaluationError::EquationHasNoValue(
+            span,
+        )));
     }
+    let mut ast_wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    let result = ast_wrapper.eval_ast()?;
+    print_warnings(&ast_wrapper.warnings, input, color);
+    println!("Evaluation result: {}", result);
+    Ok(())
 }
 
-/// Print the result of the evaluation. gets input from user for variables.
-pub fn print_evaluation(input: &str) {
-    match lex_parse_input(input) {
-        Ok(ast) => {
-            let mut ast_wrapper = ASTWrapper::new(ast);
+/// Print the error, or every error of a Multiple, with the source line it points to
+pub fn print_error(error: &CompilerError, source: &str, color: bool) {
+    for error in error.errors() {
+        eprint!("{}", Diagnostic::error(error).render(source, color));
+    }
+}
 
-            match ast_wrapper.eval_ast() {
-                Ok(result) => println!("Evaluation result: {}", result),
-                Err(error) => eprintln!("Evaluation error: {:?}", error),
+/// Print the domain warnings collected in lenient mode to stderr
+pub fn print_warnings(warnings: &[EvaluationError], source: &str, color: bool) {
+    for warning in warnings.iter() {
+        eprint!("{}", Diagnostic::warning(warning).render(source, color));
+    }
+}
+
+/// Print the report of the roots of the given input, as JSON if selected.
+/// Without an interval a polynomial gets all its complex roots instead, anything else
+/// is an error.
+pub fn print_roots(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    (a, b): (Option<f64>, Option<f64>),
+    config: RootConfig,
+    json: bool,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let mut wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    if a.is_none() && b.is_none() {
+        if let Some(roots) = wrapper.polynomial_roots()? {
+            match json {
+                true => println!("{}", roots.to_json()),
+                false => println!("{}", roots),
             }
+            return Ok(());
         }
-        Err(error) => eprintln!("Parsing error: {}", error),
+        return Err(CompilerError::Root(RootFinderError::MissingInterval));
+    }
+
+    let scan = wrapper.roots_report(a, b, config)?;
+    match json {
+        true => println!("{}", scan.to_json()),
+        false => println!("{}", scan),
     }
+    Ok(())
 }
 
-/// Print the roots of the given input
-pub fn print_roots(input: &str) {
-    match lex_parse_input(input) {
-        Ok(ast) => {
-            let mut wrapper = ASTWrapper::new(ast);
-
-            // Calling the `find_all_roots_bisection` method
-            match wrapper.roots_string(None, None) {
-                Ok(roots) => {
-                    if roots.is_empty() {
-                        println!("No roots found in the given interval.");
-                    } else {
-                        println!("Roots found: {:?}", roots);
-                    }
-                }
-                Err(e) => eprintln!("Error finding roots: {}", e),
-            }
+/// Print the solutions of the equation of the input for unknown. The interval is only
+/// used when the root finder is needed.
+pub fn print_solve(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    unknown: Option<&str>,
+    (a, b): (Option<f64>, Option<f64>),
+    config: RootConfig,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let mut wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    println!("{}", wrapper.solve(unknown, a, b, config)?);
+    Ok(())
+}
+
+/// Plot the function and store it as image. output defaults to plots/plot_XXXX.png
+/// derivative adds f' with respect to the given variable to the image.
+pub fn print_plot(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    (a, b): (Option<f64>, Option<f64>),
+    output: Option<&str>,
+    derivative: Option<&str>,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let mut wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    match derivative {
+        Some(var) => wrapper.plot_with_derivative(var, a, b, output),
+        None => wrapper.plot(a, b, output),
+    }
+}
+
+/// Print the derivative of the input with respect to var
+pub fn print_derivative(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    var: &str,
+    formats: Formats,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    let derivative = wrapper.derivative(var)?;
+    println!("Derivative with respect to {}: {}", var, derivative.infix());
+    println!("Postfix Notation: {}", derivative.postfix());
+    formats.print_node(&derivative);
+    Ok(())
+}
+
+/// Print the simplified input
+pub fn print_simplified(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    formats: Formats,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let mut wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    wrapper.simplify();
+    println!("Simplified: {}", wrapper.ast_infix_string());
+    println!("Postfix Notation: {}", wrapper.ast_postfix_string());
+    formats.print_node(&wrapper.ast);
+    Ok(())
+}
+
+/// Prints the IR of the input and the code after every optimization pass
+pub fn print_ir(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    let function = Function::lower(&wrapper.ast, &wrapper.vars, settings.angle_mode)?;
+    print!("{}", function.optimization_dump());
+    Ok(())
+}
+
+/// Compile the input to bytecode. It is written to output, without one the
+/// disassembly is printed.
+pub fn print_bytecode(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    output: Option<&str>,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    let bytecode = wrapper.bytecode()?.with_source(input);
+    match output {
+        Some(path) => {
+            bytecode.save(path)?;
+            println!("Bytecode written to {}", path);
         }
-        Err(error) => eprintln!("{}", error),
+        None => print!("{}", bytecode.disassemble()),
     }
+    Ok(())
 }
 
-/// Plot the function and store it as image.
-pub fn print_plot(input: &str) {
-    match lex_parse_input(input) {
-        Ok(ast) => {
-            let mut wrapper = ASTWrapper::new(ast);
-            let _ = wrapper.plot(None, None);
+/// Print the listing of a bytecode file
+pub fn print_disassembly(bytecode: &Bytecode) -> Result<(), CompilerError> {
+    print!("{}", bytecode.disassemble());
+    Ok(())
+}
+
+/// Run a bytecode file. Variables come from vars, then the values stored at
+/// compile time, then the resolver.
+pub fn print_execution(
+    bytecode: &Bytecode,
+    mut vars: VariableManager,
+    mut resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    color: bool,
+) -> Result<(), CompilerError> {
+    let values = bytecode.bind(&mut vars, resolver.as_mut(), None)?;
+
+    let mut machine = VirtualMachine::new().with_settings(settings);
+    let result = machine.run(bytecode, &values)?;
+    print_warnings(&machine.warnings, bytecode.source(), color);
+    println!("Evaluation result: {}", result);
+    Ok(())
+}
+
+/// Print the expression as a C or Rust function, or write it to output
+pub fn print_codegen(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    (language, name): (Language, &str),
+    output: Option<&str>,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+
+    let source = CodeGenerator::new(&wrapper.vars, language)
+        .with_angle_mode(settings.angle_mode)
+        .with_name(name)
+        .generate(&wrapper.ast)?;
+    match output {
+        Some(path) => {
+            fs::write(path, source).map_err(|e| {
+                CompilerError::Codegen(CodegenError::Io(format!(
+                    "unable to write '{}': {}",
+                    path, e
+                )))
+            })?;
+            println!("{} source written to {}", language, path);
+        }
+        None => print!("{}", source),
+    }
+    Ok(())
+}
+
+/// Print the stages of the assembly backend: the AST, the optimized three-address code and
+/// the x86-64 assembly. output is the path of the .s file.
+pub fn print_assembly(
+    input: &str,
+    vars: VariableManager,
+    resolver: Box<dyn VariableResolver>,
+    settings: EvalSettings,
+    name: &str,
+    output: Option<&str>,
+) -> Result<(), CompilerError> {
+    let program = lex_parse_program(input)?;
+    let mut wrapper = ASTWrapper::from_program(program, vars, resolver, settings)?;
+    println!("{}", wrapper.ast_string());
+
+    let function = Function::lower(&wrapper.ast, &wrapper.vars, settings.angle_mode)?.optimized();
+    println!("{}", function);
+
+    let assembly = AssemblyGenerator::new(name).generate(&function)?;
+    match output {
+        Some(path) => {
+            fs::write(path, assembly).map_err(|e| {
+                CompilerError::Codegen(CodegenError::Io(format!(
+                    "unable to write '{}': {}",
+                    path, e
+                )))
+            })?;
+            println!("Assembly written to {}", path);
         }
-        Err(error) => eprintln!("{}", error),
+        None => print!("{}", assembly),
     }
+    Ok(())
 }
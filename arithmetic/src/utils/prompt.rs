@@ -1,19 +1,22 @@
 use std::io::{self, Write};
 
-// Get Input from user and convert it into f64
-pub fn get_and_parse_user_input(name: &str) -> f64 {
+// Get Input from user and convert it into f64. None when stdin is closed.
+pub fn get_and_parse_user_input(name: &str) -> Option<f64> {
     loop {
         println!("\nPlease Enter the value for {}:", name);
         print!("> ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => {}
+        }
 
         let trimmed_input = input.trim();
 
         match trimmed_input.parse::<f64>() {
-            Ok(value) => return value,
+            Ok(value) => return Some(value),
             Err(_) => println!("Invalid input. Please enter a valid number."),
         }
     }
@@ -1,13 +1,21 @@
-use super::{get_and_parse_user_input, lex_parse_input, print::print_lexer, ASTWrapper};
+use super::{
+    get_and_parse_user_input, lex_parse_input,
+    print::{print_error, print_lexer},
+    ASTWrapper, PromptResolver,
+};
+use crate::ast::root::RootConfig;
+use crate::errors::{root::RootFinderError, CompilerError};
 
 /// lexes -> print tokens -> parses -> print tree -> print postfix --
 /// --> evaluate(get variable values from user and evaluate the ast).
-pub fn ultimate_ast_postfix_eval(input: &str) {
-    print_lexer(input);
+pub fn ultimate_ast_postfix_eval(input: &str, color: bool) {
+    if let Err(e) = print_lexer(input) {
+        print_error(&e, input, color);
+    }
 
     match lex_parse_input(input) {
         Ok(ast) => {
-            let mut wrapper = ASTWrapper::new(ast);
+            let mut wrapper = ASTWrapper::new(ast, Box::new(PromptResolver));
 
             let ast_string = wrapper.ast_string();
             println!("{}", ast_string);
@@ -20,34 +28,36 @@ pub fn ultimate_ast_postfix_eval(input: &str) {
                     println!("Evaluation result: {}", result);
                 }
                 Err(e) => {
-                    eprintln!("{}", e);
-                    return;
+                    print_error(&e, input, color);
                 }
             }
         }
-        Err(error) => eprintln!("{}", error),
+        Err(error) => print_error(&error, input, color),
     }
 }
 
 /// Returns Roots and plots the function.
-pub fn ultimate_root_plot(input: &str) {
+pub fn ultimate_root_plot(input: &str, color: bool) {
     match lex_parse_input(input) {
         Ok(ast) => {
-            let mut wrapper = ASTWrapper::new(ast);
+            let mut wrapper = ASTWrapper::new(ast, Box::new(PromptResolver));
 
-            let a = get_and_parse_user_input("a");
-            let b = get_and_parse_user_input("b");
+            let (Some(a), Some(b)) = (get_and_parse_user_input("a"), get_and_parse_user_input("b"))
+            else {
+                let error = CompilerError::Root(RootFinderError::MissingInterval);
+                return print_error(&error, input, color);
+            };
 
-            match wrapper.roots_string(Some(a), Some(b)) {
-                Ok(s) => println!("Roots: {}", s),
-                Err(e) => eprintln!("{}", e),
+            match wrapper.roots_string(Some(a), Some(b), RootConfig::default()) {
+                Ok(s) => println!("{}", s),
+                Err(e) => print_error(&e, input, color),
             }
 
-            match wrapper.plot(Some(a), Some(b)) {
+            match wrapper.plot(Some(a), Some(b), None) {
                 Ok(_) => println!("Function plot generated successfully."),
-                Err(e) => eprintln!("{}", e),
+                Err(e) => print_error(&e, input, color),
             }
         }
-        Err(error) => eprintln!("Error parsing input: {}", error),
+        Err(error) => print_error(&error, input, color),
     }
 }
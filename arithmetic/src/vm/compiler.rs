@@ -0,0 +1,214 @@
+use super::{ASTNode, Bytecode, Instruction, Slot, TextSpan, VariableManager, FORMAT, VERSION};
+use crate::builtins::BUILTINS;
+use crate::errors::{eval::EvaluationError, CompilerError};
+use crate::lexer::token::{Num, TokenKind};
+use std::f64::consts::{E, PI};
+
+/// Translates the AST into bytecode in postfix order. User functions are inlined,
+/// built-in functions get an entry of the function table and variables a slot,
+/// so nothing is looked up by name when the code runs.
+pub struct BytecodeCompiler<'a> {
+    vars: &'a VariableManager,
+    constants: Vec<f64>,
+    slots: Vec<Slot>,
+    /// Position in BUILTINS of the functions in the table
+    builtins: Vec<usize>,
+    code: Vec<Instruction>,
+    spans: Vec<TextSpan>,
+}
+
+impl<'a> BytecodeCompiler<'a> {
+    pub fn new(vars: &'a VariableManager) -> Self {
+        Self {
+            vars,
+            constants: Vec::new(),
+            slots: Vec::new(),
+            builtins: Vec::new(),
+            code: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn compile(mut self, node: &ASTNode) -> Result<Bytecode, CompilerError> {
+        self.compile_node(&node.inline_functions(self.vars)?)?;
+
+        Ok(Bytecode {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            source: String::new(),
+            constants: self.constants,
+            slots: self.slots,
+            functions: self
+                .builtins
+                .iter()
+                .map(|&index| BUILTINS[index].name.to_string())
+                .collect(),
+            builtins: self.builtins,
+            code: self.code,
+            spans: self.spans,
+        })
+    }
+
+    fn compile_node(&mut self, node: &ASTNode) -> Result<(), CompilerError> {
+        match node {
+            ASTNode::Number(Num::Integer(i), span) => self.push_constant(*i as f64, span),
+            ASTNode::Number(Num::Float(f), span) => self.push_constant(*f, span),
+            ASTNode::Mantissa(value, span) => {
+                let value = value.parse::<f64>().map_err(|_| {
+                    CompilerError::Eval(EvaluationError::InvalidMantissa(span.clone()))
+                })?;
+                self.push_constant(value, span);
+            }
+            ASTNode::Constant(TokenKind::Pi, span) => self.push_constant(PI, span),
+            ASTNode::Constant(TokenKind::Euler, span) => self.push_constant(E, span),
+            ASTNode::Constant(_, span) => {
+                return Err(CompilerError::Eval(EvaluationError::InvalidConstant(
+                    span.clone(),
+                )))
+            }
+            ASTNode::Identifier(id, span) => {
+                let slot = self.slot(id, span);
+                self.emit(Instruction::LoadVar(slot), span);
+            }
+            ASTNode::BinaryOp(left, op, right, span) => {
+                self.compile_node(left)?;
+                self.compile_node(right)?;
+
+                let instruction = match op {
+                    TokenKind::Plus => Instruction::Add,
+                    TokenKind::Minus => Instruction::Sub,
+                    TokenKind::Multiply => Instruction::Mul,
+                    TokenKind::Divide => Instruction::Div,
+                    TokenKind::Mod => Instruction::Mod,
+                    TokenKind::Div => Instruction::IntDiv,
+                    TokenKind::Power => Instruction::Pow,
+                    _ => {
+                        return Err(CompilerError::Eval(
+                            EvaluationError::UnsupportedBinaryOperator(
+                                op.to_string(),
+                                span.clone(),
+                            ),
+                        ))
+                    }
+                };
+                self.emit(instruction, span);
+            }
+            ASTNode::UnaryOp(op, expr, span) => {
+                self.compile_node(expr)?;
+
+                match op {
+                    TokenKind::Minus => self.emit(Instruction::Neg, span),
+                    TokenKind::Plus => {}
+                    _ => {
+                        return Err(CompilerError::Eval(
+                            EvaluationError::UnsupportedUnaryOperator(op.to_string(), span.clone()),
+                        ))
+                    }
+                }
+            }
+            ASTNode::FunctionCall(func, args, span) => {
+                for arg in args.iter() {
+                    self.compile_node(arg)?;
+                }
+
+                let builtin = resolve_function(self.vars, func, args.len(), span)?;
+                let function = self.function(builtin);
+                self.emit(Instruction::Call(function, args.len() as u32), span);
+            }
+            // Partial trees from error recovery can not be compiled
+            ASTNode::Error(span) => return Err(CompilerError::GenericError(span.clone())),
+        }
+
+        Ok(())
+    }
+
+    /// Equal constants share one entry of the pool
+    fn push_constant(&mut self, value: f64, span: &TextSpan) {
+        let index = match self
+            .constants
+            .iter()
+            .position(|constant| constant.to_bits() == value.to_bits())
+        {
+            Some(index) => index,
+            None => {
+                self.constants.push(value);
+                self.constants.len() - 1
+            }
+        };
+        self.emit(Instruction::PushConst(index as u32), span);
+    }
+
+    /// Slot of the variable, names are case insensitive
+    fn slot(&mut self, id: &str, span: &TextSpan) -> u32 {
+        let name = id.to_lowercase();
+        let index = match self.slots.iter().position(|slot| slot.name == name) {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    name,
+                    value: None,
+                    span: span.clone(),
+                });
+                self.slots.len() - 1
+            }
+        };
+        index as u32
+    }
+
+    /// Entry of the built-in function in the function table
+    fn function(&mut self, builtin: usize) -> u32 {
+        let index = match self.builtins.iter().position(|&index| index == builtin) {
+            Some(index) => index,
+            None => {
+                self.builtins.push(builtin);
+                self.builtins.len() - 1
+            }
+        };
+        index as u32
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: &TextSpan) {
+        self.code.push(instruction);
+        self.spans.push(span.clone());
+    }
+}
+
+/// Index of the built-in function. User functions that are still calls after
+/// inlining are called with the wrong number of arguments.
+pub fn resolve_function(
+    vars: &VariableManager,
+    func: &str,
+    arg_count: usize,
+    span: &TextSpan,
+) -> Result<usize, CompilerError> {
+    if let Some(function) = vars.get_function(func) {
+        return Err(CompilerError::Eval(EvaluationError::ArityMismatch(
+            func.to_string(),
+            function.params.len().to_string(),
+            arg_count,
+            span.clone(),
+        )));
+    }
+
+    let index = BUILTINS
+        .iter()
+        .position(|builtin| builtin.name == func)
+        .ok_or_else(|| {
+            CompilerError::Eval(EvaluationError::UndefinedFunction(
+                func.to_string(),
+                span.clone(),
+            ))
+        })?;
+
+    let builtin = &BUILTINS[index];
+    if !builtin.accepts(arg_count) {
+        return Err(CompilerError::Eval(EvaluationError::ArityMismatch(
+            func.to_string(),
+            builtin.expected_args(),
+            arg_count,
+            span.clone(),
+        )));
+    }
+
+    Ok(index)
+}
@@ -0,0 +1,95 @@
+use super::{Bytecode, Instruction};
+
+/// Human readable listing of the bytecode:
+///
+/// ```text
+/// ; arithmetic bytecode v2
+/// ; source: sin(x)^2 + y
+/// constants:
+///     #0    2
+/// slots:
+///     $0    x
+///     $1    y
+/// functions:
+///     0     sin
+/// code:
+///     0000  LOAD_VAR    $0      x           1:5
+///     0001  CALL        0/1     sin/1       1:1
+///     ...
+/// ```
+impl Bytecode {
+    pub fn disassemble(&self) -> String {
+        let mut out = format!("; {} v{}\n", self.format.replace('-', " "), self.version);
+        if !self.source.trim().is_empty() {
+            let source: Vec<&str> = self.source.lines().map(|line| line.trim()).collect();
+            out.push_str(&format!("; source: {}\n", source.join(" ")));
+        }
+
+        out.push_str("constants:\n");
+        for (index, constant) in self.constants.iter().enumerate() {
+            out.push_str(&format!("    {:<6}{}\n", format!("#{}", index), constant));
+        }
+
+        out.push_str("slots:\n");
+        for (index, slot) in self.slots.iter().enumerate() {
+            let default = match slot.value {
+                Some(value) => format!(" = {}", value),
+                None => String::new(),
+            };
+            out.push_str(&format!(
+                "    {:<6}{}{}\n",
+                format!("${}", index),
+                slot.name,
+                default
+            ));
+        }
+
+        out.push_str("functions:\n");
+        for (index, name) in self.functions.iter().enumerate() {
+            out.push_str(&format!("    {:<6}{}\n", index, name));
+        }
+
+        out.push_str("code:\n");
+        for (index, (instruction, span)) in self.code.iter().zip(self.spans.iter()).enumerate() {
+            let (operand, comment) = self.describe(instruction);
+            out.push_str(&format!(
+                "    {:04}  {:<12}{:<8}{:<12}{}:{}\n",
+                index,
+                instruction.mnemonic(),
+                operand,
+                comment,
+                span.line,
+                span.column + 1
+            ));
+        }
+
+        out
+    }
+
+    /// Operand and what it refers to
+    fn describe(&self, instruction: &Instruction) -> (String, String) {
+        match *instruction {
+            Instruction::PushConst(index) => (
+                format!("#{}", index),
+                self.constants
+                    .get(index as usize)
+                    .map(|constant| constant.to_string())
+                    .unwrap_or_default(),
+            ),
+            Instruction::LoadVar(slot) => (
+                format!("${}", slot),
+                self.slots
+                    .get(slot as usize)
+                    .map(|slot| slot.name.clone())
+                    .unwrap_or_default(),
+            ),
+            Instruction::Call(function, arg_count) => (
+                format!("{}/{}", function, arg_count),
+                self.builtin(function)
+                    .map(|builtin| format!("{}/{}", builtin.name, arg_count))
+                    .unwrap_or_default(),
+            ),
+            _ => (String::new(), String::new()),
+        }
+    }
+}
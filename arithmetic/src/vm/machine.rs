@@ -0,0 +1,137 @@
+use super::{Bytecode, EvalSettings, EvaluationError, Instruction, TextSpan};
+use crate::ast::eval::{divide, domain_problem, integer_divide, modulo, report_domain_problem};
+use crate::builtins::{power_check, Builtin};
+use crate::errors::{bytecode::BytecodeError, CompilerError};
+
+/// Runs bytecode on a stack of floats. Errors and warnings are the same as the ones
+/// of the Evaluator on the tree.
+pub struct VirtualMachine {
+    stack: Vec<f64>,
+    pub settings: EvalSettings,
+    /// Domain problems found in lenient mode
+    pub warnings: Vec<EvaluationError>,
+}
+
+impl VirtualMachine {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            settings: EvalSettings::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Sets the angle unit and the domain mode. Degrees and lenient by default.
+    pub fn with_settings(mut self, settings: EvalSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Runs the code with the slot values returned by Bytecode::bind
+    pub fn run(&mut self, bytecode: &Bytecode, values: &[f64]) -> Result<f64, CompilerError> {
+        self.stack.clear();
+
+        for (index, (instruction, span)) in
+            bytecode.code.iter().zip(bytecode.spans.iter()).enumerate()
+        {
+            let result = match *instruction {
+                Instruction::PushConst(index) => bytecode.constants[index as usize],
+                Instruction::LoadVar(slot) => values[slot as usize],
+                Instruction::Add => {
+                    let (left, right) = self.pop_operands()?;
+                    left + right
+                }
+                Instruction::Sub => {
+                    let (left, right) = self.pop_operands()?;
+                    left - right
+                }
+                Instruction::Mul => {
+                    let (left, right) = self.pop_operands()?;
+                    left * right
+                }
+                Instruction::Div => {
+                    let (left, right) = self.pop_operands()?;
+                    divide(left, right, span)?
+                }
+                Instruction::Mod => {
+                    let (left, right) = self.pop_operands()?;
+                    modulo(left, right, span)?
+                }
+                Instruction::IntDiv => {
+                    let (left, right) = self.pop_operands()?;
+                    integer_divide(left, right, span)?
+                }
+                Instruction::Pow => {
+                    let (left, right) = self.pop_operands()?;
+                    let result = left.powf(right);
+                    let offending = power_check(left, right);
+                    self.check_result("^", &[left, right], result, offending, span)?;
+                    result
+                }
+                Instruction::Neg => -self.pop()?,
+                Instruction::Call(function, arg_count) => {
+                    let builtin = bytecode.builtin(function).ok_or(CompilerError::Bytecode(
+                        BytecodeError::InvalidInstruction(index),
+                    ))?;
+                    self.call(builtin, arg_count as usize, span)?
+                }
+            };
+            self.stack.push(result);
+        }
+
+        self.pop()
+    }
+
+    /// Applies a built-in function to the arguments on top of the stack
+    fn call(
+        &mut self,
+        builtin: &Builtin,
+        arg_count: usize,
+        span: &TextSpan,
+    ) -> Result<f64, CompilerError> {
+        let start = self
+            .stack
+            .len()
+            .checked_sub(arg_count)
+            .ok_or(CompilerError::Bytecode(BytecodeError::StackMismatch))?;
+
+        // The arguments are read in place, no vector per call
+        let args = &self.stack[start..];
+        let result = (builtin.apply)(args, self.settings.angle_mode);
+        let offending = (builtin.check)(args, self.settings.angle_mode);
+        let problem = domain_problem(builtin.name, args, result, offending, span);
+        self.stack.truncate(start);
+
+        if let Some(error) = problem {
+            report_domain_problem(self.settings, &mut self.warnings, error)?;
+        }
+        Ok(result)
+    }
+
+    fn check_result(
+        &mut self,
+        func: &str,
+        args: &[f64],
+        result: f64,
+        offending: Option<f64>,
+        span: &TextSpan,
+    ) -> Result<(), CompilerError> {
+        match domain_problem(func, args, result, offending, span) {
+            Some(error) => report_domain_problem(self.settings, &mut self.warnings, error),
+            None => Ok(()),
+        }
+    }
+
+    /// The right operand is on top
+    fn pop_operands(&mut self) -> Result<(f64, f64), CompilerError> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        Ok((left, right))
+    }
+
+    fn pop(&mut self) -> Result<f64, CompilerError> {
+        self.stack
+            .pop()
+            .ok_or(CompilerError::Bytecode(BytecodeError::StackMismatch))
+    }
+}
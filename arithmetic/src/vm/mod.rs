@@ -0,0 +1,380 @@
+pub mod compiler;
+pub mod disasm;
+pub mod machine;
+
+use crate::ast::{
+    resolver::VariableResolver, settings::EvalSettings, var::VariableManager, ASTNode,
+};
+use crate::builtins::{Builtin, BUILTINS};
+use crate::errors::{bytecode::BytecodeError, eval::EvaluationError, CompilerError};
+use crate::lexer::{span::TextSpan, token::Num};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use compiler::BytecodeCompiler;
+use machine::VirtualMachine;
+
+/// Written into every bytecode file so other JSON is rejected
+const FORMAT: &str = "arithmetic-bytecode";
+
+/// Version of the file format, increased when the instruction set changes.
+/// Version 2 calls built-in functions by name instead of their position in BUILTINS.
+const VERSION: u32 = 2;
+
+/// Instructions of the stack machine. Binary operators pop the right operand first.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Instruction {
+    /// Pushes the constant with the given index
+    PushConst(u32),
+    /// Pushes the value bound to the slot
+    LoadVar(u32),
+    Add,
+    Sub,
+    Mul,
+    /// `/`, fails on a zero divisor
+    Div,
+    /// `mod`, integer operands only
+    Mod,
+    /// `div`, floor of the quotient of integer operands
+    IntDiv,
+    Pow,
+    Neg,
+    /// Calls the built-in function with the given index in the function table on the
+    /// last arguments of the stack
+    Call(u32, u32),
+}
+
+impl Instruction {
+    /// Number of values the instruction pops and pushes
+    fn stack_effect(&self) -> (usize, usize) {
+        match self {
+            Instruction::PushConst(_) | Instruction::LoadVar(_) => (0, 1),
+            Instruction::Neg => (1, 1),
+            Instruction::Call(_, arg_count) => (*arg_count as usize, 1),
+            _ => (2, 1),
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::PushConst(_) => "PUSH_CONST",
+            Instruction::LoadVar(_) => "LOAD_VAR",
+            Instruction::Add => "ADD",
+            Instruction::Sub => "SUB",
+            Instruction::Mul => "MUL",
+            Instruction::Div => "DIV",
+            Instruction::Mod => "MOD",
+            Instruction::IntDiv => "INT_DIV",
+            Instruction::Pow => "POW",
+            Instruction::Neg => "NEG",
+            Instruction::Call(_, _) => "CALL",
+        }
+    }
+}
+
+/// A variable read by the code. Its value is bound before the code runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Slot {
+    /// Lower case name, as stored in the VariableManager
+    pub name: String,
+    /// Value the compiled program gave the variable, used when none is given at run time
+    pub value: Option<f64>,
+    /// First use in the source, for undefined variable errors
+    pub span: TextSpan,
+}
+
+/// Compiled expression: constant pool, variable slots and the instructions with
+/// the source location of each one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bytecode {
+    format: String,
+    version: u32,
+    /// Source text the spans point into
+    source: String,
+    constants: Vec<f64>,
+    slots: Vec<Slot>,
+    /// Names of the called built-in functions. Files keep the names, so adding or
+    /// reordering built-ins does not change the meaning of saved code.
+    functions: Vec<String>,
+    /// Position in BUILTINS of every function, resolved when compiling or loading
+    #[serde(skip)]
+    builtins: Vec<usize>,
+    code: Vec<Instruction>,
+    spans: Vec<TextSpan>,
+}
+
+impl Bytecode {
+    /// Compiles the expression. User functions of vars are inlined.
+    pub fn compile(node: &ASTNode, vars: &VariableManager) -> Result<Self, CompilerError> {
+        BytecodeCompiler::new(vars).compile(node)
+    }
+
+    /// Keeps the source text so errors of a loaded file can point into it
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.to_string();
+        self
+    }
+
+    /// Stores the current values of the variables as the defaults of their slots
+    pub fn with_values(mut self, vars: &VariableManager) -> Self {
+        for slot in self.slots.iter_mut() {
+            slot.value = vars.get_stored(&slot.name).map(|value| match value {
+                Num::Integer(i) => i as f64,
+                Num::Float(f) => f,
+            });
+        }
+        self
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Built-in function with the given index in the function table
+    pub fn builtin(&self, function: u32) -> Option<&'static Builtin> {
+        self.builtins
+            .get(function as usize)
+            .map(|&index| &BUILTINS[index])
+    }
+
+    /// Index of the slot of a variable
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        self.slots.iter().position(|slot| slot.name == name)
+    }
+
+    /// Values for the slots: known variables first, then the stored defaults, then the
+    /// resolver. The free variable is left unbound, the caller sets it before each run.
+    pub fn bind(
+        &self,
+        vars: &mut VariableManager,
+        resolver: &mut dyn VariableResolver,
+        free: Option<&str>,
+    ) -> Result<Vec<f64>, CompilerError> {
+        let free = free.map(|name| name.to_lowercase());
+        let mut values = Vec::with_capacity(self.slots.len());
+
+        for slot in self.slots.iter() {
+            let value = match (vars.get_stored(&slot.name), slot.value) {
+                _ if free.as_deref() == Some(slot.name.as_str()) => f64::NAN,
+                (None, Some(value)) => value,
+                _ => match vars.get(&slot.name, &slot.span, resolver)? {
+                    Num::Integer(i) => i as f64,
+                    Num::Float(f) => f,
+                },
+            };
+            values.push(value);
+        }
+
+        Ok(values)
+    }
+
+    /// Writes the bytecode as JSON
+    pub fn save(&self, path: &str) -> Result<(), CompilerError> {
+        let json = serde_json::to_string_pretty(self).expect("bytecode is always serializable");
+        fs::write(path, json).map_err(|e| {
+            CompilerError::Bytecode(BytecodeError::Io(format!(
+                "unable to write '{}': {}",
+                path, e
+            )))
+        })
+    }
+
+    /// Reads a file written by save and checks that it can be run safely
+    pub fn load(path: &str) -> Result<Self, CompilerError> {
+        let json = fs::read_to_string(path).map_err(|e| {
+            CompilerError::Bytecode(BytecodeError::Io(format!(
+                "unable to read '{}': {}",
+                path, e
+            )))
+        })?;
+
+        let mut bytecode: Self = serde_json::from_str(&json)
+            .map_err(|e| CompilerError::Bytecode(BytecodeError::InvalidFormat(e.to_string())))?;
+        bytecode.verify().map_err(CompilerError::Bytecode)?;
+        Ok(bytecode)
+    }
+
+    /// Resolves the names of the function table, an unknown name is an invalid file
+    fn link(&mut self) -> Result<(), BytecodeError> {
+        self.builtins = self
+            .functions
+            .iter()
+            .map(|name| {
+                BUILTINS
+                    .iter()
+                    .position(|builtin| builtin.name == name)
+                    .ok_or_else(|| {
+                        BytecodeError::InvalidFormat(format!("unknown function '{}'", name))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Checks the header, links the function table, checks that every operand is in
+    /// range and that the code leaves exactly one value on the stack
+    fn verify(&mut self) -> Result<(), BytecodeError> {
+        if self.format != FORMAT {
+            return Err(BytecodeError::InvalidFormat(format!(
+                "unknown format '{}'",
+                self.format
+            )));
+        }
+        if self.version != VERSION {
+            return Err(BytecodeError::UnsupportedVersion(self.version));
+        }
+        if self.spans.len() != self.code.len() {
+            return Err(BytecodeError::InvalidFormat(
+                "every instruction needs a span".to_string(),
+            ));
+        }
+        self.link()?;
+
+        let mut depth = 0;
+        for (index, instruction) in self.code.iter().enumerate() {
+            let valid = match *instruction {
+                Instruction::PushConst(constant) => (constant as usize) < self.constants.len(),
+                Instruction::LoadVar(slot) => (slot as usize) < self.slots.len(),
+                Instruction::Call(function, arg_count) => self
+                    .builtin(function)
+                    .is_some_and(|builtin| builtin.accepts(arg_count as usize)),
+                _ => true,
+            };
+            if !valid {
+                return Err(BytecodeError::InvalidInstruction(index));
+            }
+
+            let (pops, pushes) = instruction.stack_effect();
+            depth = match depth >= pops {
+                true => depth - pops + pushes,
+                false => return Err(BytecodeError::StackMismatch),
+            };
+        }
+
+        match depth {
+            1 => Ok(()),
+            _ => Err(BytecodeError::StackMismatch),
+        }
+    }
+}
+
+/// An expression compiled once and run for many values of x, used by roots and plots
+pub struct CompiledFunction {
+    bytecode: Bytecode,
+    values: Vec<f64>,
+    x_slot: Option<usize>,
+    machine: VirtualMachine,
+}
+
+impl CompiledFunction {
+    /// Compiles the expression and binds every variable except x
+    pub fn new(
+        node: &ASTNode,
+        vars: &mut VariableManager,
+        resolver: &mut dyn VariableResolver,
+        settings: EvalSettings,
+    ) -> Result<Self, CompilerError> {
+        Self::new_in(node, "x", vars, resolver, settings)
+    }
+
+    /// Compiles the expression as a function of var and binds every other variable
+    pub fn new_in(
+        node: &ASTNode,
+        var: &str,
+        vars: &mut VariableManager,
+        resolver: &mut dyn VariableResolver,
+        settings: EvalSettings,
+    ) -> Result<Self, CompilerError> {
+        let bytecode = Bytecode::compile(node, vars)?;
+        let values = bytecode.bind(vars, resolver, Some(var))?;
+
+        Ok(Self {
+            x_slot: bytecode.slot(var),
+            values,
+            bytecode,
+            machine: VirtualMachine::new().with_settings(settings),
+        })
+    }
+
+    /// f(x)
+    pub fn call(&mut self, x: f64) -> Result<f64, CompilerError> {
+        if let Some(slot) = self.x_slot {
+            self.values[slot] = x;
+        }
+        self.machine.run(&self.bytecode, &self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::resolver::MapOnlyResolver;
+    use crate::utils::print::lex_parse_input;
+
+    fn compile(input: &str) -> Bytecode {
+        let vars = VariableManager::new();
+        Bytecode::compile(&lex_parse_input(input).unwrap(), &vars).unwrap()
+    }
+
+    /// Saves the JSON, after edit, to a temporary file and loads it again
+    fn reload(
+        bytecode: &Bytecode,
+        name: &str,
+        edit: impl Fn(String) -> String,
+    ) -> Result<Bytecode, CompilerError> {
+        let file = format!("arithmetic-{}-{}.bc", name, std::process::id());
+        let path = std::env::temp_dir().join(file);
+        let path = path.to_str().unwrap();
+        let json = serde_json::to_string(bytecode).unwrap();
+        fs::write(path, edit(json)).unwrap();
+        let result = Bytecode::load(path);
+        fs::remove_file(path).unwrap();
+        result
+    }
+
+    fn run(bytecode: &Bytecode, x: f64) -> f64 {
+        let mut vars = VariableManager::new();
+        vars.set("x".to_string(), Num::Float(x));
+        let values = bytecode
+            .bind(&mut vars, &mut MapOnlyResolver, None)
+            .unwrap();
+        VirtualMachine::new().run(bytecode, &values).unwrap()
+    }
+
+    #[test]
+    fn functions_are_saved_by_name() {
+        let bytecode = compile("max(cos(x), sin(x), cos(2 * x))");
+        assert_eq!(bytecode.functions, ["cos", "sin", "max"]);
+        assert!(bytecode.code.contains(&Instruction::Call(2, 3)));
+
+        let loaded = reload(&bytecode, "by-name", |json| json).unwrap();
+        assert_eq!(run(&loaded, 30.0), run(&bytecode, 30.0));
+    }
+
+    #[test]
+    fn unknown_function_names_are_rejected() {
+        let bytecode = compile("sin(x)");
+        let result = reload(&bytecode, "unknown", |json| {
+            json.replace("\"sin\"", "\"sine\"")
+        });
+        assert!(matches!(
+            result,
+            Err(CompilerError::Bytecode(BytecodeError::InvalidFormat(_)))
+        ));
+    }
+
+    #[test]
+    fn files_of_the_first_version_are_rejected() {
+        let bytecode = compile("sin(x)");
+        let result = reload(&bytecode, "version", |json| {
+            json.replace("\"version\":2", "\"version\":1")
+        });
+        assert!(matches!(
+            result,
+            Err(CompilerError::Bytecode(BytecodeError::UnsupportedVersion(
+                1
+            )))
+        ));
+    }
+}